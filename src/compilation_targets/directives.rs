@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use crate::tokens::Directive;
+
+/*
+ * Handlers turning directives into the output of a compilation target. Handlers are looked up
+ * by directive name, directives without a handler get the target's default output.
+ */
+
+pub type DirectiveHandler<T> = Box<dyn Fn(&Directive) -> T>;
+
+pub struct DirectiveRegistry<T> {
+    handlers: HashMap<String, DirectiveHandler<T>>
+}
+
+impl<T> Default for DirectiveRegistry<T> {
+    fn default() -> Self {
+        DirectiveRegistry{handlers: HashMap::new()}
+    }
+}
+
+impl<T> DirectiveRegistry<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+     Register a handler for directives with the given name, replacing any previous handler.
+    */
+    pub fn register<F>(&mut self, name: &str, handler: F) -> &mut Self
+    where F: Fn(&Directive) -> T + 'static {
+        self.handlers.insert(name.to_string(), Box::new(handler));
+        return self;
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.handlers.contains_key(name)
+    }
+
+    /**
+     Output of the handler registered for the directive, None if there is no handler.
+    */
+    pub fn render(&self, directive: &Directive) -> Option<T> {
+        self.handlers.get(&directive.name).map(|handler| handler(directive))
+    }
+}
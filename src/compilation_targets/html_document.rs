@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use crate::front_matter::split_front_matter;
+use crate::parser::parse_md_str;
+use crate::slug::text_content;
+use crate::toc::{build_toc, toc_to_html};
+use crate::tokens::*;
+use crate::tree::TreeIndex;
+use super::to_html::{compile_all_with, html_attributes, HtmlOptions};
+
+/*
+ * Standalone HTML documents. The compiled fragment is put into a template where `{{name}}` is
+ * replaced by a variable: `title`, `content`, `toc`, `head` (meta tags and stylesheet links),
+ * `lang` and every front matter key. Unknown variables are left empty.
+ */
+
+pub const DEFAULT_TEMPLATE: &str = "<!DOCTYPE html>
+<html lang=\"{{lang}}\">
+<head>
+<meta charset=\"utf-8\">
+{{head}}<title>{{title}}</title>
+</head>
+<body>
+{{content}}
+</body>
+</html>
+";
+
+pub struct DocumentOptions {
+    pub html: HtmlOptions,
+    // Used as is instead of `DEFAULT_TEMPLATE` when set.
+    pub template: Option<String>,
+    // Used when the document has neither a front matter title nor a header.
+    pub default_title: String,
+    pub lang: String,
+    pub stylesheets: Vec<String>,
+    // Attributes of `<meta>` tags, eg. `name` and `content`.
+    pub meta: Vec<Attributes>
+}
+
+impl Default for DocumentOptions {
+    fn default() -> Self {
+        DocumentOptions{
+            html: HtmlOptions::default(),
+            template: None,
+            default_title: String::new(),
+            lang: "en".to_string(),
+            stylesheets: Vec::new(),
+            meta: Vec::new()
+        }
+    }
+}
+
+impl DocumentOptions {
+    pub fn stylesheet(mut self, href: &str) -> Self {
+        self.stylesheets.push(href.to_string());
+        return self;
+    }
+
+    pub fn meta(mut self, name: &str, content: &str) -> Self {
+        self.meta.push(Attributes::from([
+            ("name".to_string(), name.to_string()),
+            ("content".to_string(), content.to_string())
+        ]));
+        return self;
+    }
+}
+
+/**
+ Compile Markdown, with optional front matter, into a complete HTML document.
+*/
+pub fn compile_document(source: &str, options: &DocumentOptions) -> String {
+    let (front_matter, markdown) = split_front_matter(source);
+    let doc = parse_md_str(markdown);
+
+    // Front matter values are text, the generated variables are HTML.
+    let mut variables: BTreeMap<String, String> = front_matter.iter()
+        .map(|(key, value)| (key.clone(), escape(value)))
+        .collect();
+    let title = front_matter.get("title").cloned()
+        .or_else(|| first_header(&doc))
+        .unwrap_or_else(|| options.default_title.clone());
+    variables.insert("title".to_string(), escape(&title));
+    variables.insert("lang".to_string(), escape(&options.lang));
+    variables.insert("head".to_string(), head(options));
+    variables.insert("toc".to_string(), toc_to_html(&build_toc(&doc, &options.html.toc)));
+    variables.insert("content".to_string(), compile_all_with(&doc, &options.html));
+
+    let template = options.template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+    return fill_template(template, &variables);
+}
+
+/**
+ Replace each `{{name}}` in a template, spaces inside the braces are allowed.
+*/
+pub fn fill_template(template: &str, variables: &BTreeMap<String, String>) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else { break };
+        filled.push_str(&rest[..start]);
+        let name = rest[start + 2..start + end].trim();
+        filled.push_str(variables.get(name).map(String::as_str).unwrap_or(""));
+        rest = &rest[start + end + 2..];
+    }
+    filled.push_str(rest);
+    return filled;
+}
+
+fn first_header(doc: &MdSyntaxTree) -> Option<String> {
+    doc.iter().find_map(|line| {
+        line.nodes_dfs()
+            .find(|node| matches!(node.val, Token::Header(_)))
+            .map(|node| text_content(line, TreeIndex::Arena(node.index())).trim().to_string())
+    })
+}
+
+fn head(options: &DocumentOptions) -> String {
+    let meta = options.meta.iter().map(|attributes| format!("<meta{}>\n", html_attributes(attributes)));
+    let stylesheets = options.stylesheets.iter().map(|href| format!(
+        "<link rel=\"stylesheet\" href=\"{}\">\n", escape(href)
+    ));
+    return meta.chain(stylesheets).collect();
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_document() {
+        let options = DocumentOptions{html: HtmlOptions::commonmark(), ..Default::default()}
+            .stylesheet("style.css")
+            .meta("author", "Ilmari");
+        assert_eq!(
+            compile_document("# Hello & welcome\nText", &options),
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+                <meta content=\"Ilmari\" name=\"author\">\n<link rel=\"stylesheet\" href=\"style.css\">\n\
+                <title>Hello &amp; welcome</title>\n</head>\n<body>\n\
+                <h1>Hello &amp; welcome</h1>\n<p>Text</p>\n\n</body>\n</html>\n"
+        );
+    }
+
+    #[test]
+    fn t_front_matter_only() {
+        let options = DocumentOptions{template: Some("{{title}}:{{toc}}:{{content}}".to_string()), ..Default::default()};
+        assert_eq!(compile_document("---\ntitle: x\n---\n", &options), "x::");
+        assert_eq!(compile_document("", &options), format!("{}::", options.default_title));
+    }
+
+    #[test]
+    fn t_template() {
+        let options = DocumentOptions{
+            html: HtmlOptions{header_ids: true, ..HtmlOptions::commonmark()},
+            template: Some("<h1>{{ title }}</h1><nav>{{toc}}</nav><i>{{author}}{{missing}}</i>{{content}}".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            compile_document("---\ntitle: Front <matter>\nauthor: Ilmari\n---\n## Part", &options),
+            "<h1>Front &lt;matter&gt;</h1><nav><ul class=\"toc\"><li><a href=\"#part\">Part</a></li></ul></nav>\
+                <i>Ilmari</i><h2 id=\"part\">Part</h2>\n"
+        );
+    }
+}
@@ -0,0 +1,304 @@
+/*
+ * Converts a practical subset of TeX math into presentation MathML.
+ *
+ * Supported: numbers, identifiers, operators, groups, sub/superscripts, `\frac`, `\sqrt`,
+ * Greek letters, common operators and relations, named functions, `\text` and font commands,
+ * `\left`/`\right` fences, spacing commands and the matrix environments. Anything else is
+ * emitted as an `<merror>` so that the rest of the expression still renders.
+ */
+
+use super::tex::{greek, is_function, is_limit_function, space, symbol, SymbolKind, TexScanner};
+
+struct MathNode {
+    markup: String,
+    // Large operators take their scripts as under/over limits in display mode.
+    large_op: bool
+}
+
+struct TexParser {
+    tex: TexScanner,
+    display: bool
+}
+
+pub fn tex_to_mathml(tex: &str, display: bool) -> String {
+    let mut parser = TexParser{tex: TexScanner::new(tex), display};
+    let mut nodes = Vec::new();
+    loop {
+        nodes.append(&mut parser.parse_row());
+        if parser.tex.peek().is_none() { break }
+
+        // Stray group closer, alignment or row separator on the top level, skip it.
+        if parser.tex.peek() == Some('\\') { parser.tex.read_command(); } else { parser.tex.pos += 1; }
+    }
+    return format!(
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"{d}\">{m}</math>",
+        d = if display {"block"} else {"inline"},
+        m = mrow(nodes)
+    );
+}
+
+fn mrow(nodes: Vec<MathNode>) -> String {
+    if nodes.len() == 1 { return nodes.into_iter().next().unwrap().markup }
+    let inner: String = nodes.into_iter().map(|n| n.markup).collect();
+    return format!("<mrow>{}</mrow>", inner);
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn node(markup: String) -> MathNode {
+    MathNode{markup, large_op: false}
+}
+
+fn mi(text: &str) -> MathNode {
+    node(format!("<mi>{}</mi>", escape(text)))
+}
+
+fn mo(text: &str) -> MathNode {
+    node(format!("<mo>{}</mo>", escape(text)))
+}
+
+fn font_variant(name: &str) -> Option<&'static str> {
+    let variant = match name {
+        "mathrm" | "operatorname" => "normal", "mathbf" => "bold", "mathit" => "italic",
+        "mathbb" => "double-struck", "mathcal" => "script", "mathfrak" => "fraktur",
+        "mathsf" => "sans-serif", "mathtt" => "monospace",
+        _ => return None
+    };
+    return Some(variant);
+}
+
+fn matrix_fences(env: &str) -> Option<(&'static str, &'static str)> {
+    let fences = match env {
+        "matrix" | "smallmatrix" | "array" | "aligned" | "align" | "align*" => ("", ""),
+        "pmatrix" => ("(", ")"),
+        "bmatrix" => ("[", "]"),
+        "Bmatrix" => ("{", "}"),
+        "vmatrix" => ("|", "|"),
+        "Vmatrix" => ("‖", "‖"),
+        "cases" => ("{", ""),
+        _ => return None
+    };
+    return Some(fences);
+}
+
+impl TexParser {
+
+    fn parse_row(&mut self) -> Vec<MathNode> {
+        let mut nodes: Vec<MathNode> = Vec::new();
+        loop {
+            self.tex.skip_whitespace();
+            if self.tex.at_row_end() { break }
+            match self.tex.peek() {
+                Some('^') | Some('_') => {
+                    let base = nodes.pop().unwrap_or_else(|| node("<mrow></mrow>".to_string()));
+                    nodes.push(self.parse_scripts(base));
+                },
+                _ => {
+                    let atom = self.parse_atom();
+                    nodes.push(atom);
+                }
+            }
+        }
+        return nodes;
+    }
+
+    // Argument of a command or script: a braced group or a single atom.
+    fn parse_argument(&mut self) -> MathNode {
+        self.tex.skip_whitespace();
+        if self.tex.at_row_end() { return node("<mrow></mrow>".to_string()) }
+        return self.parse_atom();
+    }
+
+    fn parse_scripts(&mut self, base: MathNode) -> MathNode {
+        let mut sub = None;
+        let mut sup = None;
+        loop {
+            self.tex.skip_whitespace();
+            match self.tex.peek() {
+                Some('_') if sub.is_none() => { self.tex.pos += 1; sub = Some(self.parse_argument()) },
+                Some('^') if sup.is_none() => { self.tex.pos += 1; sup = Some(self.parse_argument()) },
+                _ => break
+            }
+        }
+        let limits = base.large_op && self.display;
+        let markup = match (sub, sup) {
+            (Some(b), Some(p)) => format!(
+                "<{t}>{}{}{}</{t}>", base.markup, b.markup, p.markup,
+                t = if limits {"munderover"} else {"msubsup"}),
+            (Some(b), None) => format!(
+                "<{t}>{}{}</{t}>", base.markup, b.markup,
+                t = if limits {"munder"} else {"msub"}),
+            (None, Some(p)) => format!(
+                "<{t}>{}{}</{t}>", base.markup, p.markup,
+                t = if limits {"mover"} else {"msup"}),
+            (None, None) => base.markup
+        };
+        return node(markup);
+    }
+
+    fn parse_atom(&mut self) -> MathNode {
+        let c = self.tex.peek().unwrap();
+        if c == '{' {
+            self.tex.pos += 1;
+            let group = self.parse_row();
+            if self.tex.peek() == Some('}') { self.tex.pos += 1 }
+            return node(format!("<mrow>{}</mrow>", group.into_iter().map(|n| n.markup).collect::<String>()));
+        }
+        if c == '\\' {
+            return self.parse_command();
+        }
+        if let Some(number) = self.tex.read_number() {
+            return node(format!("<mn>{}</mn>", number));
+        }
+        self.tex.pos += 1;
+        if c.is_alphabetic() { return mi(&c.to_string()) }
+        if c == '\'' { return mo("′") }
+        return mo(&c.to_string());
+    }
+
+    fn parse_command(&mut self) -> MathNode {
+        let name = self.tex.read_command();
+        let name = name.as_str();
+
+        if let Some(letter) = greek(name) { return mi(letter.unicode) }
+        if let Some(symbol) = symbol(name) {
+            return match symbol.kind {
+                SymbolKind::Identifier => mi(symbol.unicode),
+                SymbolKind::Operator => mo(symbol.unicode),
+                SymbolKind::LargeOperator => MathNode{
+                    markup: format!("<mo largeop=\"true\">{}</mo>", symbol.unicode),
+                    large_op: true
+                }
+            };
+        }
+        if is_limit_function(name) { return MathNode{markup: format!("<mi>{}</mi>", name), large_op: true} }
+        if is_function(name) { return mi(name) }
+        if let Some(space) = space(name) { return node(format!("<mspace width=\"{}\"/>", space.width)) }
+        if let Some(variant) = font_variant(name) {
+            let text = self.tex.read_raw_group();
+            return node(format!("<mi mathvariant=\"{}\">{}</mi>", variant, escape(&text)));
+        }
+
+        match name {
+            "frac" | "dfrac" | "tfrac" => {
+                let num = self.parse_argument();
+                let den = self.parse_argument();
+                return node(format!("<mfrac>{}{}</mfrac>", num.markup, den.markup));
+            },
+            "sqrt" => {
+                if let Some(index) = self.tex.read_optional() {
+                    let radicand = self.parse_argument();
+                    let index = tex_to_mathml_row(&index, self.display);
+                    return node(format!("<mroot>{}{}</mroot>", radicand.markup, index));
+                }
+                let radicand = self.parse_argument();
+                return node(format!("<msqrt>{}</msqrt>", radicand.markup));
+            },
+            "text" | "textrm" | "mbox" => {
+                let text = self.tex.read_raw_group();
+                return node(format!("<mtext>{}</mtext>", escape(&text)));
+            },
+            "left" | "right" | "big" | "Big" | "bigg" | "Bigg" => {
+                self.tex.skip_whitespace();
+                let fence = if self.tex.peek() == Some('\\') {
+                    let cmd = self.tex.read_command();
+                    symbol(&cmd).map(|s| s.unicode.to_string()).unwrap_or(cmd)
+                } else {
+                    self.tex.peek().map(|c| { self.tex.pos += 1; c.to_string() }).unwrap_or_default()
+                };
+                if fence == "." { return node("".to_string()) }
+                return node(format!("<mo stretchy=\"true\">{}</mo>", escape(&fence)));
+            },
+            "begin" => return self.parse_environment(),
+            _ => return node(format!("<merror><mtext>\\{}</mtext></merror>", escape(name)))
+        }
+    }
+
+    fn parse_environment(&mut self) -> MathNode {
+        let env = self.tex.read_raw_group();
+        let Some((open, close)) = matrix_fences(&env) else {
+            return node(format!("<merror><mtext>\\begin{{{}}}</mtext></merror>", escape(&env)));
+        };
+
+        let mut rows = Vec::new();
+        let mut cells = Vec::new();
+        loop {
+            cells.push(format!("<mtd>{}</mtd>", mrow(self.parse_row())));
+            if self.tex.peek() == Some('&') {
+                self.tex.pos += 1;
+                continue;
+            }
+            let ended = self.tex.peek().is_none() || self.tex.rest_starts_with("\\end");
+            if self.tex.rest_starts_with("\\\\") { self.tex.pos += 2 }
+            else if self.tex.peek() == Some('}') { self.tex.pos += 1 }
+            rows.push(format!("<mtr>{}</mtr>", cells.concat()));
+            cells.clear();
+            if ended { break }
+        }
+        if self.tex.rest_starts_with("\\end") {
+            self.tex.read_command();
+            self.tex.read_raw_group();
+        }
+
+        let table = format!("<mtable>{}</mtable>", rows.concat());
+        let fence = |f: &str| if f.is_empty() {"".to_string()} else {format!("<mo>{}</mo>", escape(f))};
+        return node(format!("<mrow>{}{}{}</mrow>", fence(open), table, fence(close)));
+    }
+}
+
+fn tex_to_mathml_row(tex: &str, display: bool) -> String {
+    let mut parser = TexParser{tex: TexScanner::new(tex), display};
+    return mrow(parser.parse_row());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inner(tex: &str) -> String {
+        let ml = tex_to_mathml(tex, false);
+        let start = ml.find('>').unwrap() + 1;
+        return ml[start..ml.len() - "</math>".len()].to_string();
+    }
+
+    #[test]
+    fn t_atoms() {
+        assert_eq!(inner("x"), "<mi>x</mi>");
+        assert_eq!(inner("x+12.5"), "<mrow><mi>x</mi><mo>+</mo><mn>12.5</mn></mrow>");
+        assert_eq!(inner("\\alpha < \\infty"), "<mrow><mi>α</mi><mo>&lt;</mo><mi>∞</mi></mrow>");
+    }
+
+    #[test]
+    fn t_scripts_and_fractions() {
+        assert_eq!(inner("x^2"), "<msup><mi>x</mi><mn>2</mn></msup>");
+        assert_eq!(inner("a_{ij}^n"), "<msubsup><mi>a</mi><mrow><mi>i</mi><mi>j</mi></mrow><mi>n</mi></msubsup>");
+        assert_eq!(inner("\\frac{1}{x}"), "<mfrac><mrow><mn>1</mn></mrow><mrow><mi>x</mi></mrow></mfrac>");
+        assert_eq!(inner("\\sqrt[3]{y}"), "<mroot><mrow><mi>y</mi></mrow><mn>3</mn></mroot>");
+    }
+
+    #[test]
+    fn t_display_limits() {
+        let ml = tex_to_mathml("\\sum_{i=0}^n i", true);
+        assert!(ml.starts_with("<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\">"));
+        assert!(ml.contains("<munderover><mo largeop=\"true\">∑</mo>"));
+        assert!(inner("\\sum_i").starts_with("<msub>"));
+    }
+
+    #[test]
+    fn t_matrices() {
+        assert_eq!(
+            inner("\\begin{pmatrix} a & b \\\\ c & d \\end{pmatrix}"),
+            "<mrow><mo>(</mo><mtable>\
+                <mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi></mtd></mtr>\
+                <mtr><mtd><mi>c</mi></mtd><mtd><mi>d</mi></mtd></mtr>\
+            </mtable><mo>)</mo></mrow>"
+        );
+    }
+
+    #[test]
+    fn t_unknown_command() {
+        assert_eq!(inner("\\foo"), "<merror><mtext>\\foo</mtext></merror>");
+    }
+}
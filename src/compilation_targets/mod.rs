@@ -1 +1,27 @@
 pub mod to_html;
+pub mod mathml;
+pub mod typst_math;
+pub mod tex;
+pub mod directives;
+pub mod renderer;
+pub mod html_document;
+pub mod to_markdown;
+pub mod to_text;
+pub mod to_ansi;
+pub mod to_latex;
+pub mod to_typst;
+pub mod to_man;
+pub mod to_pandoc;
+pub mod to_asciidoc;
+pub mod to_rst;
+pub mod to_chat;
+pub mod to_gemini;
+
+pub use renderer::{render, render_line, BlockCollector, NodeContext, Renderer, Visit};
+
+/**
+ * Compile a value into a target type, eg. the opening and closing tags of a token.
+ */
+pub trait Compile<T> {
+    fn compile(&self) -> T;
+}
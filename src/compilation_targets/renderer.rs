@@ -0,0 +1,541 @@
+use unicode_width::UnicodeWidthChar;
+
+use crate::tokens::*;
+use crate::tree::TreeIndex;
+
+/*
+ * Generic traversal for compilation targets. A Renderer gets an `enter` callback when a token
+ * is reached and an `exit` callback once all of its children have been rendered, the driver
+ * takes care of walking the syntax tree in document order.
+ */
+
+// Returned from `enter` to tell whether the children of a token should be visited.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Visit {
+    Children,
+    SkipChildren
+}
+
+/**
+ Position of a token in the document.
+*/
+pub struct NodeContext<'a> {
+    pub line: &'a MdLine,
+    // Index of the line in the document.
+    pub line_index: usize,
+    // Arena index of the token in its line, see TreeIndex::Arena.
+    pub raw_idx: usize,
+    pub depth: usize,
+    // Position among the children of the parent, root tokens are alone.
+    pub sibling_index: usize,
+    // Arena indices of the children of the parent, including this token.
+    pub siblings: &'a [usize],
+    // Tokens from the root of the line down to the parent.
+    pub ancestors: &'a [&'a Token]
+}
+
+impl<'a> NodeContext<'a> {
+    pub fn parent(&self) -> Option<&'a Token> {
+        self.ancestors.last().copied()
+    }
+
+    pub fn has_children(&self) -> bool {
+        self.line.get_node(TreeIndex::Arena(self.raw_idx)).is_some_and(|n| !n.children.is_empty())
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = &'a Token> + 'a {
+        let line = self.line;
+        line.get_node(TreeIndex::Arena(self.raw_idx))
+            .map(|n| n.children.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(move |c| line.get(TreeIndex::Arena(*c)))
+    }
+
+    // Token right after this one under the same parent.
+    pub fn next_sibling(&self) -> Option<&'a Token> {
+        self.sibling(self.sibling_index + 1)
+    }
+
+    pub fn previous_sibling(&self) -> Option<&'a Token> {
+        self.sibling(self.sibling_index.checked_sub(1)?)
+    }
+
+    fn sibling(&self, index: usize) -> Option<&'a Token> {
+        let sibling = self.siblings.get(index)?;
+        self.line.get(TreeIndex::Arena(*sibling))
+    }
+
+    pub fn is_first(&self) -> bool {
+        self.sibling_index == 0
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.sibling_index + 1 >= self.siblings.len()
+    }
+
+    // Number of a list item in an ordered list, nested lists between the items are not counted.
+    pub fn item_number(&self) -> Option<u32> {
+        let Some(Token::OrderedList(list)) = self.parent() else { return None };
+        let before = (0..self.sibling_index)
+            .filter(|i| matches!(self.sibling(*i), Some(Token::ListItem(_))))
+            .count();
+        return Some(list.start + before as u32);
+    }
+
+    // Alignment of the column of a table cell.
+    pub fn cell_alignment(&self) -> Alignment {
+        let Some(Token::Table(table)) = self.ancestors.iter().rev().nth(1) else { return Alignment::None };
+        return table.alignments.get(self.sibling_index).copied().unwrap_or(Alignment::None);
+    }
+
+    // Whether any ancestor matches, eg. `context.inside(|t| matches!(t, Token::Code(_)))`.
+    pub fn inside<F>(&self, predicate: F) -> bool
+    where F: Fn(&Token) -> bool {
+        self.ancestors.iter().any(|t| predicate(t))
+    }
+}
+
+pub trait Renderer {
+    fn start_document(&mut self, _doc: &MdSyntaxTree) {}
+
+    fn enter(&mut self, token: &Token, context: &NodeContext) -> Visit;
+
+    fn exit(&mut self, token: &Token, context: &NodeContext);
+
+    fn finish_document(&mut self) {}
+}
+
+/**
+ Render a whole document, line by line.
+*/
+pub fn render<R>(doc: &MdSyntaxTree, renderer: &mut R)
+where R: Renderer + ?Sized {
+    renderer.start_document(doc);
+    for (i, line) in doc.iter().enumerate() {
+        render_line(line, i, renderer);
+    }
+    renderer.finish_document();
+}
+
+/**
+ Render a single line without the document callbacks.
+*/
+pub fn render_line<R>(line: &MdLine, line_index: usize, renderer: &mut R)
+where R: Renderer + ?Sized {
+    let mut ancestors = Vec::new();
+    walk(line, line_index, 0, 0, &[0], &mut ancestors, renderer);
+}
+
+fn walk<'a, R>(
+    line: &'a MdLine,
+    line_index: usize,
+    raw_idx: usize,
+    sibling_index: usize,
+    siblings: &'a [usize],
+    ancestors: &mut Vec<&'a Token>,
+    renderer: &mut R)
+where R: Renderer + ?Sized {
+    let Some(node) = line.get_node(TreeIndex::Arena(raw_idx)) else { return };
+    let context = NodeContext{
+        line, line_index, raw_idx, depth: node.level, sibling_index, siblings, ancestors: ancestors.as_slice()
+    };
+    let visit = renderer.enter(&node.val, &context);
+
+    if visit == Visit::Children {
+        ancestors.push(&node.val);
+        for (i, child) in node.children.iter().enumerate() {
+            walk(line, line_index, *child, i, &node.children, ancestors, renderer);
+        }
+        ancestors.pop();
+    }
+    let context = NodeContext{
+        line, line_index, raw_idx, depth: node.level, sibling_index, siblings, ancestors: ancestors.as_slice()
+    };
+    renderer.exit(&node.val, &context);
+}
+
+// ----------------------------------------------------------------------------
+// BLOCK COLLECTOR
+// ----------------------------------------------------------------------------
+
+// Wraps a paragraph to lines of at most the given number of columns, eg. `wrap`.
+pub type WrapParagraph = fn(&str, usize) -> String;
+
+// Blocks of the document or of a container token such as a list or an admonition.
+struct Frame {
+    blocks: Vec<String>,
+    // Lines of a paragraph that the next line may still continue.
+    paragraph: Option<String>,
+    // Columns the container indents its blocks by, taken off the wrap width.
+    indent: usize
+}
+
+impl Frame {
+    fn new(indent: usize) -> Self {
+        Frame{blocks: Vec::new(), paragraph: None, indent}
+    }
+}
+
+/**
+ Output of the targets that write text block by block, eg. plain text or Markdown. Inline tokens
+ are collected into the text of their block, finished blocks into the innermost open container.
+ Consecutive lines of text make up one paragraph.
+*/
+pub struct BlockCollector {
+    frames: Vec<Frame>,
+    // Text of each token being rendered, from the root of the line down.
+    buffers: Vec<String>,
+    // Put between the lines of a paragraph.
+    joiner: &'static str,
+    // Columns to wrap paragraphs at.
+    wrap: Option<(usize, WrapParagraph)>,
+    // Cells of each row of the table being rendered.
+    rows: Vec<Vec<String>>
+}
+
+impl Default for BlockCollector {
+    fn default() -> Self {
+        BlockCollector::new()
+    }
+}
+
+impl BlockCollector {
+    pub fn new() -> Self {
+        BlockCollector{frames: vec![Frame::new(0)], buffers: Vec::new(), joiner: "\n", wrap: None, rows: Vec::new()}
+    }
+
+    pub fn joiner(mut self, joiner: &'static str) -> Self {
+        self.joiner = joiner;
+        return self;
+    }
+
+    // Wrap paragraphs, the lines of a paragraph are then joined with a space.
+    pub fn wrap(mut self, width: Option<usize>, wrap: WrapParagraph) -> Self {
+        self.wrap = width.map(|w| (w, wrap));
+        if self.wrap.is_some() { self.joiner = " " }
+        return self;
+    }
+
+    /**
+     Start collecting the text of a token. Containers get a frame of their own for their blocks,
+     indented by the given number of columns.
+    */
+    pub fn enter(&mut self, container: Option<usize>) {
+        if let Some(indent) = container { self.frames.push(Frame::new(indent)) }
+        self.buffers.push(String::new());
+    }
+
+    // Text collected from the children of the token being exited.
+    pub fn exit(&mut self) -> String {
+        self.buffers.pop().unwrap_or_default()
+    }
+
+    pub fn push_inline(&mut self, text: &str) {
+        if let Some(parent) = self.buffers.last_mut() {
+            parent.push_str(text);
+        }
+    }
+
+    // Add a line to the open paragraph, an empty line ends it.
+    pub fn push_line(&mut self, line: &str) {
+        if line.is_empty() {
+            self.flush_paragraph();
+            return;
+        }
+        let joiner = self.joiner;
+        let frame = self.frame();
+        frame.paragraph = Some(match frame.paragraph.take() {
+            Some(previous) => format!("{}{}{}", previous, joiner, line),
+            None => line.to_string()
+        });
+    }
+
+    // The open paragraph, wrapped, without adding it to the blocks.
+    pub fn take_paragraph(&mut self) -> Option<String> {
+        let width = self.width();
+        let paragraph = self.frame().paragraph.take()?;
+        return Some(match (self.wrap, width) {
+            (Some((_, wrap)), Some(width)) => wrap(&paragraph, width),
+            _ => paragraph
+        });
+    }
+
+    pub fn flush_paragraph(&mut self) {
+        if let Some(paragraph) = self.take_paragraph() {
+            self.frame().blocks.push(paragraph);
+        }
+    }
+
+    pub fn push_block(&mut self, block: String) {
+        self.flush_paragraph();
+        self.frame().blocks.push(block);
+    }
+
+    // Blocks of the innermost container, once it is done.
+    pub fn pop_frame(&mut self) -> Vec<String> {
+        self.flush_paragraph();
+        return self.frames.pop().map(|f| f.blocks).unwrap_or_default();
+    }
+
+    // Cells of a table row are the blocks of its frame.
+    pub fn push_row(&mut self) {
+        let cells = self.pop_frame();
+        self.rows.push(cells);
+    }
+
+    // Rows of the table, once it is done.
+    pub fn take_rows(&mut self) -> Vec<Vec<String>> {
+        self.pop_frame();
+        return std::mem::take(&mut self.rows);
+    }
+
+    // Indentation of the innermost container.
+    pub fn container_indent(&self) -> usize {
+        self.frames.last().map_or(0, |f| f.indent)
+    }
+
+    // Whether blocks go to the document rather than into a container.
+    pub fn at_top_level(&self) -> bool {
+        self.frames.len() <= 1
+    }
+
+    // Wrap width left inside the open containers.
+    pub fn width(&self) -> Option<usize> {
+        let indent: usize = self.frames.iter().map(|f| f.indent).sum();
+        self.wrap.map(|(w, _)| w.saturating_sub(indent).max(1))
+    }
+
+    // Blocks of the document, separated by blank lines and ending in a newline.
+    pub fn finish(self) -> String {
+        let blocks = self.into_blocks();
+        if blocks.is_empty() { return String::new() }
+        return format!("{}\n", blocks.join("\n\n"));
+    }
+
+    pub fn into_blocks(mut self) -> Vec<String> {
+        self.pop_frame()
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        if self.frames.is_empty() { self.frames.push(Frame::new(0)) }
+        return self.frames.last_mut().unwrap();
+    }
+}
+
+// Indent every line that isn't blank.
+pub(crate) fn indent(text: &str, prefix: &str) -> String {
+    text.lines()
+        .map(|line| if line.is_empty() { String::new() } else { format!("{}{}", prefix, line) })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Wrap words to lines of at most `width` columns.
+pub(crate) fn wrap(text: &str, width: usize) -> String {
+    wrap_hanging(text, width, "")
+}
+
+/**
+ Wrap words to lines of at most `width` columns, lines after the first start with `hanging`.
+ With a hanging indent the first word is a list bullet, which the text always follows on the
+ same line. Escape sequences take no columns, see `visible_width`.
+*/
+pub(crate) fn wrap_hanging(text: &str, width: usize, hanging: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for (i, word) in text.split_whitespace().enumerate() {
+        let word_width = visible_width(word);
+        let after_bullet = !hanging.is_empty() && i == 1;
+        if !line.is_empty() && line_width + 1 + word_width > width && !after_bullet {
+            lines.push(std::mem::take(&mut line));
+            line.push_str(hanging);
+            line_width = visible_width(hanging);
+        } else if !line.is_empty() {
+            line.push(' ');
+            line_width += 1;
+        }
+        line.push_str(word);
+        line_width += word_width;
+    }
+    lines.push(line);
+    return lines.join("\n");
+}
+
+// Columns taken by text in a terminal: wide characters take two, combining marks and escape
+// sequences none.
+pub(crate) fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            width += c.width().unwrap_or(0);
+            continue;
+        }
+        match chars.next() {
+            // CSI, eg. SGR `ESC [ 1 m`, ends with a letter.
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() { break }
+                }
+            },
+            // OSC, eg. hyperlinks, ends with BEL or `ESC \`.
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' { break }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+    return width;
+}
+
+/**
+ Cells of a table padded to the widest cell of their column and at least `min_width` columns,
+ see `visible_width`.
+*/
+pub(crate) fn pad_columns(rows: &[Vec<String>], alignments: &[Alignment], min_width: usize) -> Vec<Vec<String>> {
+    let columns = rows.first().map_or(0, Vec::len);
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| rows.iter().filter_map(|r| r.get(i)).map(|c| visible_width(c)).max().unwrap_or(0).max(min_width))
+        .collect();
+    return rows.iter().map(|row| {
+        row.iter().zip(&widths).enumerate().map(|(i, (cell, width))| {
+            let padding = width - visible_width(cell);
+            let (left, right) = match alignments.get(i) {
+                Some(Alignment::Right) => (padding, 0),
+                Some(Alignment::Center) => (padding / 2, padding - padding / 2),
+                _ => (0, padding)
+            };
+            format!("{}{}{}", " ".repeat(left), cell, " ".repeat(right))
+        }).collect()
+    }).collect();
+}
+
+/**
+ Table of aligned columns separated by two spaces, with a line of dashes under the header row.
+*/
+pub(crate) fn text_table(rows: &[Vec<String>], alignments: &[Alignment], header: bool) -> String {
+    let padded = pad_columns(rows, alignments, 1);
+    let mut lines: Vec<String> = padded.iter().map(|row| row.join("  ").trim_end().to_string()).collect();
+    if let (true, Some(first)) = (header, padded.first()) {
+        let rule: Vec<String> = first.iter().map(|cell| "-".repeat(visible_width(cell))).collect();
+        lines.insert(1, rule.join("  "));
+    }
+    return lines.join("\n");
+}
+
+/**
+ Escape a line of text that would start another kind of block, eg. a quote or a list item, by
+ putting `escape` in front of it.
+*/
+pub(crate) fn escape_line_start(line: &str, markers: &[&str], escape: &str) -> String {
+    if markers.iter().any(|m| line.starts_with(m)) { return format!("{}{}", escape, line) }
+    return line.to_string();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    // Outline of the document, eg. "Header(PlainText)".
+    struct Outline {
+        out: String
+    }
+
+    impl Renderer for Outline {
+        fn enter(&mut self, token: &Token, context: &NodeContext) -> Visit {
+            if !context.is_first() { self.out.push(',') }
+            let name = format!("{:?}", token);
+            self.out.push_str(name.split([' ', '{']).next().unwrap());
+            if context.has_children() { self.out.push('(') }
+            if matches!(token, Token::Code(_)) { return Visit::SkipChildren }
+            return Visit::Children;
+        }
+
+        fn exit(&mut self, _: &Token, context: &NodeContext) {
+            if context.has_children() { self.out.push(')') }
+            if context.depth == 0 { self.out.push('\n') }
+        }
+    }
+
+    #[test]
+    fn t_traversal() {
+        let doc = parse_md_str("# A *b*\n```\ncode\n```");
+        let mut outline = Outline{out: String::new()};
+        render(&doc, &mut outline);
+        assert_eq!(outline.out, "Header(PlainText,Italic(PlainText))\nCode()\n");
+    }
+
+    #[test]
+    fn t_context() {
+        struct Ancestors(Vec<usize>);
+        impl Renderer for Ancestors {
+            fn enter(&mut self, token: &Token, context: &NodeContext) -> Visit {
+                if let Token::PlainText(_) = token {
+                    assert!(context.inside(|t| matches!(t, Token::Header(_))));
+                    self.0.push(context.ancestors.len());
+                }
+                return Visit::Children;
+            }
+            fn exit(&mut self, _: &Token, _: &NodeContext) {}
+        }
+        let doc = parse_md_str("# A *b*");
+        let mut ancestors = Ancestors(vec![]);
+        render(&doc, &mut ancestors);
+        assert_eq!(ancestors.0, [1, 2]);
+    }
+
+    #[test]
+    fn t_block_collector() {
+        let mut blocks = BlockCollector::new().wrap(Some(12), wrap);
+        blocks.push_line("one two three");
+        blocks.push_line("four");
+        blocks.enter(Some(4));
+        blocks.push_line("five six seven");
+        blocks.exit();
+        let inner = blocks.pop_frame();
+        blocks.push_block(inner.join("\n"));
+        assert_eq!(blocks.finish(), "one two\nthree four\n\nfive six\nseven\n");
+        assert_eq!(wrap_hanging("- a list item", 6, "  "), "- a\n  list\n  item");
+        assert_eq!(escape_line_start("# no header", &["#"], "\\"), "\\# no header");
+    }
+
+    #[test]
+    fn t_pad_columns() {
+        let rows = vec![vec!["a".to_string(), "b".to_string()], vec!["long".to_string(), String::new()]];
+        assert_eq!(
+            pad_columns(&rows, &[Alignment::Right, Alignment::Center], 3),
+            [["   a", " b "], ["long", "   "]]
+        );
+        assert_eq!(text_table(&rows, &[], true), "a     b\n----  -\nlong");
+    }
+
+    #[test]
+    fn t_siblings() {
+        // Kinds of the siblings around each token, eg. "PlainText<Italic>-".
+        struct Siblings(Vec<String>);
+        impl Renderer for Siblings {
+            fn enter(&mut self, token: &Token, context: &NodeContext) -> Visit {
+                let kind = |t: Option<&Token>| t.map_or("-".to_string(), |t| format!("{:?}", t.kind()));
+                self.0.push(format!("{}<{:?}>{}", kind(context.previous_sibling()), token.kind(), kind(context.next_sibling())));
+                return Visit::Children;
+            }
+            fn exit(&mut self, _: &Token, _: &NodeContext) {}
+        }
+        let doc = parse_md_str("# A *b* `c`");
+        let mut siblings = Siblings(vec![]);
+        render(&doc, &mut siblings);
+        assert_eq!(siblings.0, [
+            "-<Header>-", "-<PlainText>Italic", "PlainText<Italic>PlainText", "-<PlainText>-",
+            "Italic<PlainText>InlineCode", "PlainText<InlineCode>-", "-<PlainText>-"
+        ]);
+    }
+}
@@ -0,0 +1,220 @@
+/*
+ * Scanner and command tables for the TeX math subset shared by the MathML and Typst converters.
+ * The converters build their output from the scanner's tokens, the tables give each command's
+ * MathML character and Typst name.
+ */
+
+pub(crate) struct TexScanner {
+    pub chars: Vec<char>,
+    pub pos: usize
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum SymbolKind {
+    Identifier,
+    Operator,
+    // Sums, integrals and the like, which take their scripts as limits in display mode.
+    LargeOperator
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Symbol {
+    pub kind: SymbolKind,
+    pub unicode: &'static str,
+    pub typst: &'static str
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Space {
+    pub width: &'static str,
+    pub typst: &'static str
+}
+
+impl TexScanner {
+
+    pub fn new(tex: &str) -> Self {
+        TexScanner{chars: tex.chars().collect(), pos: 0}
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    pub fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) { self.pos += 1 }
+    }
+
+    pub fn rest_starts_with(&self, s: &str) -> bool {
+        s.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    // Row separators and environment ends terminate the current row.
+    pub fn at_row_end(&self) -> bool {
+        match self.peek() {
+            None | Some('}') | Some('&') => true,
+            Some('\\') => self.rest_starts_with("\\\\") || self.rest_starts_with("\\end"),
+            _ => false
+        }
+    }
+
+    // A number at the current position, eg. `12.5` or `.5`.
+    pub fn read_number(&mut self) -> Option<String> {
+        let c = self.peek()?;
+        let fraction = c == '.' && self.chars.get(self.pos + 1).is_some_and(char::is_ascii_digit);
+        if !c.is_ascii_digit() && !fraction { return None }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') { self.pos += 1 }
+        return Some(self.chars[start..self.pos].iter().collect());
+    }
+
+    /**
+     Read a command name after a backslash. Names are either a run of letters or a single
+     other character, eg. `\,` or `\{`.
+    */
+    pub fn read_command(&mut self) -> String {
+        self.pos += 1;
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) { self.pos += 1 }
+        if self.pos == start && self.peek().is_some() { self.pos += 1 }
+        return self.chars[start..self.pos].iter().collect();
+    }
+
+    // Raw text of a braced group, used for `\text` and environment names.
+    pub fn read_raw_group(&mut self) -> String {
+        self.skip_whitespace();
+        if self.peek() != Some('{') {
+            return self.peek().map(|c| { self.pos += 1; c.to_string() }).unwrap_or_default();
+        }
+        self.pos += 1;
+        let mut depth = 1;
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c == '{' { depth += 1 }
+            if c == '}' { depth -= 1; if depth == 0 { break } }
+            self.pos += 1;
+        }
+        let raw: String = self.chars[start..self.pos].iter().collect();
+        if self.peek().is_some() { self.pos += 1 }
+        return raw;
+    }
+
+    // Raw text of an optional argument in brackets, eg. the index of `\sqrt[3]{x}`.
+    pub fn read_optional(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        if self.peek() != Some('[') { return None }
+        self.pos += 1;
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != ']') { self.pos += 1 }
+        let raw: String = self.chars[start..self.pos].iter().collect();
+        if self.peek().is_some() { self.pos += 1 }
+        return Some(raw);
+    }
+}
+
+pub(crate) fn greek(name: &str) -> Option<Symbol> {
+    let (unicode, typst) = match name {
+        "alpha" => ("α", "alpha"), "beta" => ("β", "beta"), "gamma" => ("γ", "gamma"),
+        "delta" => ("δ", "delta"), "epsilon" => ("ϵ", "epsilon.alt"), "varepsilon" => ("ε", "epsilon"),
+        "zeta" => ("ζ", "zeta"), "eta" => ("η", "eta"), "theta" => ("θ", "theta"),
+        "vartheta" => ("ϑ", "theta.alt"), "iota" => ("ι", "iota"), "kappa" => ("κ", "kappa"),
+        "lambda" => ("λ", "lambda"), "mu" => ("μ", "mu"), "nu" => ("ν", "nu"), "xi" => ("ξ", "xi"),
+        "pi" => ("π", "pi"), "rho" => ("ρ", "rho"), "sigma" => ("σ", "sigma"), "tau" => ("τ", "tau"),
+        "upsilon" => ("υ", "upsilon"), "phi" => ("ϕ", "phi.alt"), "varphi" => ("φ", "phi"),
+        "chi" => ("χ", "chi"), "psi" => ("ψ", "psi"), "omega" => ("ω", "omega"),
+        "Gamma" => ("Γ", "Gamma"), "Delta" => ("Δ", "Delta"), "Theta" => ("Θ", "Theta"),
+        "Lambda" => ("Λ", "Lambda"), "Xi" => ("Ξ", "Xi"), "Pi" => ("Π", "Pi"), "Sigma" => ("Σ", "Sigma"),
+        "Upsilon" => ("Υ", "Upsilon"), "Phi" => ("Φ", "Phi"), "Psi" => ("Ψ", "Psi"),
+        "Omega" => ("Ω", "Omega"),
+        _ => return None
+    };
+    return Some(Symbol{kind: SymbolKind::Identifier, unicode, typst});
+}
+
+pub(crate) fn symbol(name: &str) -> Option<Symbol> {
+    use SymbolKind::*;
+    let (kind, unicode, typst) = match name {
+        "infty" => (Identifier, "∞", "infinity"), "partial" => (Identifier, "∂", "diff"),
+        "nabla" => (Identifier, "∇", "nabla"), "emptyset" => (Identifier, "∅", "emptyset"),
+        "hbar" => (Identifier, "ℏ", "planck.reduce"), "ell" => (Identifier, "ℓ", "ell"),
+
+        "times" => (Operator, "×", "times"), "cdot" => (Operator, "⋅", "dot.op"),
+        "pm" => (Operator, "±", "plus.minus"), "mp" => (Operator, "∓", "minus.plus"),
+        "div" => (Operator, "÷", "div"), "ast" => (Operator, "∗", "ast"),
+        "leq" | "le" => (Operator, "≤", "<="), "geq" | "ge" => (Operator, "≥", ">="),
+        "neq" | "ne" => (Operator, "≠", "!="), "approx" => (Operator, "≈", "approx"),
+        "equiv" => (Operator, "≡", "equiv"), "sim" => (Operator, "∼", "tilde.op"),
+        "propto" => (Operator, "∝", "prop"), "ll" => (Operator, "≪", "<<"), "gg" => (Operator, "≫", ">>"),
+        "in" => (Operator, "∈", "in"), "notin" => (Operator, "∉", "in.not"),
+        "subset" => (Operator, "⊂", "subset"), "subseteq" => (Operator, "⊆", "subset.eq"),
+        "supset" => (Operator, "⊃", "supset"), "cup" => (Operator, "∪", "union"),
+        "cap" => (Operator, "∩", "inter"), "setminus" => (Operator, "∖", "without"),
+        "land" | "wedge" => (Operator, "∧", "and"), "lor" | "vee" => (Operator, "∨", "or"),
+        "neg" => (Operator, "¬", "not"), "forall" => (Operator, "∀", "forall"),
+        "exists" => (Operator, "∃", "exists"), "to" | "rightarrow" => (Operator, "→", "->"),
+        "leftarrow" => (Operator, "←", "<-"), "Rightarrow" | "implies" => (Operator, "⇒", "=>"),
+        "Leftarrow" => (Operator, "⇐", "arrow.l.double"), "Leftrightarrow" | "iff" => (Operator, "⇔", "<=>"),
+        "mapsto" => (Operator, "↦", "|->"), "cdots" => (Operator, "⋯", "dots.c"),
+        "ldots" | "dots" => (Operator, "…", "dots"), "vdots" => (Operator, "⋮", "dots.v"),
+        "ddots" => (Operator, "⋱", "dots.down"), "langle" => (Operator, "⟨", "angle.l"),
+        "rangle" => (Operator, "⟩", "angle.r"), "mid" => (Operator, "∣", "divides"),
+        "circ" => (Operator, "∘", "compose"), "prime" => (Operator, "′", "prime"),
+        "{" => (Operator, "{", "\\{"), "}" => (Operator, "}", "\\}"), "|" => (Operator, "‖", "||"),
+
+        "sum" => (LargeOperator, "∑", "sum"), "prod" => (LargeOperator, "∏", "product"),
+        "coprod" => (LargeOperator, "∐", "product.co"), "int" => (LargeOperator, "∫", "integral"),
+        "iint" => (LargeOperator, "∬", "integral.double"), "oint" => (LargeOperator, "∮", "integral.cont"),
+        "bigcup" => (LargeOperator, "⋃", "union.big"), "bigcap" => (LargeOperator, "⋂", "inter.big"),
+        _ => return None
+    };
+    return Some(Symbol{kind, unicode, typst});
+}
+
+// Functions written upright under their own name, eg. `\sin`.
+pub(crate) fn is_function(name: &str) -> bool {
+    matches!(name,
+        "sin" | "cos" | "tan" | "cot" | "sec" | "csc" | "arcsin" | "arccos" | "arctan" |
+        "sinh" | "cosh" | "tanh" | "log" | "ln" | "exp" | "det" | "dim" | "ker" | "gcd" |
+        "deg" | "arg" | "Pr") || is_limit_function(name)
+}
+
+// Functions taking their scripts as limits in display mode, eg. `\lim`.
+pub(crate) fn is_limit_function(name: &str) -> bool {
+    matches!(name, "lim" | "max" | "min" | "sup" | "inf" | "liminf" | "limsup")
+}
+
+pub(crate) fn space(name: &str) -> Option<Space> {
+    let (width, typst) = match name {
+        "," => ("0.167em", "thin"), ":" | ">" => ("0.222em", "med"), ";" => ("0.278em", "thick"),
+        " " => ("0.333em", "space"), "quad" => ("1em", "quad"), "qquad" => ("2em", "wide"),
+        "!" => ("-0.167em", "#h(-0.167em)"),
+        _ => return None
+    };
+    return Some(Space{width, typst});
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_scanner() {
+        let mut scanner = TexScanner::new("\\frac{a{b}}c \\, 1.5[2]");
+        assert_eq!(scanner.read_command(), "frac");
+        assert_eq!(scanner.read_raw_group(), "a{b}");
+        assert_eq!(scanner.read_raw_group(), "c");
+        scanner.skip_whitespace();
+        assert_eq!(scanner.read_command(), ",");
+        scanner.skip_whitespace();
+        assert_eq!(scanner.read_number().as_deref(), Some("1.5"));
+        assert_eq!(scanner.read_optional().as_deref(), Some("2"));
+        assert!(scanner.at_row_end());
+    }
+
+    #[test]
+    fn t_tables() {
+        assert_eq!(symbol("sum"), Some(Symbol{kind: SymbolKind::LargeOperator, unicode: "∑", typst: "sum"}));
+        assert_eq!(greek("phi").map(|s| s.typst), Some("phi.alt"));
+        assert!(is_function("lim") && !is_function("foo"));
+        assert_eq!(space(";").map(|s| s.width), Some("0.278em"));
+    }
+}
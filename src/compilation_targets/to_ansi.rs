@@ -0,0 +1,286 @@
+use std::env;
+
+use crate::toc::{build_toc, TocOptions};
+use crate::tokens::*;
+use super::renderer::{indent, text_table, visible_width, wrap, wrap_hanging};
+use super::to_text::toc_to_text;
+use super::{render, BlockCollector, NodeContext, Renderer, Visit};
+
+/*
+ * Terminal target. Text is styled with ANSI escape codes, headings are colored, code blocks are
+ * drawn in boxes, table columns are aligned with bold headers, paragraphs wrap to the terminal
+ * width and links become OSC 8 hyperlinks. Without color only the layout is kept, links then show
+ * their url after the text.
+ */
+
+pub struct AnsiOptions {
+    // Styles and colors, off when `NO_COLOR` is set.
+    pub color: bool,
+    // OSC 8 hyperlinks, only used with color.
+    pub hyperlinks: bool,
+    // Terminal columns to wrap at.
+    pub width: usize,
+    // SGR color codes of header levels 1 to 6.
+    pub heading_colors: [u8; 6],
+    // Spaces per level of nested lists.
+    pub list_indent: usize,
+    pub toc: TocOptions
+}
+
+impl Default for AnsiOptions {
+    fn default() -> Self {
+        AnsiOptions{
+            color: true,
+            hyperlinks: true,
+            width: 80,
+            heading_colors: [35, 34, 36, 32, 33, 33],
+            list_indent: 2,
+            toc: TocOptions::default()
+        }
+    }
+}
+
+impl AnsiOptions {
+    /**
+     Defaults adjusted to the environment: color follows `NO_COLOR` and the width `COLUMNS`.
+    */
+    pub fn from_env() -> Self {
+        AnsiOptions::from_vars(env::var("NO_COLOR").ok(), env::var("COLUMNS").ok())
+    }
+
+    fn from_vars(no_color: Option<String>, columns: Option<String>) -> Self {
+        // Any non-empty value of NO_COLOR disables color, see https://no-color.org.
+        let color = no_color.is_none_or(|v| v.is_empty());
+        let width = columns.and_then(|c| c.trim().parse().ok()).filter(|w| *w > 0).unwrap_or(80);
+        AnsiOptions{color, width, ..Default::default()}
+    }
+}
+
+pub fn compile_all(input: MdSyntaxTree) -> String {
+    return compile_all_with(&input, &AnsiOptions::from_env());
+}
+
+pub fn compile_all_with(input: &MdSyntaxTree, options: &AnsiOptions) -> String {
+    let mut renderer = AnsiRenderer::new(options);
+    render(input, &mut renderer);
+    return renderer.finish();
+}
+
+const DEFINITION_INDENT: usize = 4;
+// Admonition content is prefixed with a bar and a space.
+const ADMONITION_INDENT: usize = 2;
+const CODE_COLOR: u8 = 36;
+
+/**
+ Renderer for the terminal, see `BlockCollector`. Paragraphs always wrap to the terminal width.
+*/
+pub struct AnsiRenderer<'a> {
+    options: &'a AnsiOptions,
+    blocks: BlockCollector,
+    toc: String
+}
+
+impl<'a> AnsiRenderer<'a> {
+    pub fn new(options: &'a AnsiOptions) -> Self {
+        AnsiRenderer{options, blocks: BlockCollector::new().wrap(Some(options.width), wrap), toc: String::new()}
+    }
+
+    pub fn finish(self) -> String {
+        self.blocks.finish()
+    }
+
+    // Text between an SGR code and the code resetting it.
+    fn style(&self, text: &str, on: &str, off: &str) -> String {
+        if !self.options.color { return text.to_string() }
+        return format!("\x1b[{}m{}\x1b[{}m", on, text, off);
+    }
+
+    // The text is rendered already, only the url still comes from the document.
+    fn link(&self, link: &Link, text: &str) -> String {
+        let url = strip_controls(&link.url);
+        if url.is_empty() { return text.to_string() }
+        if !self.options.color || !self.options.hyperlinks {
+            if url == text { return text.to_string() }
+            return format!("{} <{}>", text, url);
+        }
+        let underlined = self.style(text, "4", "24");
+        return format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, underlined);
+    }
+
+    fn header(&self, level: u32, text: &str) -> String {
+        if !self.options.color {
+            return format!("{} {}", "#".repeat(level as usize), text);
+        }
+        let color = self.options.heading_colors[(level.clamp(1, 6) - 1) as usize];
+        let underline = if level == 1 { ";4" } else { "" };
+        return format!("\x1b[1;{}{}m{}\x1b[0m", color, underline, text);
+    }
+
+    fn code_block(&self, lang: Option<&str>, code: &str) -> String {
+        let lines: Vec<&str> = code.trim_end_matches('\n').lines().collect();
+        let label = lang.map(|l| format!(" {} ", l)).unwrap_or_default();
+        let inner = lines.iter().map(|l| visible_width(l)).max().unwrap_or(0)
+            .max(visible_width(&label) + 1);
+        let top = format!("┌─{}{}┐", label, "─".repeat(inner + 1 - visible_width(&label)));
+        let body = lines.iter().map(|line| {
+            let padding = " ".repeat(inner - visible_width(line));
+            format!("│ {}{} │", self.style(line, &CODE_COLOR.to_string(), "39"), padding)
+        });
+        let bottom = format!("└{}┘", "─".repeat(inner + 2));
+        return std::iter::once(top).chain(body).chain(std::iter::once(bottom)).collect::<Vec<_>>().join("\n");
+    }
+}
+
+impl Renderer for AnsiRenderer<'_> {
+    fn start_document(&mut self, doc: &MdSyntaxTree) {
+        self.toc = strip_controls(&toc_to_text(&build_toc(doc, &self.options.toc), self.options.list_indent));
+    }
+
+    fn enter(&mut self, token: &Token, context: &NodeContext) -> Visit {
+        let indent = match token {
+            Token::List(_) | Token::OrderedList(_) if context.inside(Token::is_list) => Some(self.options.list_indent),
+            Token::List(_) | Token::OrderedList(_) | Token::DefinitionList(_) => Some(0),
+            Token::Definition(_) => Some(DEFINITION_INDENT),
+            Token::Admonition(_) => Some(ADMONITION_INDENT),
+            Token::Directive(d) if !d.inline => Some(0),
+            Token::Table(_) | Token::TableRow(_) => Some(0),
+            _ => None
+        };
+        self.blocks.enter(indent);
+        return Visit::Children;
+    }
+
+    fn exit(&mut self, token: &Token, context: &NodeContext) {
+        let content = self.blocks.exit();
+        match token {
+            Token::PlainText(t) => self.blocks.push_inline(&strip_controls(&t.text())),
+            Token::Bold(_) => self.blocks.push_inline(&self.style(&content, "1", "22")),
+            Token::Italic(_) => self.blocks.push_inline(&self.style(&content, "3", "23")),
+            Token::InlineCode(_) => self.blocks.push_inline(&self.style(&content, &CODE_COLOR.to_string(), "39")),
+            Token::InlineMath(m) => {
+                self.blocks.push_inline(&self.style(&strip_controls(&m.src()), &CODE_COLOR.to_string(), "39"));
+            },
+            Token::DisplayMath(m) if context.depth > 0 => {
+                self.blocks.push_inline(&self.style(&strip_controls(&m.src()), &CODE_COLOR.to_string(), "39"));
+            },
+            Token::Directive(d) if d.inline => self.blocks.push_inline(&content),
+            Token::Link(l) => self.blocks.push_inline(&self.link(l, &content)),
+            Token::Paragraph(_) => self.blocks.push_line(content.trim()),
+            Token::Header(h) => self.blocks.push_block(self.header(h.level, content.trim())),
+            Token::Code(c) => self.blocks.push_block(self.code_block(c.lang.as_deref(), &content)),
+            Token::DisplayMath(m) => self.blocks.push_block(self.code_block(None, &strip_controls(&m.src()))),
+            Token::TableOfContents(_) if !self.toc.is_empty() => self.blocks.push_block(self.toc.clone()),
+            Token::ListItem(_) => {
+                let marker = context.item_number().map_or("•".to_string(), |n| format!("{}.", n));
+                let hanging = " ".repeat(marker.chars().count() + 1);
+                let marker = self.style(&marker, &self.options.heading_colors[0].to_string(), "39");
+                let item = format!("{} {}", marker, content.trim());
+                let width = self.blocks.width().unwrap_or(self.options.width);
+                self.blocks.push_block(wrap_hanging(&item, width, &hanging));
+            },
+            Token::DefinitionTerm(_) => self.blocks.push_block(self.style(content.trim(), "1", "22")),
+            Token::TableCell(_) if matches!(context.parent(), Some(Token::TableRow(r)) if r.header) => {
+                self.blocks.push_block(self.style(content.trim(), "1", "22"));
+            },
+            Token::TableCell(_) => self.blocks.push_block(content.trim().to_string()),
+            Token::TableRow(_) => self.blocks.push_row(),
+            Token::Table(t) => {
+                let rows = self.blocks.take_rows();
+                let header = context.children().any(|r| matches!(r, Token::TableRow(r) if r.header));
+                self.blocks.push_block(text_table(&rows, &t.alignments, header));
+            },
+            Token::List(_) | Token::OrderedList(_) | Token::DefinitionList(_) => {
+                let frame_indent = self.blocks.container_indent();
+                let items = self.blocks.pop_frame().join("\n");
+                self.blocks.push_block(indent(&items, &" ".repeat(frame_indent)));
+            },
+            Token::Definition(_) => {
+                let definition = self.blocks.pop_frame().join("\n");
+                self.blocks.push_block(indent(&definition, &" ".repeat(DEFINITION_INDENT)));
+            },
+            Token::Admonition(a) => {
+                let inner = self.blocks.pop_frame().join("\n\n");
+                let bar = self.style("│", "33", "39");
+                let title = format!("{} {}", bar, self.style(&strip_controls(&a.display_title()), "1;33", "0"));
+                let lines = inner.lines()
+                    .map(|line| if line.is_empty() { bar.clone() } else { format!("{} {}", bar, line) });
+                self.blocks.push_block(std::iter::once(title).chain(lines).collect::<Vec<_>>().join("\n"));
+            },
+            Token::Directive(_) => {
+                let inner = self.blocks.pop_frame().join("\n\n");
+                if !inner.is_empty() { self.blocks.push_block(inner) }
+            },
+            _ => {}
+        }
+    }
+}
+
+/**
+ Text from the document without control characters, so that it can't move the cursor, change
+ the terminal's settings or end an escape sequence of the output early, eg. a hyperlink url with
+ `ESC \` in it. Line breaks and tabs are kept.
+*/
+pub fn strip_controls(text: &str) -> String {
+    return text.chars().filter(|c| !c.is_control() || *c == '\n' || *c == '\t').collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    #[test]
+    fn t_styles() {
+        let md_syntax = parse_md_str("# Title\n**bold** *it* [gnu](https://gnu.org)\n");
+        assert_eq!(
+            compile_all_with(&md_syntax, &AnsiOptions::default()),
+            "\x1b[1;35;4mTitle\x1b[0m\n\n\x1b[1mbold\x1b[22m \x1b[3mit\x1b[23m \
+                \x1b]8;;https://gnu.org\x1b\\\x1b[4mgnu\x1b[24m\x1b]8;;\x1b\\\n"
+        );
+    }
+
+    #[test]
+    fn t_table() {
+        // Escape codes of the header take no columns.
+        let md_syntax = parse_md_str("| a | b |\n|--|--|\n| long | x |\n");
+        assert_eq!(
+            compile_all_with(&md_syntax, &AnsiOptions::default()),
+            "\x1b[1ma\x1b[22m     \x1b[1mb\x1b[22m\n----  -\nlong  x\n"
+        );
+    }
+
+    #[test]
+    fn t_no_color() {
+        let options = AnsiOptions::from_vars(Some("1".to_string()), Some("24".to_string()));
+        let md_syntax = parse_md_str(
+            "## Part\nwords that wrap around [gnu](https://gnu.org)\n- item\n\n```rust\nfn main() {}\n```"
+        );
+        assert_eq!(
+            compile_all_with(&md_syntax, &options),
+            "## Part\n\nwords that wrap around\ngnu <https://gnu.org>\n\n• item\n\n\
+                ┌─ rust ───────┐\n│ fn main() {} │\n└──────────────┘\n"
+        );
+        assert!(AnsiOptions::from_vars(Some(String::new()), None).color);
+        assert_eq!(AnsiOptions::from_vars(None, Some("x".to_string())).width, 80);
+    }
+
+    #[test]
+    fn t_visible_width() {
+        assert_eq!(visible_width("\x1b[1mbold\x1b[22m"), 4);
+        assert_eq!(visible_width("\x1b]8;;https://gnu.org\x1b\\gnu\x1b]8;;\x1b\\"), 3);
+        // Wide characters take two columns, combining marks none.
+        assert_eq!(visible_width("日本"), 4);
+        assert_eq!(visible_width("e\u{301}"), 1);
+    }
+
+    #[test]
+    fn t_control_characters() {
+        // Escape sequences in the document can't reach the terminal, neither in text nor in urls.
+        let md_syntax = parse_md_str("Hi \x1b]0;title\x07there [a](https://x.org/\x1b\\\x1b]8;;evil)\n");
+        assert_eq!(
+            compile_all_with(&md_syntax, &AnsiOptions::default()),
+            "Hi ]0;titlethere \x1b]8;;https://x.org/\\]8;;evil\x1b\\\x1b[4ma\x1b[24m\x1b]8;;\x1b\\\n"
+        );
+        assert_eq!(strip_controls("a\u{9b}b\u{7f}\tc\n"), "ab\tc\n");
+    }
+}
@@ -0,0 +1,275 @@
+use crate::tokens::*;
+use super::renderer::{escape_line_start, indent};
+use super::{render, BlockCollector, NodeContext, Renderer, Visit};
+
+/*
+ * AsciiDoc target. Headers become `=` sections, emphasis `_` and `*` quotes, lists `*` items,
+ * definition lists `term::` entries, tables `|===` blocks and code `----` listing blocks with a
+ * source language. Admonitions use AsciiDoc's own admonition blocks and math the `latexmath`
+ * macros.
+ *
+ * A backslash only escapes AsciiDoc markup where the markup would otherwise apply, so special
+ * characters in text are written as attribute references or passthroughs instead.
+ */
+
+#[derive(Default)]
+pub struct AsciidocOptions {
+    // Added to header levels, 0 keeps `# Title` as the `= Title` document title.
+    pub level_offset: u32
+}
+
+pub fn compile_all(input: MdSyntaxTree) -> String {
+    return compile_all_with(&input, &AsciidocOptions::default());
+}
+
+pub fn compile_all_with(input: &MdSyntaxTree, options: &AsciidocOptions) -> String {
+    let mut renderer = AsciidocRenderer::new(options);
+    render(input, &mut renderer);
+    return renderer.finish();
+}
+
+/**
+ Renderer for the AsciiDoc target, see `BlockCollector`.
+*/
+pub struct AsciidocRenderer<'a> {
+    options: &'a AsciidocOptions,
+    blocks: BlockCollector
+}
+
+impl<'a> AsciidocRenderer<'a> {
+    pub fn new(options: &'a AsciidocOptions) -> Self {
+        AsciidocRenderer{options, blocks: BlockCollector::new()}
+    }
+
+    pub fn finish(self) -> String {
+        self.blocks.finish()
+    }
+}
+
+impl Renderer for AsciidocRenderer<'_> {
+    fn enter(&mut self, token: &Token, _: &NodeContext) -> Visit {
+        let container = token.is_container() || matches!(token, Token::Definition(_));
+        self.blocks.enter(container.then_some(0));
+        return Visit::Children;
+    }
+
+    fn exit(&mut self, token: &Token, context: &NodeContext) {
+        let content = self.blocks.exit();
+        if context.inside(|t| matches!(t, Token::Code(_) | Token::InlineCode(_))) {
+            // Code is literal, inline tokens in it keep only their text.
+            if let Token::PlainText(t) = token { self.blocks.push_inline(&t.text()) } else { self.blocks.push_inline(&content) }
+            return;
+        }
+        let (word_before, word_after) = adjacent_words(context);
+        match token {
+            Token::PlainText(t) => self.blocks.push_inline(&escape_asciidoc(&t.text())),
+            // Doubled marks also apply inside words.
+            Token::Italic(_) | Token::Bold(_) => {
+                let mark = if matches!(token, Token::Italic(_)) { "_" } else { "*" };
+                let mark = if word_before || word_after { mark.repeat(2) } else { mark.to_string() };
+                self.blocks.push_inline(&format!("{}{}{}", mark, content, mark));
+            },
+            Token::InlineCode(_) => self.blocks.push_inline(&format!("`+{}+`", content)),
+            Token::Link(l) => {
+                let text = content.replace(']', "{endsb}");
+                let link = match l.url.strip_prefix('#') {
+                    Some(id) if text.is_empty() => format!("<<{}>>", id),
+                    Some(id) => format!("<<{},{}>>", id, text),
+                    None if l.url.contains("://") || l.url.starts_with("mailto:") => format!("{}[{}]", l.url, text),
+                    None => format!("link:{}[{}]", l.url.replace(' ', "%20"), text)
+                };
+                self.blocks.push_inline(&link);
+            },
+            Token::InlineMath(m) => self.blocks.push_inline(&format!("latexmath:[{}]", m.src().replace(']', "\\]"))),
+            Token::DisplayMath(m) if context.depth > 0 => {
+                self.blocks.push_inline(&format!("latexmath:[{}]", m.src().replace(']', "\\]")));
+            },
+            Token::Directive(d) if d.inline => self.blocks.push_inline(&format!("[.{}]#{}#", d.name, content)),
+            Token::Paragraph(_) => {
+                let line = content.trim();
+                let line = if is_label(line) { format!("{{empty}}{}", line) } else { escape_line_start(line, LINE_START, "{empty}") };
+                self.blocks.push_line(&line);
+            },
+            Token::Header(h) => {
+                let level = (h.level + self.options.level_offset).clamp(1, 6) as usize;
+                let anchor = h.attributes.get("id").map(|id| format!("[#{}]\n", id)).unwrap_or_default();
+                self.blocks.push_block(format!("{}{} {}", anchor, "=".repeat(level), content.trim()));
+            },
+            Token::Code(c) => {
+                let code = content.strip_suffix('\n').unwrap_or(&content);
+                let delimiter = "-".repeat(fence_length(code, '-'));
+                let style = c.lang.as_ref().map(|l| format!("[source,{}]\n", l)).unwrap_or_default();
+                self.blocks.push_block(format!("{}{}\n{}\n{}", style, delimiter, code, delimiter));
+            },
+            Token::DisplayMath(m) => self.blocks.push_block(format!("[latexmath]\n++++\n{}\n++++", m.src())),
+            Token::TableOfContents(_) => self.blocks.push_block("toc::[]".to_string()),
+            Token::ListItem(_) => {
+                // Nested lists repeat the marker of their kind.
+                let ordered = context.item_number().is_some();
+                let depth = context.ancestors.iter()
+                    .filter(|t| matches!(t, Token::OrderedList(_)) == ordered && t.is_list())
+                    .count();
+                let marker = if ordered { "." } else { "*" };
+                self.blocks.push_block(format!("{} {}", marker.repeat(depth), content.trim()));
+            },
+            Token::List(_) => {
+                let items = self.blocks.pop_frame().join("\n");
+                self.blocks.push_block(items);
+            },
+            Token::OrderedList(l) => {
+                let items = self.blocks.pop_frame().join("\n");
+                let start = if l.start == 1 { String::new() } else { format!("[start={}]\n", l.start) };
+                self.blocks.push_block(format!("{}{}", start, items));
+            },
+            Token::DefinitionTerm(_) => self.blocks.push_block(format!("{}::", content.trim())),
+            Token::Definition(_) => {
+                // Further paragraphs are attached to the entry with list continuations.
+                let definition = indent(&self.blocks.pop_frame().join("\n+\n"), "  ");
+                if matches!(context.previous_sibling(), Some(Token::Definition(_))) {
+                    self.blocks.push_block(format!("+\n{}", definition.trim_start()));
+                } else {
+                    self.blocks.push_block(definition);
+                }
+            },
+            Token::DefinitionList(_) => {
+                let entries = self.blocks.pop_frame().join("\n");
+                self.blocks.push_block(entries);
+            },
+            Token::TableCell(_) => self.blocks.push_block(format!("|{}", content.trim().replace('|', "\\|"))),
+            Token::TableRow(r) => {
+                // A blank line after the first row makes it the header.
+                let cells = self.blocks.pop_frame().join(" ");
+                self.blocks.push_block(if r.header { format!("{}\n", cells) } else { cells });
+            },
+            Token::Table(t) => {
+                let rows = self.blocks.pop_frame().join("\n");
+                let cols = t.alignments.iter()
+                    .map(|a| match a {
+                        Alignment::Left => "<",
+                        Alignment::Center => "^",
+                        Alignment::Right => ">",
+                        Alignment::None => "1"
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",");
+                self.blocks.push_block(format!("[cols=\"{}\"]\n|===\n{}\n|===", cols, rows));
+            },
+            Token::Admonition(a) => {
+                let label = match a.kind {
+                    AdmonitionKind::Note | AdmonitionKind::Info => "NOTE",
+                    AdmonitionKind::Tip => "TIP",
+                    AdmonitionKind::Important => "IMPORTANT",
+                    AdmonitionKind::Warning | AdmonitionKind::Danger => "WARNING",
+                    AdmonitionKind::Caution => "CAUTION"
+                };
+                let title = a.title.as_ref().map(|t| format!(".{}\n", escape_asciidoc(t))).unwrap_or_default();
+                let inner = self.blocks.pop_frame().join("\n\n");
+                self.blocks.push_block(format!("[{}]\n{}====\n{}\n====", label, title, inner));
+            },
+            Token::Directive(d) => {
+                let inner = self.blocks.pop_frame().join("\n\n");
+                self.blocks.push_block(format!("[.{}]\n--\n{}\n--", d.name, inner));
+            }
+        }
+    }
+}
+
+// Whether the text next to a token continues a word on either side.
+pub(crate) fn adjacent_words(context: &NodeContext) -> (bool, bool) {
+    let before = matches!(
+        context.previous_sibling(),
+        Some(Token::PlainText(t)) if t.text.ends_with(char::is_alphanumeric)
+    );
+    let after = matches!(
+        context.next_sibling(),
+        Some(Token::PlainText(t)) if t.text.starts_with(char::is_alphanumeric)
+    );
+    return (before, after);
+}
+
+// Length of a block delimiter that no line of the content is equal to.
+pub(crate) fn fence_length(content: &str, marker: char) -> usize {
+    let longest = content.lines()
+        .filter(|line| !line.is_empty() && line.chars().all(|c| c == marker))
+        .map(|line| line.len())
+        .max()
+        .unwrap_or(0);
+    return (longest + 1).max(4);
+}
+
+/**
+ Escape the characters that could start AsciiDoc markup in text.
+*/
+pub fn escape_asciidoc(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut escaped = String::with_capacity(text.len());
+    for (i, c) in chars.iter().enumerate() {
+        let word_before = i > 0 && chars[i - 1].is_alphanumeric();
+        let word_after = chars.get(i + 1).is_some_and(|c| c.is_alphanumeric());
+        match c {
+            '*' => escaped.push_str("{asterisk}"),
+            '`' => escaped.push_str("{backtick}"),
+            '^' => escaped.push_str("{caret}"),
+            '~' => escaped.push_str("{tilde}"),
+            '+' => escaped.push_str("{plus}"),
+            '\\' => escaped.push_str("{backslash}"),
+            '[' => escaped.push_str("{startsb}"),
+            ']' => escaped.push_str("{endsb}"),
+            '{' => escaped.push_str("++{++"),
+            '<' if chars.get(i + 1) == Some(&'<') => escaped.push_str("{lt}"),
+            // Quotes inside words, eg. snake_case, don't apply.
+            '_' | '#' if !(word_before && word_after) => escaped.push_str(&format!("++{}++", c)),
+            _ => escaped.push(*c)
+        }
+    }
+    return escaped;
+}
+
+// Lines starting with these would be read as block markup, eg. a section title or block title.
+const LINE_START: &[&str] = &["=", "-", ".", "/", "|", "'", ":"];
+
+// Lines starting with a label, eg. `NOTE: `, would be admonition paragraphs.
+fn is_label(line: &str) -> bool {
+    line.split_once(": ").is_some_and(|(label, _)| {
+        matches!(label, "NOTE" | "TIP" | "IMPORTANT" | "WARNING" | "CAUTION")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    #[test]
+    fn t_asciidoc() {
+        let md_syntax = parse_md_str(
+            "# Title\n## Usage {#usage}\nSome *new* **bold**text and [docs](https://gnu.org) or [back](#usage).\n\
+            a snake_case x*y [tag] _private\n\n- one\n- `a*b`\n\n```rust\nlet x = 1;\n```\nTerm\n: First\n: Second\n\
+            \n> [!WARNING] Careful\n> Text\n"
+        );
+        assert_eq!(
+            compile_all(md_syntax),
+            "= Title\n\n[#usage]\n== Usage\n\n\
+                Some _new_ **bold**text and https://gnu.org[docs] or <<usage,back>>.\n\
+                a snake_case x{asterisk}y {startsb}tag{endsb} ++_++private\n\n\
+                * one\n* `+a*b+`\n\n[source,rust]\n----\nlet x = 1;\n----\n\nTerm::\n  First\n+\nSecond\n\n\
+                [WARNING]\n.Careful\n====\nText\n====\n"
+        );
+    }
+
+    #[test]
+    fn t_line_start() {
+        let md_syntax = parse_md_str(".hidden\nNOTE: not a label\nplain\n");
+        assert_eq!(compile_all(md_syntax), "{empty}.hidden\n{empty}NOTE: not a label\nplain\n");
+        assert_eq!(fence_length("a\n----\nb", '-'), 5);
+    }
+
+    #[test]
+    fn t_table() {
+        let md_syntax = parse_md_str("| Item | Price |\n|:--|--:|\n| *tea* | 3 |\n| a \\| b |\n");
+        assert_eq!(
+            compile_all(md_syntax),
+            "[cols=\"<,>\"]\n|===\n|Item |Price\n\n|_tea_ |3\n|a \\| b |\n|===\n"
+        );
+    }
+}
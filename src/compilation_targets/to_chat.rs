@@ -0,0 +1,293 @@
+use crate::tokens::*;
+use super::renderer::{escape_line_start, text_table};
+use super::{render, BlockCollector, NodeContext, Renderer, Visit};
+
+/*
+ * Chat platform targets: Slack mrkdwn, Discord Markdown and Jira wiki markup, eg. for posting
+ * release notes. Constructs a dialect can't express degrade to something readable: headers Slack
+ * has no markup for become bold lines, admonitions quotes or panels, math code, tables aligned
+ * text in a code block outside of Jira and the table of contents is left out.
+ */
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ChatDialect {
+    // Slack mrkdwn: `*bold*`, `_italic_`, `<url|text>`.
+    Slack,
+    // Discord Markdown: CommonMark-like with headers up to level 3.
+    Discord,
+    // Jira and Confluence wiki markup: `h1.`, `{code}`, `[text|url]`.
+    Jira
+}
+
+pub fn compile_all(input: MdSyntaxTree, dialect: ChatDialect) -> String {
+    return compile_all_with(&input, dialect);
+}
+
+pub fn compile_all_with(input: &MdSyntaxTree, dialect: ChatDialect) -> String {
+    let mut renderer = ChatRenderer::new(dialect);
+    render(input, &mut renderer);
+    return renderer.finish();
+}
+
+/**
+ Renderer for the chat targets, see `BlockCollector`.
+*/
+pub struct ChatRenderer {
+    dialect: ChatDialect,
+    blocks: BlockCollector
+}
+
+impl ChatRenderer {
+    pub fn new(dialect: ChatDialect) -> Self {
+        ChatRenderer{dialect, blocks: BlockCollector::new()}
+    }
+
+    pub fn finish(self) -> String {
+        return self.blocks.finish();
+    }
+
+    fn escape(&self, text: &str) -> String {
+        match self.dialect {
+            // Slack has no escapes for its formatting characters, only these three must be entities.
+            ChatDialect::Slack => text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;"),
+            ChatDialect::Discord => escape_with(text, &['\\', '*', '_', '~', '`', '|', '[', ']']),
+            // A single `?` is left alone, only the `??` of a citation is markup.
+            ChatDialect::Jira => escape_with(text, &['\\', '*', '_', '{', '}', '[', ']', '|', '!', '^', '~', '-', '+'])
+                .replace("??", "\\?\\?")
+        }
+    }
+
+    // Markers that would start a block at the beginning of a line, eg. a quote or a list.
+    fn line_start_markers(&self) -> &'static [&'static str] {
+        match self.dialect {
+            ChatDialect::Slack => &[],
+            ChatDialect::Discord => &[">", "#", "-", "+"],
+            ChatDialect::Jira => &["#", "-", "+"]
+        }
+    }
+
+    fn bold(&self, text: &str) -> String {
+        match self.dialect {
+            ChatDialect::Slack | ChatDialect::Jira => format!("*{}*", text),
+            ChatDialect::Discord => format!("**{}**", text)
+        }
+    }
+
+    fn italic(&self, text: &str) -> String {
+        match self.dialect {
+            ChatDialect::Slack | ChatDialect::Jira => format!("_{}_", text),
+            ChatDialect::Discord => format!("*{}*", text)
+        }
+    }
+
+    fn inline_code(&self, code: &str) -> String {
+        match self.dialect {
+            ChatDialect::Jira => format!("{{{{{}}}}}", code),
+            _ => format!("`{}`", code)
+        }
+    }
+
+    fn code_block(&self, lang: Option<&str>, code: &str) -> String {
+        let code = code.strip_suffix('\n').unwrap_or(code);
+        match (self.dialect, lang) {
+            // Slack doesn't highlight code, the language is dropped.
+            (ChatDialect::Slack, _) => format!("```\n{}\n```", code),
+            (ChatDialect::Discord, lang) => format!("```{}\n{}\n```", lang.unwrap_or(""), code),
+            (ChatDialect::Jira, Some(lang)) => format!("{{code:{}}}\n{}\n{{code}}", lang, code),
+            (ChatDialect::Jira, None) => format!("{{code}}\n{}\n{{code}}", code)
+        }
+    }
+
+    fn link(&self, url: &str, text: &str) -> String {
+        // Links within the document only work in Jira, elsewhere the text is kept.
+        let internal = url.starts_with('#');
+        if url.is_empty() || internal && self.dialect != ChatDialect::Jira { return text.to_string() }
+        let bare = text.is_empty() || text == url;
+        match self.dialect {
+            ChatDialect::Slack if bare => format!("<{}>", url),
+            ChatDialect::Slack => format!("<{}|{}>", url, text),
+            ChatDialect::Discord if bare => url.to_string(),
+            ChatDialect::Discord => format!("[{}]({})", text, url),
+            ChatDialect::Jira if bare => format!("[{}]", url),
+            ChatDialect::Jira => format!("[{}|{}]", text, url)
+        }
+    }
+
+    fn header(&self, level: u32, text: &str) -> String {
+        match self.dialect {
+            ChatDialect::Slack => self.bold(text),
+            ChatDialect::Discord if level <= 3 => format!("{} {}", "#".repeat(level as usize), text),
+            ChatDialect::Discord => self.bold(text),
+            ChatDialect::Jira => format!("h{}. {}", level.clamp(1, 6), text)
+        }
+    }
+
+    // Admonitions and other boxed content: a quote in Slack and Discord, a panel in Jira.
+    fn boxed(&self, title: Option<&str>, blocks: &[String]) -> String {
+        let content = blocks.join("\n\n");
+        if self.dialect == ChatDialect::Jira {
+            let title = title.map(|t| format!(":title={}", t.replace(['|', '}'], ""))).unwrap_or_default();
+            return format!("{{panel{}}}\n{}\n{{panel}}", title, content);
+        }
+        let mut lines: Vec<String> = title.map(|t| self.bold(t)).into_iter().collect();
+        lines.extend(content.lines().map(|l| l.to_string()));
+        return lines.iter()
+            .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    fn bullet(&self) -> &'static str {
+        match self.dialect {
+            ChatDialect::Slack => "•",
+            ChatDialect::Discord => "-",
+            ChatDialect::Jira => "*"
+        }
+    }
+}
+
+impl Renderer for ChatRenderer {
+    fn enter(&mut self, token: &Token, _: &NodeContext) -> Visit {
+        let container = token.is_container() || matches!(token, Token::Definition(_));
+        self.blocks.enter(container.then_some(0));
+        return Visit::Children;
+    }
+
+    fn exit(&mut self, token: &Token, context: &NodeContext) {
+        let content = self.blocks.exit();
+        // Tables outside of Jira are written in code blocks.
+        let literal_table = self.dialect != ChatDialect::Jira && context.inside(|t| matches!(t, Token::TableCell(_)));
+        if literal_table || context.inside(|t| matches!(t, Token::Code(_) | Token::InlineCode(_))) {
+            // Code is literal, inline tokens in it keep only their text.
+            if let Token::PlainText(t) = token { self.blocks.push_inline(&t.text()) } else { self.blocks.push_inline(&content) }
+            return;
+        }
+        match token {
+            Token::PlainText(t) => self.blocks.push_inline(&self.escape(&t.text())),
+            Token::Italic(_) => self.blocks.push_inline(&self.italic(&content)),
+            Token::Bold(_) => self.blocks.push_inline(&self.bold(&content)),
+            Token::InlineCode(_) => self.blocks.push_inline(&self.inline_code(&content)),
+            Token::Link(l) => self.blocks.push_inline(&self.link(&l.url, &content)),
+            Token::InlineMath(m) => self.blocks.push_inline(&self.inline_code(&m.src())),
+            Token::DisplayMath(m) if context.depth > 0 => self.blocks.push_inline(&self.inline_code(&m.src())),
+            Token::Directive(d) if d.inline => self.blocks.push_inline(&content),
+            Token::Paragraph(_) => {
+                let line = escape_line_start(content.trim(), self.line_start_markers(), "\\");
+                self.blocks.push_line(&line);
+            },
+            Token::Header(h) => self.blocks.push_block(self.header(h.level, content.trim())),
+            Token::Code(c) => self.blocks.push_block(self.code_block(c.lang.as_deref(), &content)),
+            Token::DisplayMath(m) => self.blocks.push_block(self.code_block(None, &m.src())),
+            Token::TableOfContents(_) => {},
+            Token::ListItem(_) => {
+                let lists: Vec<&&Token> = context.ancestors.iter().filter(|t| t.is_list()).collect();
+                let depth = lists.len().max(1);
+                let item = match (self.dialect, context.item_number()) {
+                    // Jira stacks the markers of all enclosing lists, eg. `#*` for a bullet in a numbered list.
+                    (ChatDialect::Jira, _) => {
+                        let markers: String = lists.iter()
+                            .map(|t| if matches!(t, Token::OrderedList(_)) { '#' } else { '*' })
+                            .collect();
+                        format!("{} {}", markers, content.trim())
+                    },
+                    (_, Some(number)) => format!("{}{}. {}", "  ".repeat(depth - 1), number, content.trim()),
+                    (_, None) => format!("{}{} {}", "  ".repeat(depth - 1), self.bullet(), content.trim())
+                };
+                self.blocks.push_block(item);
+            },
+            Token::DefinitionTerm(_) => self.blocks.push_block(self.bold(content.trim())),
+            Token::List(_) | Token::OrderedList(_) | Token::DefinitionList(_) | Token::Definition(_) => {
+                let items = self.blocks.pop_frame().join("\n");
+                self.blocks.push_block(items);
+            },
+            Token::TableCell(_) => self.blocks.push_block(content.trim().to_string()),
+            Token::TableRow(_) => self.blocks.push_row(),
+            Token::Table(t) => {
+                let rows = self.blocks.take_rows();
+                let header = context.children().any(|r| matches!(r, Token::TableRow(r) if r.header));
+                let table = match self.dialect {
+                    // Jira marks header cells with double pipes.
+                    ChatDialect::Jira => rows.iter().enumerate()
+                        .map(|(i, row)| {
+                            let pipe = if i == 0 && header { "||" } else { "|" };
+                            let cells: Vec<&str> = row.iter().map(|c| if c.is_empty() { " " } else { c.as_str() }).collect();
+                            format!("{}{}{}", pipe, cells.join(pipe), pipe)
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    _ => self.code_block(None, &text_table(&rows, &t.alignments, header))
+                };
+                self.blocks.push_block(table);
+            },
+            Token::Admonition(a) => {
+                let inner = self.blocks.pop_frame();
+                self.blocks.push_block(self.boxed(Some(&a.display_title()), &inner));
+            },
+            Token::Directive(_) => {
+                let inner = self.blocks.pop_frame().join("\n\n");
+                if !inner.is_empty() { self.blocks.push_block(inner) }
+            }
+        }
+    }
+}
+
+fn escape_with(text: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(&c) { escaped.push('\\') }
+        escaped.push(c);
+    }
+    return escaped;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    const SOURCE: &str = "# Release 1.2\nSome *new* **bold** text & [docs](https://gnu.org) [back](#release-12)\n\
+        - `one`\n- two\n\n```rust\nlet x = 1;\n```\n> [!NOTE]\n> Careful\n";
+
+    #[test]
+    fn t_slack() {
+        assert_eq!(
+            compile_all(parse_md_str(SOURCE), ChatDialect::Slack),
+            "*Release 1.2*\n\nSome _new_ *bold* text &amp; <https://gnu.org|docs> back\n\n• `one`\n• two\n\n\
+                ```\nlet x = 1;\n```\n\n> *Note*\n> Careful\n"
+        );
+    }
+
+    #[test]
+    fn t_discord() {
+        assert_eq!(
+            compile_all(parse_md_str(SOURCE), ChatDialect::Discord),
+            "# Release 1.2\n\nSome *new* **bold** text & [docs](https://gnu.org) back\n\n- `one`\n- two\n\n\
+                ```rust\nlet x = 1;\n```\n\n> **Note**\n> Careful\n"
+        );
+        assert_eq!(compile_all(parse_md_str("#### Deep\n"), ChatDialect::Discord), "**Deep**\n");
+    }
+
+    #[test]
+    fn t_jira() {
+        assert_eq!(
+            compile_all(parse_md_str(SOURCE), ChatDialect::Jira),
+            "h1. Release 1.2\n\nSome _new_ *bold* text & [docs|https://gnu.org] [back|#release-12]\n\n* {{one}}\n* two\n\n\
+                {code:rust}\nlet x = 1;\n{code}\n\n{panel:title=Note}\nCareful\n{panel}\n"
+        );
+        assert_eq!(compile_all(parse_md_str("a*b [c] {d}\n"), ChatDialect::Jira), "a\\*b \\[c\\] \\{d\\}\n");
+        assert_eq!(compile_all(parse_md_str("a -b- +c+ ??d?? e?\n"), ChatDialect::Jira), "a \\-b\\- \\+c\\+ \\?\\?d\\?\\? e?\n");
+    }
+
+    #[test]
+    fn t_table() {
+        let source = "| Item | Price |\n|:--|--:|\n| *tea* | 3 |\n| coffee | |\n";
+        assert_eq!(
+            compile_all(parse_md_str(source), ChatDialect::Jira),
+            "||Item||Price||\n|_tea_|3|\n|coffee| |\n"
+        );
+        assert_eq!(
+            compile_all(parse_md_str(source), ChatDialect::Slack),
+            "```\nItem    Price\n------  -----\ntea         3\ncoffee\n```\n"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use crate::tokens::*;
+use super::renderer::{escape_line_start, text_table};
+use super::{render, BlockCollector, NodeContext, Renderer, Visit};
+
+/*
+ * Gemini gemtext (text/gemini) target. Gemtext is line based: headers are clamped to three
+ * levels, list items become `* ` lines, code and tables with aligned columns preformatted blocks
+ * and each paragraph a single line that the client wraps. There are no inline links or
+ * formatting, so emphasis is dropped and the links of a block are listed as `=> url text` lines
+ * after it.
+ */
+
+pub fn compile_all(input: MdSyntaxTree) -> String {
+    return compile_all_with(&input);
+}
+
+pub fn compile_all_with(input: &MdSyntaxTree) -> String {
+    let mut renderer = GeminiRenderer::new();
+    render(input, &mut renderer);
+    return renderer.finish();
+}
+
+/**
+ Renderer for the gemtext target, see `BlockCollector`. Paragraphs are taken from the collector
+ rather than flushed by it, to put their links after them.
+*/
+pub struct GeminiRenderer {
+    // Lines of a paragraph are joined, the client wraps them.
+    blocks: BlockCollector,
+    // Link lines of the block being rendered, written after it once it's done.
+    links: Vec<String>
+}
+
+impl Default for GeminiRenderer {
+    fn default() -> Self {
+        GeminiRenderer::new()
+    }
+}
+
+impl GeminiRenderer {
+    pub fn new() -> Self {
+        GeminiRenderer{blocks: BlockCollector::new().joiner(" "), links: Vec::new()}
+    }
+
+    pub fn finish(mut self) -> String {
+        self.flush_paragraph();
+        return self.blocks.finish();
+    }
+
+    // Add a block, at the top level followed by the links in it. Links in containers wait for the
+    // container, as link lines inside a quote wouldn't be links.
+    fn add_block(&mut self, block: String) {
+        let links = if self.blocks.at_top_level() { std::mem::take(&mut self.links) } else { Vec::new() };
+        // A paragraph that is only a link is replaced by the link line.
+        if links.len() == 1 && links[0].ends_with(&format!(" {}", block)) {
+            self.blocks.push_block(links[0].clone());
+        } else if links.is_empty() {
+            self.blocks.push_block(block);
+        } else {
+            self.blocks.push_block(format!("{}\n{}", block, links.join("\n")));
+        }
+    }
+
+    fn flush_paragraph(&mut self) {
+        if let Some(paragraph) = self.blocks.take_paragraph() {
+            self.add_block(paragraph);
+        }
+    }
+
+    fn push_block(&mut self, block: String) {
+        self.flush_paragraph();
+        self.add_block(block);
+    }
+
+    fn pop_frame(&mut self) -> Vec<String> {
+        self.flush_paragraph();
+        return self.blocks.pop_frame();
+    }
+
+    fn link(&mut self, url: &str, text: &str) {
+        // Gemtext has no anchors to link to within a document.
+        if url.is_empty() || url.starts_with('#') { return }
+        let line = if text.is_empty() || text == url { format!("=> {}", url) } else { format!("=> {} {}", url, text) };
+        if !self.links.contains(&line) { self.links.push(line) }
+    }
+}
+
+impl Renderer for GeminiRenderer {
+    fn enter(&mut self, token: &Token, _: &NodeContext) -> Visit {
+        // An open paragraph ends before the next block collects links of its own.
+        if token.is_block() && !matches!(token, Token::Paragraph(_)) { self.flush_paragraph() }
+        let container = token.is_container() || matches!(token, Token::Definition(_));
+        self.blocks.enter(container.then_some(0));
+        return Visit::Children;
+    }
+
+    fn exit(&mut self, token: &Token, context: &NodeContext) {
+        let content = self.blocks.exit();
+        if context.inside(|t| matches!(t, Token::Code(_))) {
+            if let Token::PlainText(t) = token { self.blocks.push_inline(&t.text()) } else { self.blocks.push_inline(&content) }
+            return;
+        }
+        match token {
+            Token::PlainText(t) => self.blocks.push_inline(&t.text()),
+            Token::Italic(_) | Token::Bold(_) => self.blocks.push_inline(&content),
+            Token::InlineCode(_) => self.blocks.push_inline(&format!("`{}`", content)),
+            Token::Link(l) => {
+                self.link(&l.url, content.trim());
+                self.blocks.push_inline(if content.is_empty() { &l.url } else { &content });
+            },
+            Token::InlineMath(m) => self.blocks.push_inline(&m.src()),
+            Token::DisplayMath(m) if context.depth > 0 => self.blocks.push_inline(&m.src()),
+            Token::Directive(d) if d.inline => self.blocks.push_inline(&content),
+            Token::Paragraph(_) => {
+                let line = content.trim();
+                if line.is_empty() {
+                    self.flush_paragraph();
+                    return;
+                }
+                self.blocks.push_line(&escape_line_start(line, LINE_START, " "));
+            },
+            Token::Header(h) => {
+                let level = h.level.clamp(1, 3) as usize;
+                self.push_block(format!("{} {}", "#".repeat(level), content.trim()));
+            },
+            Token::Code(c) => {
+                let code = content.strip_suffix('\n').unwrap_or(&content);
+                self.push_block(format!("```{}\n{}\n```", c.lang.as_deref().unwrap_or(""), code));
+            },
+            Token::DisplayMath(m) => self.push_block(format!("```tex\n{}\n```", m.src())),
+            // Gemtext has no anchors for a table of contents to link to.
+            Token::TableOfContents(_) => {},
+            Token::ListItem(_) | Token::DefinitionTerm(_) => {
+                // Gemtext only has bulleted lists, numbered items are text lines of their own.
+                let marker = match (token, context.item_number()) {
+                    (Token::ListItem(_), Some(number)) => format!("{}. ", number),
+                    (Token::ListItem(_), None) => "* ".to_string(),
+                    _ => String::new()
+                };
+                self.push_block(format!("{}{}", marker, content.trim()));
+            },
+            Token::List(_) | Token::OrderedList(_) | Token::DefinitionList(_) | Token::Definition(_) => {
+                let lines = self.pop_frame().join("\n");
+                self.push_block(lines);
+            },
+            Token::TableCell(_) => self.blocks.push_block(content.trim().to_string()),
+            Token::TableRow(_) => self.blocks.push_row(),
+            Token::Table(t) => {
+                let rows = self.blocks.take_rows();
+                let header = context.children().any(|r| matches!(r, Token::TableRow(r) if r.header));
+                self.push_block(format!("```\n{}\n```", text_table(&rows, &t.alignments, header)));
+            },
+            Token::Admonition(a) => {
+                let mut lines = vec![format!("> {}", a.display_title())];
+                let inner = self.pop_frame().join("\n");
+                lines.extend(inner.lines().filter(|l| !l.is_empty()).map(|l| format!("> {}", l)));
+                self.push_block(lines.join("\n"));
+            },
+            Token::Directive(_) => {
+                let inner = self.pop_frame().join("\n\n");
+                if !inner.is_empty() { self.push_block(inner) }
+            }
+        }
+    }
+}
+
+// Text lines starting like another line type get a leading space, gemtext has no escapes.
+const LINE_START: &[&str] = &["#", ">", "=>", "* ", "```"];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    #[test]
+    fn t_gemini() {
+        let md_syntax = parse_md_str(
+            "# Blog\n#### Deep *header*\nRead the *new* [docs](gemini://example.org/docs) and [notes](/notes)\n\
+            across two lines with [docs](gemini://example.org/docs) again.\n\n[Archive](/archive)\n\
+            - one [a](/a)\n- two\n\n```rust\nlet x = 1;\n```\n> [!TIP]\n> See [b](/b)\n"
+        );
+        assert_eq!(
+            compile_all(md_syntax),
+            "# Blog\n\n### Deep header\n\n\
+                Read the new docs and notes across two lines with docs again.\n\
+                => gemini://example.org/docs docs\n=> /notes notes\n\n\
+                => /archive Archive\n\n* one a\n* two\n=> /a a\n\n```rust\nlet x = 1;\n```\n\n\
+                > Tip\n> See b\n=> /b b\n"
+        );
+    }
+
+    #[test]
+    fn t_line_start() {
+        let md_syntax = parse_md_str("=> not a link\n\n#hashtag\n\n*emphasis* stays\n");
+        assert_eq!(compile_all(md_syntax), " => not a link\n\n #hashtag\n\nemphasis stays\n");
+    }
+
+    #[test]
+    fn t_table() {
+        let md_syntax = parse_md_str("| Item | Price |\n|:--|--:|\n| [tea](/tea) | 3 |\n");
+        assert_eq!(compile_all(md_syntax), "```\nItem  Price\n----  -----\ntea       3\n```\n=> /tea tea\n");
+    }
+}
@@ -1,59 +1,515 @@
+use std::collections::HashMap;
+use std::{fmt, io};
+
 use crate::tokens::*;
+use crate::tree::TreeIndex;
+use crate::slug::header_slugs;
+use crate::toc::{build_toc, toc_to_html, TocOptions};
+use super::mathml::tex_to_mathml;
+use super::directives::DirectiveRegistry;
+use super::{render, render_line, Compile, NodeContext, Renderer, Visit};
 
 type TargetHTML = String;
-type HtmlTags = (String, String);
+pub type HtmlTags = (String, String);
+
+/**
+ Replaces the tags of a token. Gets the token and the tags it would have been compiled to.
+*/
+pub type TokenOverride = Box<dyn Fn(&Token, HtmlTags) -> HtmlTags>;
+
+/**
+ How math tokens are emitted: wrapped in spans for rendering on the client (eg. KaTeX or
+ MathJax), or converted to MathML.
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MathOutput {
+    Span,
+    MathML
+}
+
+/**
+ Which HTML is produced for the basic Markdown constructs. `Legacy` is what the web preview
+ expects: text runs in spans, lines in divs and `<br>` for blank lines. `CommonMark` matches the
+ output of the CommonMark reference implementation: `<p>`, `<em>`, `<pre><code>`, escaped text
+ and a newline after each block.
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HtmlFlavor {
+    Legacy,
+    CommonMark
+}
+
+/**
+ Whitespace between the tags. `Compact` adds none besides the newlines of the CommonMark flavor,
+ `Pretty` puts block elements on their own lines indented by `indent` spaces per level and
+ `Minified` also collapses runs of whitespace in text. Code blocks are always left as they are.
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HtmlLayout {
+    Compact,
+    Pretty{indent: usize},
+    Minified
+}
+
+/**
+ Class names and icons of admonitions. Rendered as
+ `<div class="{class} {kind class}"><p class="{title_class}">{icon}{title}</p>...</div>`.
+*/
+pub struct AdmonitionStyle {
+    pub class: String,
+    pub title_class: String,
+    pub icon_class: String,
+    pub kind_classes: HashMap<AdmonitionKind, String>,
+    pub icons: HashMap<AdmonitionKind, String>
+}
+
+impl Default for AdmonitionStyle {
+    fn default() -> Self {
+        let kinds = [
+            (AdmonitionKind::Note, "ℹ️"),
+            (AdmonitionKind::Tip, "💡"),
+            (AdmonitionKind::Info, "ℹ️"),
+            (AdmonitionKind::Important, "❗"),
+            (AdmonitionKind::Warning, "⚠️"),
+            (AdmonitionKind::Caution, "🛑"),
+            (AdmonitionKind::Danger, "🔥"),
+        ];
+        AdmonitionStyle{
+            class: "admonition".to_string(),
+            title_class: "admonition-title".to_string(),
+            icon_class: "admonition-icon".to_string(),
+            kind_classes: kinds.iter().map(|(k, _)| (*k, k.name().to_string())).collect(),
+            icons: kinds.iter().map(|(k, icon)| (*k, icon.to_string())).collect()
+        }
+    }
+}
+
+impl AdmonitionStyle {
+    fn tags(&self, admonition: &Admonition) -> HtmlTags {
+        let kind_class = self.kind_classes.get(&admonition.kind).map(|c| format!(" {}", c)).unwrap_or_default();
+        // Classes given as attributes come after the classes of the style.
+        let mut attributes = admonition.attributes.clone();
+        let classes = attributes.entry("class".to_string()).or_default();
+        *classes = format!("{}{} {}", self.class, kind_class, classes).trim_end().to_string();
+        let icon = self.icons.get(&admonition.kind)
+            .map(|i| format!("<span class=\"{}\">{}</span>", self.icon_class, i))
+            .unwrap_or_default();
+        return (
+            format!(
+                "<div{a}><p class=\"{t}\">{i}{title}</p>",
+                a=html_attributes(&attributes), t=self.title_class, i=icon,
+                title=escape_html(&admonition.display_title())
+            ),
+            "</div>".to_string()
+        );
+    }
+}
+
+pub struct HtmlOptions {
+    pub flavor: HtmlFlavor,
+    pub layout: HtmlLayout,
+    pub math: MathOutput,
+    pub admonitions: AdmonitionStyle,
+    // Handlers return the opening and closing tags wrapped around the directive's children.
+    pub directives: DirectiveRegistry<HtmlTags>,
+    // Add unique slugs of the header text as ids to headers without a custom id.
+    pub header_ids: bool,
+    // Content of a self-link `<a class="anchor" href="#slug">` added to each header, eg. "#".
+    pub header_anchors: Option<String>,
+    // Levels included in the table of contents replacing `[TOC]` placeholders.
+    pub toc: TocOptions,
+    pub overrides: HashMap<TokenKind, TokenOverride>
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        HtmlOptions{
+            flavor: HtmlFlavor::Legacy,
+            layout: HtmlLayout::Compact,
+            math: MathOutput::Span,
+            admonitions: AdmonitionStyle::default(),
+            directives: DirectiveRegistry::new(),
+            header_ids: true,
+            header_anchors: None,
+            toc: TocOptions::default(),
+            overrides: HashMap::new()
+        }
+    }
+}
+
+impl HtmlOptions {
+
+    // Output matching the CommonMark spec examples, headers don't get generated ids.
+    pub fn commonmark() -> Self {
+        HtmlOptions{flavor: HtmlFlavor::CommonMark, header_ids: false, ..Default::default()}
+    }
+
+    /**
+     Customize the tags of one kind of token. The override gets the default tags, eg.
+     ```
+     # use rustmd::compilation_targets::to_html::HtmlOptions;
+     # use rustmd::tokens::TokenKind;
+     HtmlOptions::default().override_token(TokenKind::Code, |_, (open, close)| (
+         format!("<div class=\"copyable\"><button>Copy</button>{}", open),
+         format!("{}</div>", close)
+     ));
+     ```
+    */
+    pub fn override_token<F>(mut self, kind: TokenKind, f: F) -> Self
+    where F: Fn(&Token, HtmlTags) -> HtmlTags + 'static {
+        self.overrides.insert(kind, Box::new(f));
+        return self;
+    }
 
-trait Compile<T> {
-    fn compile(&self) -> T;
+    // Register a directive handler, see `DirectiveRegistry::register`.
+    pub fn directive<F>(mut self, name: &str, handler: F) -> Self
+    where F: Fn(&Directive) -> HtmlTags + 'static {
+        self.directives.register(name, handler);
+        return self;
+    }
 }
 
 pub fn compile_all(input: MdSyntaxTree) -> String {
-    return input.compile();
+    return compile_all_with(&input, &HtmlOptions::default());
 }
 
-// TODO: generic version of this that takes a function argument, similar use as `fold`
-// Allows implementing different compilation targets
-impl Compile<TargetHTML> for MdLine {
-    fn compile(&self) -> TargetHTML {
+// Document wide state of a compilation.
+struct DocumentContext {
+    // Slugs are unique across the whole document, headers take them in order.
+    slugs: std::vec::IntoIter<String>,
+    toc: String,
+    // Attributes of each top level CommonMark paragraph by its first line, the lines of a
+    // paragraph can each give attributes.
+    paragraph_attributes: HashMap<usize, Attributes>
+}
 
-        let mut html_str: String = String::new();
-        let mut unclosed_by_depth = Vec::from([("".to_string(),0)]);
-        for node in self.nodes_dfs() {
+impl DocumentContext {
+    fn new(input: &MdSyntaxTree, options: &HtmlOptions) -> Self {
+        DocumentContext{
+            slugs: header_slugs(input).into_iter(),
+            toc: toc_to_html(&build_toc(input, &options.toc)),
+            paragraph_attributes: paragraph_attributes(input)
+        }
+    }
+}
 
-            let current_depth = node.level;
+fn paragraph_attributes(input: &MdSyntaxTree) -> HashMap<usize, Attributes> {
+    let mut merged: HashMap<usize, Attributes> = HashMap::new();
+    let mut start = None;
+    for (i, line) in input.iter().enumerate() {
+        match line.get(TreeIndex::Arena(0)) {
+            Some(Token::Paragraph(p)) if line.size() > 1 => {
+                let first = *start.get_or_insert(i);
+                merged.entry(first).or_default().extend(p.attributes.clone());
+            },
+            _ => start = None
+        }
+    }
+    return merged;
+}
 
-            // Close tags if higher in tree.
-            let depth_predicate = |n: &&(String, usize)| n.1 >= current_depth;
-            let to_close: Vec<_> = unclosed_by_depth.iter().filter(depth_predicate).collect();
-            for unclosed in to_close {
-                html_str.push_str(&unclosed.0);
-            }
-            unclosed_by_depth = unclosed_by_depth.iter().filter(|uc| !depth_predicate(uc)).cloned().collect();
+pub fn compile_all_with(input: &MdSyntaxTree, options: &HtmlOptions) -> String {
+    let mut html = String::new();
+    // Writing to a String can't fail.
+    render_html_with(input, options, &mut html).unwrap();
+    return html;
+}
+
+/**
+ Stream the HTML of a document into a writer as it is compiled, without building the whole
+ document as a String first.
+*/
+pub fn render_html<W: fmt::Write>(input: &MdSyntaxTree, out: &mut W) -> fmt::Result {
+    render_html_with(input, &HtmlOptions::default(), out)
+}
+
+pub fn render_html_with<W: fmt::Write>(input: &MdSyntaxTree, options: &HtmlOptions, out: &mut W) -> fmt::Result {
+    let mut renderer = HtmlRenderer::new(options, out);
+    render(input, &mut renderer);
+    return renderer.finish().map(|_| ());
+}
+
+/**
+ Same as `render_html_with` for byte oriented writers such as files and sockets.
+*/
+pub fn render_html_io<W: io::Write>(input: &MdSyntaxTree, options: &HtmlOptions, out: &mut W) -> io::Result<()> {
+    let mut adapter = IoAdapter{inner: out, error: None};
+    let result = render_html_with(input, options, &mut adapter);
+    return match (result, adapter.error) {
+        (_, Some(e)) => Err(e),
+        (Err(_), None) => Err(io::Error::other("formatting error")),
+        (Ok(_), None) => Ok(())
+    };
+}
+
+// Keeps the io::Error, fmt::Error carries no information.
+struct IoAdapter<'w, W: io::Write> {
+    inner: &'w mut W,
+    error: Option<io::Error>
+}
+
+impl<W: io::Write> fmt::Write for IoAdapter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
 
-            let html_tags = node.val.compile();
-            html_str.push_str(&html_tags.0);
+/**
+ Renderer for the HTML target. Opening tags are written when a token is entered and the
+ matching closing tags once its children are done. The first write error stops output and is
+ returned from `finish`.
+*/
+pub struct HtmlRenderer<'a, W: fmt::Write> {
+    options: &'a HtmlOptions,
+    context: DocumentContext,
+    closing: Vec<String>,
+    // Closing tag and depth of a CommonMark paragraph the next line may still continue.
+    open_paragraph: Option<(usize, String)>,
+    // Anything written yet, pretty output starts blocks on a new line after the first one.
+    written: bool,
+    out: W,
+    result: fmt::Result
+}
 
-            // Add tag to queue to be closed later if has children. Else close tag now.
-            if node.children.len() > 0  {
-                unclosed_by_depth.push((html_tags.1, node.level));
-            } else {
-                html_str.push_str(&html_tags.1);
+impl<'a, W: fmt::Write> HtmlRenderer<'a, W> {
+    pub fn new(options: &'a HtmlOptions, out: W) -> Self {
+        HtmlRenderer{
+            options,
+            context: DocumentContext::new(&vec![], options),
+            closing: Vec::new(),
+            open_paragraph: None,
+            written: false,
+            out,
+            result: Ok(())
+        }
+    }
+
+    fn write(&mut self, s: &str) {
+        self.written |= !s.is_empty();
+        if self.result.is_ok() {
+            self.result = self.out.write_str(s);
+        }
+    }
+
+    fn close_paragraph(&mut self) {
+        if let Some((_, close)) = self.open_paragraph.take() {
+            self.write(&close);
+        }
+    }
+
+    // Give back the writer, or the first error that occurred while writing to it.
+    pub fn finish(self) -> Result<W, fmt::Error> {
+        self.result.map(|_| self.out)
+    }
+}
+
+impl<W: fmt::Write> Renderer for HtmlRenderer<'_, W> {
+    fn start_document(&mut self, doc: &MdSyntaxTree) {
+        self.context = DocumentContext::new(doc, self.options);
+    }
+
+    fn enter(&mut self, token: &Token, context: &NodeContext) -> Visit {
+        let commonmark = self.options.flavor == HtmlFlavor::CommonMark;
+        if commonmark && matches!(token, Token::Paragraph(_)) {
+            // Consecutive lines of text are one paragraph, blank lines end it.
+            if !context.has_children() {
+                self.close_paragraph();
+                self.closing.push(String::new());
+                return Visit::Children;
             }
+            if self.open_paragraph.as_ref().is_some_and(|(depth, _)| *depth == context.depth) {
+                let (_, close) = self.open_paragraph.take().unwrap();
+                self.write(if self.options.layout == HtmlLayout::Minified {" "} else {"\n"});
+                self.closing.push(close);
+                return Visit::Children;
+            }
+        }
+        self.close_paragraph();
+        let merged;
+        let token = match self.context.paragraph_attributes.get(&context.line_index) {
+            Some(attributes) if commonmark && context.depth == 0 && matches!(token, Token::Paragraph(_)) => {
+                merged = Token::Paragraph(Paragraph{attributes: attributes.clone()});
+                &merged
+            },
+            _ => token
+        };
+        let mut tags = compile_token(token, self.options, &mut self.context);
+        if matches!(token, Token::TableRow(_) | Token::TableCell(_)) {
+            let separator = if commonmark && self.options.layout == HtmlLayout::Compact { "\n" } else { "" };
+            tags = table_tags(token, tags, context, separator);
+        }
+        if let Some(override_tags) = self.options.overrides.get(&token.kind()) {
+            tags = override_tags(token, tags);
+        }
+        let (mut open, mut close) = tags;
+        let in_code = context.inside(|t| matches!(t, Token::Code(_)));
+        match self.options.layout {
+            HtmlLayout::Compact if commonmark && token.is_block() => {
+                if token.is_list() && context.inside(Token::is_list) { open.insert(0, '\n'); }
+                if token.is_container() { open.push('\n'); }
+                close.push('\n');
+            },
+            HtmlLayout::Pretty{indent} if token.is_block() => {
+                let level = context.ancestors.iter().filter(|t| t.is_block()).count();
+                let pad = " ".repeat(indent * level);
+                open = if self.written {format!("\n{}{}", pad, open)} else {format!("{}{}", pad, open)};
+                if token.is_container() { close = format!("\n{}{}", pad, close); }
+            },
+            HtmlLayout::Minified if matches!(token, Token::PlainText(_)) && !in_code => {
+                open = collapse_whitespace(&open);
+            },
+            _ => {}
+        }
+        self.write(&open);
+        self.closing.push(close);
+        return Visit::Children;
+    }
+
+    fn exit(&mut self, token: &Token, context: &NodeContext) {
+        // A list nested in an item follows it in the tree, the item is closed after the list.
+        if matches!(token, Token::ListItem(_)) && context.next_sibling().is_some_and(Token::is_list) {
+            return;
+        }
+        let close = self.closing.pop().unwrap_or_default();
+        if self.options.flavor == HtmlFlavor::CommonMark
+            && matches!(token, Token::Paragraph(_)) && context.has_children() {
+            self.open_paragraph = Some((context.depth, close));
+            return;
+        }
+        self.close_paragraph();
+        self.write(&close);
+        if token.is_list() && matches!(context.previous_sibling(), Some(Token::ListItem(_))) {
+            let item_close = self.closing.pop().unwrap_or_default();
+            self.write(&item_close);
+        }
+    }
+
+    fn finish_document(&mut self) {
+        self.close_paragraph();
+        if matches!(self.options.layout, HtmlLayout::Pretty{..}) && self.written {
+            self.write("\n");
         }
-        for unclosed in &unclosed_by_depth { html_str.push_str(&unclosed.0) }
-        return html_str;
     }
 }
 
-impl Compile<TargetHTML> for MdSyntaxTree {
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut previous_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !previous_space { collapsed.push(' '); }
+            previous_space = true;
+        } else {
+            collapsed.push(c);
+            previous_space = false;
+        }
+    }
+    return collapsed;
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/**
+ Attributes formatted for an HTML tag, each preceded by a space eg. ` id="intro" class="wide"`.
+*/
+pub fn html_attributes(attributes: &Attributes) -> String {
+    attributes.iter()
+        .map(|(key, value)| format!(" {}=\"{}\"", key, escape_html(value).replace('"', "&quot;")))
+        .collect()
+}
+
+fn compile_header(header: &Header, slug: Option<String>, options: &HtmlOptions) -> HtmlTags {
+    let Some(slug) = slug else { return header.compile() };
+    let mut header = header.clone();
+    if options.header_ids {
+        header.attributes.insert("id".to_string(), slug.clone());
+    }
+    let (mut open, close) = header.compile();
+    if let Some(anchor) = &options.header_anchors {
+        open.push_str(&format!("<a class=\"anchor\" href=\"#{}\">{}</a>", escape_html(&slug), anchor));
+    }
+    return (open, close);
+}
+
+// Tags of a single token, taking options into account.
+fn compile_token(token: &Token, options: &HtmlOptions, context: &mut DocumentContext) -> HtmlTags {
+    match (token, options.math) {
+        (Token::Header(h), _) => compile_header(h, context.slugs.next(), options),
+        (Token::TableOfContents(_), _) => (context.toc.clone(), "".to_string()),
+        (Token::InlineMath(m), MathOutput::MathML) => (tex_to_mathml(&m.src(), false), "".to_string()),
+        (Token::DisplayMath(m), MathOutput::MathML) => (tex_to_mathml(&m.src(), true), "".to_string()),
+        (Token::Admonition(a), _) => options.admonitions.tags(a),
+        (Token::Directive(d), _) => options.directives.render(d).unwrap_or_else(|| d.compile()),
+        _ => match options.flavor {
+            HtmlFlavor::Legacy => token.compile(),
+            HtmlFlavor::CommonMark => commonmark_tags(token)
+        }
+    }
+}
+
+/**
+ Tags of table rows and cells depending on their place in the table: body rows are grouped in a
+ `<tbody>` and header cells are `<th>`, aligned cells get an `align` attribute.
+*/
+fn table_tags(token: &Token, (mut open, mut close): HtmlTags, context: &NodeContext, separator: &str) -> HtmlTags {
+    match token {
+        Token::TableRow(row) if !row.header => {
+            if !matches!(context.previous_sibling(), Some(Token::TableRow(r)) if !r.header) {
+                open = format!("<tbody>{}{}", separator, open);
+            }
+            if context.is_last() { close = format!("{}{}</tbody>", close, separator) }
+        },
+        Token::TableCell(_) => {
+            if matches!(context.parent(), Some(Token::TableRow(r)) if r.header) {
+                open = open.replacen("<td", "<th", 1);
+                close = close.replacen("</td", "</th", 1);
+            }
+            if let (Some(alignment), Some(end)) = (context.cell_alignment().name(), open.find('>')) {
+                open.insert_str(end, &format!(" align=\"{}\"", alignment));
+            }
+        },
+        _ => {}
+    }
+    return (open, close);
+}
+
+// Tags of the CommonMark flavor, only the tokens that differ from the legacy output.
+fn commonmark_tags(token: &Token) -> HtmlTags {
+    let tags = |open: &str, close: &str| (open.to_string(), close.to_string());
+    match token {
+        Token::PlainText(t) => (escape_html(&t.text()).replace('"', "&quot;"), "".to_string()),
+        Token::Paragraph(p) => (format!("<p{}>", html_attributes(&p.attributes)), "</p>".to_string()),
+        Token::Italic(_) => tags("<em>", "</em>"),
+        Token::Link(l) if l.url.is_empty() => tags("<a>", "</a>"),
+        Token::Link(l) => (format!("<a href=\"{}\">", escape_html(&l.url).replace('"', "&quot;")), "</a>".to_string()),
+        Token::TableRow(r) if r.header => tags("<thead>\n<tr>", "</tr>\n</thead>"),
+        Token::Code(c) => {
+            // The language class and attributes go on the code element.
+            let (pre, _) = c.compile();
+            let attributes = pre.trim_start_matches("<pre").trim_end_matches('>');
+            (format!("<pre><code{}>", attributes), "</code></pre>".to_string())
+        },
+        _ => token.compile()
+    }
+}
+
+impl Compile<TargetHTML> for MdLine {
     fn compile(&self) -> TargetHTML {
+        let options = HtmlOptions::default();
+        let mut renderer = HtmlRenderer::new(&options, String::new());
+        render_line(self, 0, &mut renderer);
+        return renderer.finish().unwrap();
+    }
+}
 
-        // Fold all lines in syntax tree.
-        self.iter()
-            .fold("".to_string(),
-                |mut acc: String,line: &MdLine|
-                {acc.push_str(&line.compile()); acc}
-            )
+impl Compile<TargetHTML> for MdSyntaxTree {
+    fn compile(&self) -> TargetHTML {
+        compile_all_with(self, &HtmlOptions::default())
     }
 }
 
@@ -62,6 +518,7 @@ impl Compile<HtmlTags> for Token {
         match self {
             Token::Header(h) => return h.compile(),
             Token::List(t) => return t.compile(),
+            Token::OrderedList(t) => return t.compile(),
             Token::Paragraph(t) => return t.compile(),
             Token::Code(t) => return t.compile(),
             Token::PlainText(t) => return t.compile(),
@@ -70,6 +527,17 @@ impl Compile<HtmlTags> for Token {
             Token::Bold(t) => return t.compile(),
             Token::Link(t) => return t.compile(),
             Token::ListItem(t) => return t.compile(),
+            Token::DefinitionList(t) => return t.compile(),
+            Token::DefinitionTerm(t) => return t.compile(),
+            Token::Definition(t) => return t.compile(),
+            Token::InlineMath(t) => return t.compile(),
+            Token::DisplayMath(t) => return t.compile(),
+            Token::Admonition(t) => return t.compile(),
+            Token::Directive(t) => return t.compile(),
+            Token::TableOfContents(t) => return t.compile(),
+            Token::Table(t) => return t.compile(),
+            Token::TableRow(t) => return t.compile(),
+            Token::TableCell(t) => return t.compile(),
         }
     }
 }
@@ -78,27 +546,30 @@ impl Compile<HtmlTags> for PlainText {
 
     fn compile(&self) -> HtmlTags {
         if self.text().is_empty() {("<br>".to_string(),"".to_string())}
-        else { (format!("<span>{}",self.text().to_string()), "</span>".to_string()) }
+        else { (format!("<span>{}",self.text()), "</span>".to_string()) }
     }
 }
 
 impl Compile<HtmlTags> for Paragraph {
     fn compile(&self) -> HtmlTags {
-        return ("<div>".to_string(), "</div>".to_string());
+        return (format!("<div{}>", html_attributes(&self.attributes)), "</div>".to_string());
     }
 }
 
 impl Compile<HtmlTags> for Link {
     fn compile(&self) -> HtmlTags {
-        let href_tag = if self.url.is_empty() {"".to_string()} else {format!("href='{}'",self.url)};
-        return ( format!("<a {h}>",h=href_tag,), "</a>".to_string());
+        let href_tag = if self.url.is_empty() {"".to_string()} else {format!(" href='{}'",self.url)};
+        return ( format!("<a{h}>",h=href_tag,), "</a>".to_string());
     }
 }
 
 impl Compile<HtmlTags> for Header {
 
     fn compile(&self) -> HtmlTags {
-        return ( format!("<h{l}>",l=self.level()), format!("</h{l}>",l=self.level()))
+        return (
+            format!("<h{l}{a}>",l=self.level(), a=html_attributes(&self.attributes)),
+            format!("</h{l}>",l=self.level())
+        )
     }
 }
 
@@ -126,7 +597,15 @@ impl Compile<HtmlTags> for InlineCode {
 impl Compile<HtmlTags> for List {
 
     fn compile(&self) -> HtmlTags {
-        return ("<ul>".to_string(), "</ul>".to_string())
+        return (format!("<ul{}>", html_attributes(&self.attributes)), "</ul>".to_string())
+    }
+}
+
+impl Compile<HtmlTags> for OrderedList {
+
+    fn compile(&self) -> HtmlTags {
+        let start = if self.start == 1 { String::new() } else { format!(" start=\"{}\"", self.start) };
+        return (format!("<ol{}{}>", start, html_attributes(&self.attributes)), "</ol>".to_string())
     }
 }
 
@@ -139,7 +618,314 @@ impl Compile<HtmlTags> for ListItem {
 
 impl Compile<HtmlTags> for Code {
 
+    // The language is added as a `language-*` class in front of any other classes.
+    fn compile(&self) -> HtmlTags {
+        let mut attributes = self.attributes.clone();
+        if let Some(lang) = &self.lang {
+            let classes = attributes.entry("class".to_string()).or_default();
+            *classes = format!("language-{} {}", lang, classes).trim_end().to_string();
+        }
+        return (format!("<pre{}>", html_attributes(&attributes)), "</pre>".to_string())
+    }
+}
+
+impl Compile<HtmlTags> for DefinitionList {
+
+    fn compile(&self) -> HtmlTags {
+        return ("<dl>".to_string(), "</dl>".to_string())
+    }
+}
+
+impl Compile<HtmlTags> for DefinitionTerm {
+
+    fn compile(&self) -> HtmlTags {
+        return ("<dt>".to_string(), "</dt>".to_string())
+    }
+}
+
+impl Compile<HtmlTags> for Definition {
+
+    fn compile(&self) -> HtmlTags {
+        return ("<dd>".to_string(), "</dd>".to_string())
+    }
+}
+
+impl Compile<HtmlTags> for InlineMath {
+
+    fn compile(&self) -> HtmlTags {
+        return (format!("<span class=\"math inline\">\\({}\\)", escape_html(&self.src())), "</span>".to_string())
+    }
+}
+
+impl Compile<HtmlTags> for DisplayMath {
+
+    fn compile(&self) -> HtmlTags {
+        return (format!("<span class=\"math display\">\\[{}\\]", escape_html(&self.src())), "</span>".to_string())
+    }
+}
+
+impl Compile<HtmlTags> for Admonition {
+
+    fn compile(&self) -> HtmlTags {
+        return AdmonitionStyle::default().tags(self)
+    }
+}
+
+// Without a handler directives become a div or span with the directive name as a class.
+impl Compile<HtmlTags> for Directive {
+
     fn compile(&self) -> HtmlTags {
-        return ("<pre>".to_string(), "</pre>".to_string())
+        let mut attributes = self.attributes.clone();
+        let classes = attributes.entry("class".to_string()).or_default();
+        *classes = if classes.is_empty() { self.name.clone() } else { format!("{} {}", self.name, classes) };
+        let tag = if self.inline {"span"} else {"div"};
+        return (format!("<{}{}>", tag, html_attributes(&attributes)), format!("</{}>", tag))
+    }
+}
+
+// Outside of a document there are no headers to list.
+impl Compile<HtmlTags> for TableOfContents {
+
+    fn compile(&self) -> HtmlTags {
+        return ("".to_string(), "".to_string())
+    }
+}
+
+impl Compile<HtmlTags> for Table {
+
+    fn compile(&self) -> HtmlTags {
+        return ("<table>".to_string(), "</table>".to_string())
+    }
+}
+
+impl Compile<HtmlTags> for TableRow {
+
+    fn compile(&self) -> HtmlTags {
+        if self.header { return ("<thead><tr>".to_string(), "</tr></thead>".to_string()) }
+        return ("<tr>".to_string(), "</tr>".to_string())
+    }
+}
+
+// Header cells and alignments depend on the row and the table, see `table_tags`.
+impl Compile<HtmlTags> for TableCell {
+
+    fn compile(&self) -> HtmlTags {
+        return ("<td>".to_string(), "</td>".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    #[test]
+    fn t_math_output() {
+        let md_syntax = parse_md_str("$a<b$");
+        assert_eq!(
+            compile_all_with(&md_syntax, &HtmlOptions::default()),
+            "<div><span class=\"math inline\">\\(a&lt;b\\)</span></div>"
+        );
+        assert_eq!(
+            compile_all_with(&md_syntax, &HtmlOptions{math: MathOutput::MathML, ..Default::default()}),
+            "<div><math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"inline\">\
+                <mrow><mi>a</mi><mo>&lt;</mo><mi>b</mi></mrow></math></div>"
+        );
+    }
+
+    #[test]
+    fn t_nested_closing() {
+        // Tags close deepest first, also where several levels end at once.
+        let md_syntax = parse_md_str(":::note\n- **[x](/u)** *a*\n:::\n> [!TIP]\n> Some *it*\n");
+        assert_eq!(
+            compile_all(md_syntax),
+            "<div class=\"admonition note\"><p class=\"admonition-title\"><span class=\"admonition-icon\">ℹ️</span>Note</p>\
+                <ul><li><strong><a href='/u'><span>x</span></a></strong><span> </span><i><span>a</span></i></li></ul></div>\
+                <div class=\"admonition tip\"><p class=\"admonition-title\"><span class=\"admonition-icon\">💡</span>Tip</p>\
+                <div><span>Some </span><i><span>it</span></i></div></div>"
+        );
+    }
+
+    #[test]
+    fn t_block_attributes() {
+        let md_syntax = parse_md_str("# Intro {#start .big}\n```rust {.numbered}\nlet x = 1;\n```");
+        assert_eq!(
+            compile_all_with(&md_syntax, &HtmlOptions::default()),
+            "<h1 class=\"big\" id=\"start\"><span>Intro</span></h1>\
+                <pre class=\"language-rust numbered\"><span>let x = 1;\n</span></pre>"
+        );
+    }
+
+    #[test]
+    fn t_other_block_attributes() {
+        let md_syntax = parse_md_str("Text {.lead}\n- a\n- b\n{#items}\n> [!NOTE] Title {.wide}\n> c\n");
+        assert_eq!(
+            compile_all_with(&md_syntax, &HtmlOptions::default()),
+            "<div class=\"lead\"><span>Text</span></div>\
+                <ul id=\"items\"><li><span>a</span></li><li><span>b</span></li></ul>\
+                <div class=\"admonition note wide\"><p class=\"admonition-title\">\
+                <span class=\"admonition-icon\">ℹ\u{fe0f}</span>Title</p><div><span>c</span></div></div>"
+        );
+        // Braces right after the text are not attributes.
+        let md_syntax = parse_md_str("See :abbr[HTML]{title=x}");
+        assert_eq!(
+            compile_all_with(&md_syntax, &HtmlOptions::default()),
+            "<div><span>See </span><span class=\"abbr\" title=\"x\"><span>HTML</span></span></div>"
+        );
+        // Any line of a paragraph can give its attributes.
+        let md_syntax = parse_md_str("aaa\nbbb {#p}\n");
+        assert_eq!(compile_all_with(&md_syntax, &HtmlOptions::commonmark()), "<p id=\"p\">aaa\nbbb</p>\n");
+    }
+
+    #[test]
+    fn t_header_slugs() {
+        let md_syntax = parse_md_str("# Intro\n# Intro");
+        let mut options = HtmlOptions::default();
+        assert_eq!(
+            compile_all_with(&md_syntax, &options),
+            "<h1 id=\"intro\"><span>Intro</span></h1><h1 id=\"intro-1\"><span>Intro</span></h1>"
+        );
+        options.header_anchors = Some("#".to_string());
+        assert!(compile_all_with(&md_syntax, &options)
+            .starts_with("<h1 id=\"intro\"><a class=\"anchor\" href=\"#intro\">#</a><span>Intro</span></h1>"));
+        // Custom ids are kept, generated slugs make way for them.
+        let md_syntax = parse_md_str("# Setup\n## Install {#setup}");
+        assert_eq!(
+            compile_all_with(&md_syntax, &HtmlOptions::default()),
+            "<h1 id=\"setup-1\"><span>Setup</span></h1><h2 id=\"setup\"><span>Install</span></h2>"
+        );
+    }
+
+    #[test]
+    fn t_toc_placeholder() {
+        let md_syntax = parse_md_str("[TOC]\n# One\n## Two");
+        assert_eq!(
+            compile_all_with(&md_syntax, &HtmlOptions::default()),
+            "<ul class=\"toc\"><li><a href=\"#one\">One</a><ul><li><a href=\"#two\">Two</a></li></ul></li></ul>\
+                <h1 id=\"one\"><span>One</span></h1><h2 id=\"two\"><span>Two</span></h2>"
+        );
+    }
+
+    #[test]
+    fn t_token_overrides() {
+        let md_syntax = parse_md_str("[in](/docs) [out](https://gnu.org)\n```\nx\n```");
+        let options = HtmlOptions::default()
+            .override_token(TokenKind::Link, |token, default| match token {
+                Token::Link(l) if l.url.starts_with("http") => (
+                    format!("<a href='{}' target=\"_blank\" rel=\"noopener\">", l.url),
+                    default.1
+                ),
+                _ => default
+            })
+            .override_token(TokenKind::Code, |_, (open, close)| (
+                format!("<div class=\"copy\">{}", open),
+                format!("{}</div>", close)
+            ));
+        assert_eq!(
+            compile_all_with(&md_syntax, &options),
+            "<div><a href='/docs'><span>in</span></a><span> </span>\
+                <a href='https://gnu.org' target=\"_blank\" rel=\"noopener\"><span>out</span></a></div>\
+                <div class=\"copy\"><pre><span>x\n</span></pre></div>"
+        );
+    }
+
+    #[test]
+    fn t_streaming() {
+        let md_syntax = parse_md_str("# Title\nText");
+        let mut bytes: Vec<u8> = Vec::new();
+        render_html_io(&md_syntax, &HtmlOptions::default(), &mut bytes).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), compile_all(parse_md_str("# Title\nText")));
+
+        // Errors of the writer are passed on.
+        struct Full;
+        impl io::Write for Full {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> { Err(io::Error::new(io::ErrorKind::StorageFull, "full")) }
+            fn flush(&mut self) -> io::Result<()> { Ok(()) }
+        }
+        let err = render_html_io(&md_syntax, &HtmlOptions::default(), &mut Full).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::StorageFull);
+    }
+
+    #[test]
+    fn t_directives() {
+        let md_syntax = parse_md_str(":badge[New]{color=green} :kbd[Ctrl]");
+        let options = HtmlOptions::default().directive("badge", |d| (
+            format!("<em class=\"badge-{}\">", d.attributes["color"]),
+            "</em>".to_string()
+        ));
+        assert_eq!(
+            compile_all_with(&md_syntax, &options),
+            "<div><em class=\"badge-green\"><span>New</span></em><span> </span>\
+                <span class=\"kbd\"><span>Ctrl</span></span></div>"
+        );
+    }
+
+    #[test]
+    fn t_admonition_style() {
+        let md_syntax = parse_md_str("> [!WARNING] Careful\n> Text");
+        let mut options = HtmlOptions::default();
+        options.admonitions.class = "callout".to_string();
+        options.admonitions.icons.insert(AdmonitionKind::Warning, "!".to_string());
+        assert_eq!(
+            compile_all_with(&md_syntax, &options),
+            "<div class=\"callout warning\"><p class=\"admonition-title\">\
+                <span class=\"admonition-icon\">!</span>Careful</p>\
+                <div><span>Text</span></div></div>"
+        );
+    }
+
+    #[test]
+    fn t_commonmark() {
+        // Expected output of examples from the CommonMark spec.
+        let examples = [
+            ("# foo", "<h1>foo</h1>\n"),
+            ("aaa\nbbb", "<p>aaa\nbbb</p>\n"),
+            ("aaa\n\nbbb", "<p>aaa</p>\n<p>bbb</p>\n"),
+            ("*foo bar*", "<p><em>foo bar</em></p>\n"),
+            ("**foo bar**", "<p><strong>foo bar</strong></p>\n"),
+            ("`foo`", "<p><code>foo</code></p>\n"),
+            ("[link](/uri)", "<p><a href=\"/uri\">link</a></p>\n"),
+            ("[link]()", "<p><a>link</a></p>\n"),
+            ("a < b & \"c\"", "<p>a &lt; b &amp; &quot;c&quot;</p>\n"),
+            ("```\n<\n >\n```", "<pre><code>&lt;\n &gt;\n</code></pre>\n"),
+            ("```ruby\ndef foo(x)\n  return 3\nend\n```",
+                "<pre><code class=\"language-ruby\">def foo(x)\n  return 3\nend\n</code></pre>\n"),
+            ("- a\n- b\n- c\n\n", "<ul>\n<li>a</li>\n<li>b</li>\n<li>c</li>\n</ul>\n"),
+            ("1. foo\n2. bar\n3. baz", "<ol>\n<li>foo</li>\n<li>bar</li>\n<li>baz</li>\n</ol>\n"),
+            ("123456789. ok", "<ol start=\"123456789\">\n<li>ok</li>\n</ol>\n"),
+            ("1. a\n   - b\n2. c", "<ol>\n<li>a\n<ul>\n<li>b</li>\n</ul>\n</li>\n<li>c</li>\n</ol>\n"),
+            ("| foo | bar |\n| --- | --- |\n| baz | bim |",
+                "<table>\n<thead>\n<tr>\n<th>foo</th>\n<th>bar</th>\n</tr>\n</thead>\n\
+                <tbody>\n<tr>\n<td>baz</td>\n<td>bim</td>\n</tr>\n</tbody>\n</table>\n"),
+            ("| abc | defghi |\n:-: | -----------:\nbar | baz",
+                "<table>\n<thead>\n<tr>\n<th align=\"center\">abc</th>\n<th align=\"right\">defghi</th>\n</tr>\n</thead>\n\
+                <tbody>\n<tr>\n<td align=\"center\">bar</td>\n<td align=\"right\">baz</td>\n</tr>\n</tbody>\n</table>\n"),
+            ("| abc | def |\n| --- | --- |",
+                "<table>\n<thead>\n<tr>\n<th>abc</th>\n<th>def</th>\n</tr>\n</thead>\n</table>\n"),
+        ];
+        for (md, html) in examples {
+            assert_eq!(compile_all_with(&parse_md_str(md), &HtmlOptions::commonmark()), html, "{:?}", md);
+        }
+    }
+
+    #[test]
+    fn t_layout() {
+        let md_syntax = parse_md_str("# Title\n- a\n- b\n\n```\nfn main() {\n    x\n}\n```");
+        let pretty = HtmlOptions{layout: HtmlLayout::Pretty{indent: 2}, ..HtmlOptions::commonmark()};
+        assert_eq!(
+            compile_all_with(&md_syntax, &pretty),
+            "<h1>Title</h1>\n<ul>\n  <li>a</li>\n  <li>b</li>\n</ul>\n\
+                <pre><code>fn main() {\n    x\n}\n</code></pre>\n"
+        );
+
+        let minified = HtmlOptions{layout: HtmlLayout::Minified, ..HtmlOptions::commonmark()};
+        assert_eq!(
+            compile_all_with(&md_syntax, &minified),
+            "<h1>Title</h1><ul><li>a</li><li>b</li></ul><pre><code>fn main() {\n    x\n}\n</code></pre>"
+        );
+        assert_eq!(
+            compile_all_with(&parse_md_str("a   b\nc"), &minified),
+            "<p>a b c</p>"
+        );
     }
 }
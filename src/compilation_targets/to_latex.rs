@@ -0,0 +1,273 @@
+use crate::slug::header_slugs;
+use crate::tokens::*;
+use super::directives::DirectiveRegistry;
+use super::{render, NodeContext, Renderer, Visit};
+
+/*
+ * LaTeX target. Headers become sectioning commands labeled with their slugs, emphasis `\emph` and
+ * `\textbf`, lists `itemize`, definition lists `description`, tables `tabular` and code `verbatim`
+ * or `lstlisting`. Plain text is escaped, code is copied as is.
+ */
+
+pub type LatexTags = (String, String);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CodeStyle {
+    Verbatim,
+    // `lstlisting` from the listings package, with the language of the code block.
+    Listings
+}
+
+pub struct LatexOptions {
+    pub code: CodeStyle,
+    // Wrap the output in a document with a preamble.
+    pub standalone: bool,
+    pub document_class: String,
+    // Packages loaded in the preamble in addition to the ones the output needs.
+    pub packages: Vec<String>,
+    // `\title` of a standalone document, followed by `\maketitle`.
+    pub title: Option<String>,
+    // Handlers return the code around the directive's content, which is otherwise output as is.
+    pub directives: DirectiveRegistry<LatexTags>
+}
+
+impl Default for LatexOptions {
+    fn default() -> Self {
+        LatexOptions{
+            code: CodeStyle::Verbatim,
+            standalone: false,
+            document_class: "article".to_string(),
+            packages: Vec::new(),
+            title: None,
+            directives: DirectiveRegistry::new()
+        }
+    }
+}
+
+pub fn compile_all(input: MdSyntaxTree) -> String {
+    return compile_all_with(&input, &LatexOptions::default());
+}
+
+pub fn compile_all_with(input: &MdSyntaxTree, options: &LatexOptions) -> String {
+    let mut renderer = LatexRenderer::new(options);
+    render(input, &mut renderer);
+    return renderer.finish();
+}
+
+/**
+ Renderer for the LaTeX target. Like the HTML renderer, the opening code of a token is written
+ when it is entered and the closing code once its children are done.
+*/
+pub struct LatexRenderer<'a> {
+    options: &'a LatexOptions,
+    slugs: std::vec::IntoIter<String>,
+    closing: Vec<String>,
+    out: String
+}
+
+impl<'a> LatexRenderer<'a> {
+    pub fn new(options: &'a LatexOptions) -> Self {
+        LatexRenderer{options, slugs: Vec::new().into_iter(), closing: Vec::new(), out: String::new()}
+    }
+
+    pub fn finish(self) -> String {
+        if !self.options.standalone { return self.out }
+        return format!("{}{}\\end{{document}}\n", preamble(self.options), self.out);
+    }
+
+    fn tags(&mut self, token: &Token, context: &NodeContext) -> LatexTags {
+        let tags = |open: &str, close: &str| (open.to_string(), close.to_string());
+        match token {
+            Token::PlainText(t) if context.inside(|t| matches!(t, Token::Code(_))) => (t.text(), String::new()),
+            Token::PlainText(t) => (escape_latex(&t.text()), String::new()),
+            Token::Paragraph(_) if !context.has_children() => tags("\n", ""),
+            Token::Paragraph(_) => tags("", "\n"),
+            Token::Header(h) => {
+                let command = match h.level {
+                    1 => "section",
+                    2 => "subsection",
+                    3 => "subsubsection",
+                    4 => "paragraph",
+                    _ => "subparagraph"
+                };
+                let label = self.slugs.next().map(|s| format!("\\label{{{}}}", s)).unwrap_or_default();
+                (format!("\\{}{{", command), format!("}}{}\n", label))
+            },
+            Token::Italic(_) => tags("\\emph{", "}"),
+            Token::Bold(_) => tags("\\textbf{", "}"),
+            Token::InlineCode(_) => tags("\\texttt{", "}"),
+            Token::Link(l) => match l.url.strip_prefix('#') {
+                Some(label) => (format!("\\hyperref[{}]{{", label), "}".to_string()),
+                None => (format!("\\href{{{}}}{{", escape_url(&l.url)), "}".to_string())
+            },
+            Token::List(_) => tags("\\begin{itemize}\n", "\\end{itemize}\n"),
+            Token::OrderedList(l) if l.start == 1 => tags("\\begin{enumerate}\n", "\\end{enumerate}\n"),
+            Token::OrderedList(l) => {
+                // The counter of the nesting level is increased before each item.
+                let nesting = context.ancestors.iter().filter(|t| matches!(t, Token::OrderedList(_))).count();
+                let counter = ["enumi", "enumii", "enumiii", "enumiv"][nesting.min(3)];
+                (
+                    format!("\\begin{{enumerate}}\n\\setcounter{{{}}}{{{}}}\n", counter, l.start - 1),
+                    "\\end{enumerate}\n".to_string()
+                )
+            },
+            Token::ListItem(_) => tags("\\item ", "\n"),
+            Token::DefinitionList(_) => tags("\\begin{description}\n", "\\end{description}\n"),
+            Token::DefinitionTerm(_) => tags("\\item[", "] "),
+            // Further definitions of a term start a new paragraph of the same item.
+            Token::Definition(_) if matches!(context.previous_sibling(), Some(Token::Definition(_))) => tags("\\par ", ""),
+            Token::Definition(_) => tags("", ""),
+            Token::Code(c) => match (self.options.code, &c.lang) {
+                (CodeStyle::Listings, Some(lang)) => {
+                    (format!("\\begin{{lstlisting}}[language={}]\n", lang), "\\end{lstlisting}\n".to_string())
+                },
+                (CodeStyle::Listings, None) => tags("\\begin{lstlisting}\n", "\\end{lstlisting}\n"),
+                (CodeStyle::Verbatim, _) => tags("\\begin{verbatim}\n", "\\end{verbatim}\n")
+            },
+            Token::InlineMath(m) => (format!("${}$", m.src()), String::new()),
+            Token::DisplayMath(m) if context.depth == 0 => (format!("\\[\n{}\n\\]\n", m.src()), String::new()),
+            Token::DisplayMath(m) => (format!("\\[{}\\]", m.src()), String::new()),
+            Token::Admonition(a) => (
+                format!("\\begin{{quote}}\n\\textbf{{{}}}\n\n", escape_latex(&a.display_title())),
+                "\\end{quote}\n".to_string()
+            ),
+            Token::Directive(d) => self.options.directives.render(d).unwrap_or_default(),
+            Token::TableOfContents(_) => tags("\\tableofcontents\n", ""),
+            Token::Table(t) => {
+                let columns: String = t.alignments.iter().map(|a| match a {
+                    Alignment::Center => 'c',
+                    Alignment::Right => 'r',
+                    _ => 'l'
+                }).collect();
+                (format!("\\begin{{tabular}}{{{}}}\n", columns), "\\end{tabular}\n".to_string())
+            },
+            // A rule separates the header from the body.
+            Token::TableRow(r) if r.header => tags("", " \\\\\n\\hline\n"),
+            Token::TableRow(_) => tags("", " \\\\\n"),
+            Token::TableCell(_) if context.is_first() => tags("", ""),
+            Token::TableCell(_) => tags(" & ", "")
+        }
+    }
+}
+
+impl Renderer for LatexRenderer<'_> {
+    fn start_document(&mut self, doc: &MdSyntaxTree) {
+        self.slugs = header_slugs(doc).into_iter();
+    }
+
+    fn enter(&mut self, token: &Token, context: &NodeContext) -> Visit {
+        let (open, close) = self.tags(token, context);
+        self.out.push_str(&open);
+        self.closing.push(close);
+        return Visit::Children;
+    }
+
+    fn exit(&mut self, token: &Token, _: &NodeContext) {
+        // The end of a verbatim environment has to be on a line of its own.
+        if matches!(token, Token::Code(_)) && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        let close = self.closing.pop().unwrap_or_default();
+        self.out.push_str(&close);
+    }
+}
+
+fn preamble(options: &LatexOptions) -> String {
+    let mut packages = vec!["amsmath".to_string(), "hyperref".to_string()];
+    if options.code == CodeStyle::Listings { packages.push("listings".to_string()) }
+    packages.extend(options.packages.iter().cloned());
+
+    let mut preamble = format!("\\documentclass{{{}}}\n", options.document_class);
+    for package in packages {
+        preamble.push_str(&format!("\\usepackage{{{}}}\n", package));
+    }
+    if let Some(title) = &options.title {
+        preamble.push_str(&format!("\\title{{{}}}\n", escape_latex(title)));
+    }
+    preamble.push_str("\\begin{document}\n");
+    if options.title.is_some() { preamble.push_str("\\maketitle\n") }
+    return preamble;
+}
+
+/**
+ Escape the characters with a special meaning in LaTeX text.
+*/
+pub fn escape_latex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            _ => escaped.push(c)
+        }
+    }
+    return escaped;
+}
+
+// Urls in `\href` only need the characters escaped that would end the argument or the line.
+fn escape_url(url: &str) -> String {
+    url.replace('\\', "\\\\").replace('%', "\\%").replace('#', "\\#").replace('{', "\\{").replace('}', "\\}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    #[test]
+    fn t_latex() {
+        let md_syntax = parse_md_str(
+            "# Results & notes\nSome *new* **bold** 100% [link](https://gnu.org/a#b) $x^2$\n\
+            - one\n- `a_b`\n\n```rust\nlet x = {};\n```\nTerm\n: First\n: Second\n"
+        );
+        assert_eq!(
+            compile_all(md_syntax),
+            "\\section{Results \\& notes}\\label{results--notes}\n\
+                Some \\emph{new} \\textbf{bold} 100\\% \\href{https://gnu.org/a\\#b}{link} $x^2$\n\
+                \\begin{itemize}\n\\item one\n\\item \\texttt{a\\_b}\n\\end{itemize}\n\n\
+                \\begin{verbatim}\nlet x = {};\n\\end{verbatim}\n\n\
+                \\begin{description}\n\\item[Term] First\n\\par Second\n\\end{description}\n"
+        );
+    }
+
+    #[test]
+    fn t_enumerate() {
+        let md_syntax = parse_md_str("1. one\n2. two\n\n5. five\n");
+        assert_eq!(
+            compile_all(md_syntax),
+            "\\begin{enumerate}\n\\item one\n\\item two\n\\end{enumerate}\n\n\
+                \\begin{enumerate}\n\\setcounter{enumi}{4}\n\\item five\n\\end{enumerate}\n"
+        );
+    }
+
+    #[test]
+    fn t_tabular() {
+        let md_syntax = parse_md_str("| Name | Cost |\n|---|--:|\n| *a* | 5% |\n");
+        assert_eq!(
+            compile_all(md_syntax),
+            "\\begin{tabular}{lr}\nName & Cost \\\\\n\\hline\n\\emph{a} & 5\\% \\\\\n\\end{tabular}\n"
+        );
+    }
+
+    #[test]
+    fn t_standalone() {
+        let md_syntax = parse_md_str("```rust\nfn main() {}\n```");
+        let options = LatexOptions{
+            code: CodeStyle::Listings,
+            standalone: true,
+            title: Some("Draft".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            compile_all_with(&md_syntax, &options),
+            "\\documentclass{article}\n\\usepackage{amsmath}\n\\usepackage{hyperref}\n\\usepackage{listings}\n\
+                \\title{Draft}\n\\begin{document}\n\\maketitle\n\
+                \\begin{lstlisting}[language=rust]\nfn main() {}\n\\end{lstlisting}\n\\end{document}\n"
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use crate::slug::text_content;
+use crate::tokens::*;
+use crate::tree::TreeIndex;
+use super::directives::DirectiveRegistry;
+use super::{render, NodeContext, Renderer, Visit};
+
+/*
+ * Man page target producing roff for the man(7) macros. The first level-1 header becomes the
+ * `.TH` title, eg. `# mytool(1)`, further headers `.SH` and `.SS` sections. Emphasis switches
+ * fonts, lists are `.IP` paragraphs, definition lists `.TP` paragraphs, tables tbl(1) tables and
+ * code is printed in no-fill mode. Requests have to start a line, so text is written line by line
+ * with the control characters escaped where a line starts.
+ */
+
+pub type ManTags = (String, String);
+
+pub struct ManOptions {
+    // Section of the page when the title header doesn't have one, eg. "1" for commands.
+    pub section: String,
+    // Optional `.TH` arguments shown in the page footer and header.
+    pub date: Option<String>,
+    pub source: Option<String>,
+    pub manual: Option<String>,
+    // Section names are conventionally written in capitals.
+    pub uppercase_sections: bool,
+    // Handlers return the roff around the directive's content, which is otherwise output as is.
+    pub directives: DirectiveRegistry<ManTags>
+}
+
+impl Default for ManOptions {
+    fn default() -> Self {
+        ManOptions{
+            section: "1".to_string(),
+            date: None,
+            source: None,
+            manual: None,
+            uppercase_sections: true,
+            directives: DirectiveRegistry::new()
+        }
+    }
+}
+
+pub fn compile_all(input: MdSyntaxTree) -> String {
+    return compile_all_with(&input, &ManOptions::default());
+}
+
+pub fn compile_all_with(input: &MdSyntaxTree, options: &ManOptions) -> String {
+    let mut renderer = ManRenderer::new(options);
+    render(input, &mut renderer);
+    return renderer.finish();
+}
+
+/**
+ Renderer for the man page target.
+*/
+pub struct ManRenderer<'a> {
+    options: &'a ManOptions,
+    out: String,
+    // Fonts of the open emphasis tokens, the innermost last.
+    fonts: Vec<char>,
+    closing: Vec<String>,
+    // The next paragraph has to be separated from the output before it with `.PP`.
+    separate: bool,
+    // Code is printed in no-fill mode, which keeps indentation and line breaks.
+    no_fill: bool,
+    // A link was closed with `.UE`, punctuation right after it is passed to the request.
+    link_closed: bool,
+    title_written: bool,
+    // Pages with tables have to be run through tbl, which a comment on the first line asks for.
+    tables: bool
+}
+
+impl<'a> ManRenderer<'a> {
+    pub fn new(options: &'a ManOptions) -> Self {
+        ManRenderer{
+            options,
+            out: String::new(),
+            fonts: Vec::new(),
+            closing: Vec::new(),
+            separate: false,
+            no_fill: false,
+            link_closed: false,
+            title_written: false,
+            tables: false
+        }
+    }
+
+    pub fn finish(mut self) -> String {
+        self.line_start();
+        if self.tables { return format!("'\\\" t\n{}", self.out) }
+        return self.out;
+    }
+
+    // Make sure the output continues on a new line, eg. for a request.
+    fn line_start(&mut self) {
+        self.link_closed = false;
+        if self.out.is_empty() || self.out.ends_with('\n') { return }
+        if !self.no_fill {
+            let trimmed = self.out.trim_end_matches([' ', '\t']).len();
+            self.out.truncate(trimmed);
+        }
+        if !self.out.is_empty() && !self.out.ends_with('\n') { self.out.push('\n') }
+    }
+
+    fn request(&mut self, request: &str) {
+        self.line_start();
+        self.out.push_str(request);
+        self.out.push('\n');
+    }
+
+    fn paragraph(&mut self) {
+        if self.separate { self.request(".PP") }
+        self.separate = false;
+    }
+
+    // Write escaped text, which may start new lines.
+    fn text(&mut self, text: &str) {
+        let mut text = text;
+        if self.link_closed {
+            // Punctuation following a link is an argument of `.UE` so that no space is put before it.
+            let punctuation = text.len() - text.trim_start_matches(|c: char| c.is_ascii_punctuation()).len();
+            if punctuation > 0 {
+                self.out.push(' ');
+                self.out.push_str(&escape_roff(&text[..punctuation]));
+            }
+            text = &text[punctuation..];
+            self.line_start();
+        }
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 { self.out.push('\n') }
+            let at_line_start = self.out.is_empty() || self.out.ends_with('\n');
+            // Leading spaces in filled text would break the line.
+            let line = if at_line_start && !self.no_fill { line.trim_start() } else { line };
+            if at_line_start && line.starts_with(['.', '\'']) { self.out.push_str("\\&") }
+            self.out.push_str(&escape_roff(line));
+        }
+    }
+
+    fn push_font(&mut self, font: char) {
+        self.fonts.push(font);
+        self.out.push_str(&format!("\\f{}", font));
+    }
+
+    fn pop_font(&mut self) {
+        self.fonts.pop();
+        let font = self.fonts.last().copied().unwrap_or('R');
+        self.out.push_str(&format!("\\f{}", font));
+    }
+
+    // `.TH` from a title such as `mytool(1)`.
+    fn title(&self, title: &str) -> String {
+        let title = title.trim();
+        let (name, section) = match title.strip_suffix(')').and_then(|t| t.rsplit_once('(')) {
+            Some((name, section)) if !section.is_empty() && !name.trim().is_empty() => (name.trim(), section),
+            _ => (title, self.options.section.as_str())
+        };
+        let mut arguments = vec![quote(&name.to_uppercase()), quote(section)];
+        let optional = [&self.options.date, &self.options.source, &self.options.manual];
+        let last = optional.iter().rposition(|a| a.is_some()).map_or(0, |i| i + 1);
+        for argument in &optional[..last] {
+            arguments.push(quote(argument.as_deref().unwrap_or("")));
+        }
+        return format!(".TH {}", arguments.join(" "));
+    }
+
+    // Paragraphs directly in a list item or definition continue it instead of starting a new one.
+    fn starts_paragraph(context: &NodeContext) -> bool {
+        return !context.inside(|t| matches!(t, Token::ListItem(_) | Token::Definition(_)));
+    }
+}
+
+impl Renderer for ManRenderer<'_> {
+    fn enter(&mut self, token: &Token, context: &NodeContext) -> Visit {
+        let in_code = context.inside(|t| matches!(t, Token::Code(_)));
+        let in_header = context.inside(|t| matches!(t, Token::Header(_)));
+        let in_table = context.inside(|t| matches!(t, Token::Table(_)));
+        match token {
+            // Header text is an argument of `.SH`, where double quotes would delimit arguments.
+            Token::PlainText(t) if in_header => {
+                let text = if self.options.uppercase_sections { t.text().to_uppercase() } else { t.text() };
+                self.out.push_str(&escape_roff(&text).replace('"', "\\(dq"));
+            },
+            Token::PlainText(t) => self.text(&t.text()),
+            _ if in_code => {},
+            Token::Paragraph(_) if !context.has_children() => self.separate = true,
+            Token::Paragraph(_) if Self::starts_paragraph(context) => self.paragraph(),
+            Token::Paragraph(_) => {},
+            Token::Header(h) if h.level == 1 && !self.title_written => {
+                self.title_written = true;
+                let title = self.title(&text_content(context.line, TreeIndex::Arena(context.raw_idx)));
+                self.request(&title);
+                self.separate = false;
+                return Visit::SkipChildren;
+            },
+            Token::Header(h) => {
+                self.line_start();
+                self.out.push_str(if h.level <= 2 { ".SH " } else { ".SS " });
+                self.separate = false;
+            },
+            Token::Italic(_) => self.push_font('I'),
+            Token::Bold(_) | Token::InlineCode(_) => self.push_font('B'),
+            // Links in headers and tables can't be requests on lines of their own.
+            Token::Link(l) if l.url.is_empty() || in_header || in_table => {},
+            Token::Link(l) => self.request(&format!(".UR {}", l.url)),
+            Token::List(_) | Token::OrderedList(_) | Token::DefinitionList(_) => {},
+            Token::ListItem(_) => match context.item_number() {
+                Some(number) => self.request(&format!(".IP {}. 4", number)),
+                None => self.request(".IP \\(bu 2")
+            },
+            Token::DefinitionTerm(_) => self.request(".TP"),
+            Token::Definition(_) if matches!(context.previous_sibling(), Some(Token::Definition(_))) => self.request(".IP"),
+            Token::Definition(_) => {},
+            Token::Code(_) => {
+                if Self::starts_paragraph(context) { self.request(".PP") }
+                self.request(".RS 4");
+                self.request(".nf");
+                self.no_fill = true;
+            },
+            Token::InlineMath(m) => self.text(&m.src()),
+            Token::DisplayMath(m) if context.depth == 0 => {
+                self.request(".PP");
+                self.request(".RS 4");
+                self.text(&m.src());
+                self.request(".RE");
+                self.separate = true;
+            },
+            Token::DisplayMath(m) => self.text(&m.src()),
+            Token::Admonition(a) => {
+                self.request(".PP");
+                self.out.push_str("\\fB");
+                self.text(&a.display_title());
+                self.out.push_str("\\fR");
+                self.request(".RS 4");
+                self.separate = false;
+            },
+            Token::Directive(d) => {
+                let (open, close) = self.options.directives.render(d).unwrap_or_default();
+                self.out.push_str(&open);
+                self.closing.push(close);
+            },
+            // Man pages are short enough to do without a table of contents.
+            Token::TableOfContents(_) => {},
+            Token::Table(t) => {
+                self.tables = true;
+                self.paragraph();
+                self.request(".TS");
+                // Header cells are bold, the last format line applies to the rows after it.
+                let format = |font: &str| t.alignments.iter()
+                    .map(|a| format!("{}{}", match a { Alignment::Center => 'c', Alignment::Right => 'r', _ => 'l' }, font))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let header = context.children().any(|r| matches!(r, Token::TableRow(r) if r.header));
+                if header { self.request(&format("b")) }
+                self.request(&format!("{}.", format("")));
+            },
+            Token::TableRow(_) => {},
+            // Cells are separated by tabs.
+            Token::TableCell(_) if !context.is_first() => self.out.push('\t'),
+            Token::TableCell(_) => {}
+        }
+        return Visit::Children;
+    }
+
+    fn exit(&mut self, token: &Token, context: &NodeContext) {
+        if context.inside(|t| matches!(t, Token::Code(_))) { return }
+        match token {
+            Token::Italic(_) | Token::Bold(_) | Token::InlineCode(_) => self.pop_font(),
+            Token::Link(l) if l.url.is_empty() || context.inside(|t| matches!(t, Token::Header(_) | Token::Table(_))) => {},
+            Token::Link(_) => {
+                self.line_start();
+                self.out.push_str(".UE");
+                self.link_closed = true;
+            },
+            Token::Paragraph(_) | Token::Header(_) | Token::ListItem(_) | Token::DefinitionTerm(_) => self.line_start(),
+            Token::List(_) | Token::OrderedList(_) | Token::DefinitionList(_) => self.separate = true,
+            Token::Code(_) => {
+                self.no_fill = false;
+                self.request(".fi");
+                self.request(".RE");
+                self.separate = true;
+            },
+            Token::Admonition(_) => {
+                self.request(".RE");
+                self.separate = true;
+            },
+            Token::Directive(_) => {
+                let close = self.closing.pop().unwrap_or_default();
+                self.out.push_str(&close);
+            },
+            Token::TableRow(r) => {
+                self.line_start();
+                if r.header { self.request("_") }
+            },
+            Token::Table(_) => {
+                self.request(".TE");
+                self.separate = true;
+            },
+            _ => {}
+        }
+    }
+}
+
+/**
+ Escape backslashes and hyphens in roff text. Hyphens are printed as minus signs, so that options
+ such as `--help` can be copied from the page.
+*/
+pub fn escape_roff(text: &str) -> String {
+    text.replace('\\', "\\e").replace('-', "\\-")
+}
+
+// Macro argument in double quotes.
+fn quote(argument: &str) -> String {
+    format!("\"{}\"", escape_roff(argument).replace('"', "\\(dq"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    #[test]
+    fn t_man() {
+        let md_syntax = parse_md_str(
+            "# mytool(8)\n## \"Synopsis\"\n**mytool** [*options*] file\n\n.hidden and 'quoted' C:\\\\path\n\
+            - one\n- two\n\n## Options\n`--help`\n: Print help, see [the docs](https://example.com).\n\n\
+            ```sh\n.start\nmytool --help\n```\n"
+        );
+        assert_eq!(
+            compile_all(md_syntax),
+            ".TH \"MYTOOL\" \"8\"\n.SH \\(dqSYNOPSIS\\(dq\n\\fBmytool\\fR [\\fIoptions\\fR] file\n\
+                .PP\n\\&.hidden and 'quoted' C:\\e\\epath\n\
+                .IP \\(bu 2\none\n.IP \\(bu 2\ntwo\n\
+                .SH OPTIONS\n.TP\n\\fB\\-\\-help\\fR\nPrint help, see\n.UR https://example.com\nthe docs\n.UE .\n\
+                .PP\n.RS 4\n.nf\n\\&.start\nmytool \\-\\-help\n.fi\n.RE\n"
+        );
+    }
+
+    #[test]
+    fn t_table() {
+        let md_syntax = parse_md_str("Sizes:\n\n| Name | Size |\n|---|--:|\n| .a | [5](x) |\n");
+        assert_eq!(
+            compile_all(md_syntax),
+            "'\\\" t\nSizes:\n.PP\n.TS\nlb rb\nl r.\nName\tSize\n_\n\\&.a\t5\n.TE\n"
+        );
+    }
+
+    #[test]
+    fn t_title() {
+        let options = ManOptions{date: Some("2024-01-01".to_string()), ..Default::default()};
+        let renderer = ManRenderer::new(&options);
+        assert_eq!(renderer.title("tool"), ".TH \"TOOL\" \"1\" \"2024\\-01\\-01\"");
+    }
+}
@@ -0,0 +1,371 @@
+use crate::front_matter::split_front_matter;
+use crate::parser::parse_md_str;
+use crate::tokens::*;
+use super::renderer::{indent, pad_columns, visible_width, wrap};
+use super::{render, BlockCollector, NodeContext, Renderer, Visit};
+
+/*
+ * Markdown target: prints the syntax tree back as normalized Markdown. Blocks are separated by a
+ * single blank line, list items share one bullet and emphasis one marker. The output parses into
+ * the same tree, so formatting formatted text doesn't change it.
+ */
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HeadingStyle {
+    Atx,
+    // Underlined with `=` or `-` for levels 1 and 2, deeper levels fall back to Atx.
+    Setext
+}
+
+pub struct MarkdownOptions {
+    // `-`, `*` or `+`.
+    pub bullet: char,
+    // `*` or `_`, doubled for bold. The other marker is used where this one wouldn't parse back.
+    pub emphasis: char,
+    pub heading_style: HeadingStyle,
+    // Spaces per level of nesting in lists.
+    pub list_indent: usize,
+    // Reflow paragraphs to lines of at most this many characters. Inline tokens are never split.
+    pub wrap: Option<usize>
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        MarkdownOptions{
+            bullet: '-',
+            emphasis: '*',
+            heading_style: HeadingStyle::Atx,
+            list_indent: 2,
+            wrap: None
+        }
+    }
+}
+
+pub fn compile_markdown(input: &MdSyntaxTree, options: &MarkdownOptions) -> String {
+    let mut renderer = MarkdownRenderer::new(options);
+    render(input, &mut renderer);
+    return renderer.finish();
+}
+
+/**
+ Format a Markdown document. Front matter is kept as is.
+*/
+pub fn format_markdown(source: &str, options: &MarkdownOptions) -> String {
+    let (_, markdown) = split_front_matter(source);
+    let front_matter = &source[..source.len() - markdown.len()];
+    return format!("{}{}", front_matter, compile_markdown(&parse_md_str(markdown), options));
+}
+
+// Stands in for spaces that lines mustn't break at while reflowing, eg. inside inline tokens.
+const GLUE: char = '\u{E000}';
+
+/**
+ Renderer for the Markdown target, see `BlockCollector`.
+*/
+pub struct MarkdownRenderer<'a> {
+    options: &'a MarkdownOptions,
+    blocks: BlockCollector
+}
+
+impl<'a> MarkdownRenderer<'a> {
+    pub fn new(options: &'a MarkdownOptions) -> Self {
+        MarkdownRenderer{options, blocks: BlockCollector::new().wrap(options.wrap, reflow)}
+    }
+
+    pub fn finish(self) -> String {
+        self.blocks.finish().replace(GLUE, " ")
+    }
+
+    // Marker for emphasis around `content`, falling back to the other one where the preferred
+    // marker would end the emphasis early or mid-word. Markers in code are kept as asterisks.
+    fn emphasis_marker(&self, content: &str, context: &NodeContext) -> char {
+        let preferred = if context.inside(|t| matches!(t, Token::InlineCode(_))) { '*' } else { self.options.emphasis };
+        let other = if preferred == '*' { '_' } else { '*' };
+        let word_follows = matches!(
+            context.next_sibling(),
+            Some(Token::PlainText(t)) if t.text().starts_with(char::is_alphanumeric)
+        );
+        let fits = |marker: char| !(content.contains(marker) || marker == '_' && word_follows);
+        return if fits(preferred) || !fits(other) { preferred } else { other };
+    }
+
+    fn header(&self, header: &Header, text: &str) -> String {
+        let text = format!("{}{}", text.trim(), block_attributes(&header.attributes));
+        match (self.options.heading_style, header.level) {
+            (HeadingStyle::Setext, level @ 1..=2) if !text.is_empty() => {
+                let underline = if level == 1 { "=" } else { "-" };
+                format!("{}\n{}", text, underline.repeat(text.chars().count().max(3)))
+            },
+            (_, level) => format!("{} {}", "#".repeat(level as usize), text)
+        }
+    }
+}
+
+impl Renderer for MarkdownRenderer<'_> {
+    fn enter(&mut self, token: &Token, _: &NodeContext) -> Visit {
+        let container = matches!(
+            token,
+            Token::List(_) | Token::OrderedList(_) | Token::DefinitionList(_) | Token::Definition(_) | Token::Admonition(_)
+            | Token::Table(_) | Token::TableRow(_)
+        ) || matches!(token, Token::Directive(d) if !d.inline);
+        self.blocks.enter(container.then_some(0));
+        return Visit::Children;
+    }
+
+    fn exit(&mut self, token: &Token, context: &NodeContext) {
+        let content = self.blocks.exit();
+        let inline = match token {
+            // Pipes in table cells were unescaped by the parser.
+            Token::PlainText(t) if context.inside(|t| matches!(t, Token::TableCell(_))) => {
+                self.blocks.push_inline(&t.text().replace('|', "\\|"));
+                return;
+            },
+            Token::PlainText(t) => {
+                self.blocks.push_inline(&t.text());
+                return;
+            },
+            Token::Italic(_) => {
+                let m = self.emphasis_marker(&content, context);
+                Some(format!("{m}{}{m}", content))
+            },
+            Token::Bold(_) => {
+                let m = self.emphasis_marker(&content, context);
+                Some(format!("{m}{m}{}{m}{m}", content))
+            },
+            Token::InlineCode(_) => Some(format!("`{}`", content)),
+            Token::Link(l) => Some(format!("[{}]({})", content, l.url)),
+            Token::InlineMath(m) => Some(format!("${}$", m.src())),
+            Token::DisplayMath(m) if context.depth > 0 => Some(format!("$${}$$", m.src())),
+            Token::Directive(d) if d.inline => Some(format!(":{}[{}]{}", d.name, content, attributes(&d.attributes))),
+            _ => None
+        };
+        if let Some(inline) = inline {
+            let glued = if self.options.wrap.is_some() { inline.replace(' ', &GLUE.to_string()) } else { inline };
+            self.blocks.push_inline(&glued);
+            return;
+        }
+
+        match token {
+            Token::Paragraph(p) => {
+                let line = content.trim();
+                if line.is_empty() {
+                    self.blocks.flush_paragraph();
+                    return;
+                }
+                // Attributes stay on the line when the paragraph is reflowed.
+                let attributes = block_attributes(&p.attributes);
+                let attributes = if self.options.wrap.is_some() { attributes.replace(' ', &GLUE.to_string()) } else { attributes };
+                self.blocks.push_line(&format!("{}{}", line, attributes));
+            },
+            Token::Header(h) => {
+                let header = self.header(h, &content);
+                self.blocks.push_block(header);
+            },
+            Token::DisplayMath(m) => self.blocks.push_block(format!("$${}$$", m.src())),
+            Token::TableOfContents(_) => self.blocks.push_block("[TOC]".to_string()),
+            Token::Code(c) => {
+                let info = format!("{}{}", c.lang.clone().unwrap_or_default(), block_attributes(&c.attributes));
+                let newline = if content.is_empty() || content.ends_with('\n') { "" } else { "\n" };
+                self.blocks.push_block(format!("```{}\n{}{}```", info.trim_start(), content, newline));
+            },
+            Token::ListItem(_) => {
+                let marker = context.item_number().map_or(self.options.bullet.to_string(), |n| format!("{}.", n));
+                self.blocks.push_block(format!("{} {}", marker, content.trim()));
+            },
+            Token::DefinitionTerm(_) => self.blocks.push_block(content.trim().to_string()),
+            Token::TableCell(_) => self.blocks.push_block(content.trim().to_string()),
+            Token::TableRow(_) => self.blocks.push_row(),
+            Token::Table(t) => {
+                let rows = self.blocks.take_rows();
+                self.blocks.push_block(table(&rows, &t.alignments));
+            },
+            Token::List(List{attributes: list_attributes, ..}) | Token::OrderedList(OrderedList{attributes: list_attributes, ..}) => {
+                let items = self.blocks.pop_frame().join("\n");
+                // Lists nested in a numbered item are indented past its number.
+                let list_indent = match context.ancestors.iter().rev().find(|t| t.is_list()) {
+                    Some(Token::OrderedList(_)) => self.options.list_indent.max(3),
+                    Some(_) => self.options.list_indent,
+                    None => 0
+                };
+                let list = indent(&items, &" ".repeat(list_indent));
+                let list = match attributes(list_attributes) {
+                    a if a.is_empty() => list,
+                    a => format!("{}\n{}", list, a)
+                };
+                self.blocks.push_block(list);
+            },
+            Token::DefinitionList(_) => {
+                let entries = self.blocks.pop_frame().join("\n");
+                self.blocks.push_block(entries);
+            },
+            Token::Definition(_) => {
+                // Continuation lines are indented to stay part of the definition.
+                let definition = indent(&self.blocks.pop_frame().join("\n"), "  ");
+                self.blocks.push_block(format!(": {}", definition.trim_start()));
+            },
+            Token::Admonition(a) => {
+                let title = a.title.as_ref().map(|t| format!(" {}", t)).unwrap_or_default();
+                let opening = format!(":::{}{}{}", a.kind.name(), title, block_attributes(&a.attributes));
+                let inner = self.blocks.pop_frame();
+                self.blocks.push_block(fenced(&opening, &inner));
+            },
+            Token::Directive(d) => {
+                let opening = format!(":::{}{}", d.name, attributes(&d.attributes));
+                let inner = self.blocks.pop_frame();
+                self.blocks.push_block(fenced(&opening, &inner));
+            },
+            _ => {}
+        }
+    }
+}
+
+// Columns padded to the same width, the delimiter row goes below the header row.
+fn table(rows: &[Vec<String>], alignments: &[Alignment]) -> String {
+    let padded = pad_columns(rows, alignments, 3);
+    let row = |cells: &[String]| format!("| {} |", cells.join(" | "));
+    let delimiter: Vec<String> = padded.first().map(Vec::as_slice).unwrap_or_default().iter().enumerate()
+        .map(|(i, cell)| delimiter_cell(alignments.get(i).copied().unwrap_or(Alignment::None), visible_width(cell)))
+        .collect();
+    let mut lines: Vec<String> = padded.iter().map(|cells| row(cells)).collect();
+    lines.insert(lines.len().min(1), row(&delimiter));
+    return lines.join("\n");
+}
+
+// Cell of the delimiter row, at least three columns wide.
+pub(crate) fn delimiter_cell(alignment: Alignment, width: usize) -> String {
+    let width = width.max(3);
+    match alignment {
+        Alignment::Left => format!(":{}", "-".repeat(width - 1)),
+        Alignment::Center => format!(":{}:", "-".repeat(width - 2)),
+        Alignment::Right => format!("{}:", "-".repeat(width - 1)),
+        Alignment::None => "-".repeat(width)
+    }
+}
+
+fn fenced(opening: &str, blocks: &[String]) -> String {
+    if blocks.is_empty() { return format!("{}\n:::", opening) }
+    return format!("{}\n{}\n:::", opening, blocks.join("\n\n"));
+}
+
+// Attributes in curly braces, eg. `{#intro .wide data-x=1}`. Empty when there are none.
+pub(crate) fn attributes(attributes: &Attributes) -> String {
+    if attributes.is_empty() { return String::new() }
+    let quote = |value: &str| {
+        if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '}') {
+            format!("\"{}\"", value)
+        } else {
+            value.to_string()
+        }
+    };
+    let mut parts = Vec::new();
+    if let Some(id) = attributes.get("id") { parts.push(format!("#{}", id)) }
+    if let Some(classes) = attributes.get("class") {
+        parts.extend(classes.split_whitespace().map(|c| format!(".{}", c)));
+    }
+    parts.extend(attributes.iter()
+        .filter(|(key, _)| *key != "id" && *key != "class")
+        .map(|(key, value)| format!("{}={}", key, quote(value))));
+    return format!("{{{}}}", parts.join(" "));
+}
+
+// Attributes at the end of a header or code info string, separated by a space.
+pub(crate) fn block_attributes(attributes: &Attributes) -> String {
+    if attributes.is_empty() { return String::new() }
+    return format!(" {}", self::attributes(attributes));
+}
+
+// Words that would be read as the start of a block if a line began with them.
+fn starts_block(word: &str) -> bool {
+    let lower = word.to_lowercase();
+    word.chars().all(|c| c == '#' || c == '=' || c == '-')
+        || word == "*" || word == "+"
+        || word.starts_with(['>', ':'])
+        || word.starts_with("```") || word.starts_with("$$")
+        || lower == "[toc]" || lower == "[[toc]]"
+}
+
+// Wrap a paragraph, words that would start a block stay on the line of the word before them.
+fn reflow(text: &str, width: usize) -> String {
+    let mut glued = String::with_capacity(text.len());
+    for (i, word) in text.split_whitespace().enumerate() {
+        if i > 0 { glued.push(if starts_block(word) { GLUE } else { ' ' }) }
+        glued.push_str(word);
+    }
+    return wrap(&glued, width);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(source: &str, options: &MarkdownOptions) -> String {
+        let formatted = format_markdown(source, options);
+        assert_eq!(format_markdown(&formatted, options), formatted, "not idempotent");
+        return formatted;
+    }
+
+    #[test]
+    fn t_normalize() {
+        let source = "Title {#top}\n=====\n* one\n+ *two*\n\n\n\ntext __bold__ and _it_\nsecond line\n\
+            ```rust\nfn main() {}\n```\nTerm\n: Definition\n  continued\n> [!NOTE] Heads up\n> Quoted\n";
+        assert_eq!(
+            format(source, &MarkdownOptions::default()),
+            "# Title {#top}\n\n- one\n- *two*\n\ntext **bold** and *it*\nsecond line\n\n\
+                ```rust\nfn main() {}\n```\n\nTerm\n: Definition\n  continued\n\n:::note Heads up\nQuoted\n:::\n"
+        );
+    }
+
+    #[test]
+    fn t_options() {
+        let options = MarkdownOptions{
+            bullet: '*',
+            emphasis: '_',
+            heading_style: HeadingStyle::Setext,
+            ..Default::default()
+        };
+        assert_eq!(
+            format("## Part\n- *a*b and **c**\n", &options),
+            "Part\n----\n\n* *a*b and __c__\n"
+        );
+        assert_eq!(
+            format("a `*code*` [*x*](/u) :kbd[Ctrl]{.key}", &options),
+            "a `*code*` [_x_](/u) :kbd[Ctrl]{.key}\n"
+        );
+    }
+
+    #[test]
+    fn t_wrap() {
+        let options = MarkdownOptions{wrap: Some(20), ..Default::default()};
+        assert_eq!(
+            format("one two three four five six\nseven [a long link](/x) - eight", &options),
+            "one two three four\nfive six seven\n[a long link](/x) -\neight\n"
+        );
+    }
+
+    #[test]
+    fn t_block_attributes() {
+        let source = "Text  {.lead}\n- a\n- b\n{#items}\n\n> [!NOTE] Title {.wide}\n> c\n";
+        let formatted = "Text {.lead}\n\n- a\n- b\n{#items}\n\n:::note Title {.wide}\nc\n:::\n";
+        assert_eq!(format(source, &MarkdownOptions::default()), formatted);
+        assert_eq!(format(formatted, &MarkdownOptions::default()), formatted);
+        let options = MarkdownOptions{wrap: Some(10), ..Default::default()};
+        assert_eq!(format("one two three {#p .x}", &options), "one two\nthree {#p .x}\n");
+    }
+
+    #[test]
+    fn t_tables() {
+        assert_eq!(
+            format("|a|*b*|c\n|:-|:-:|--:\n|one|2\n", &MarkdownOptions::default()),
+            "| a   | *b* |   c |\n| :-- | :-: | --: |\n| one |  2  |     |\n"
+        );
+        assert_eq!(format("|a|\n|-|\n|x \\| y|\n", &MarkdownOptions::default()), "| a      |\n| ------ |\n| x \\| y |\n");
+    }
+
+    #[test]
+    fn t_front_matter_kept() {
+        assert_eq!(
+            format("---\ntitle: Doc\n---\n#  Hi", &MarkdownOptions::default()),
+            "---\ntitle: Doc\n---\n# Hi\n"
+        );
+    }
+}
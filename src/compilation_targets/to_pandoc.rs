@@ -0,0 +1,379 @@
+use serde_json::{json, Value};
+
+use crate::front_matter::{split_front_matter, FrontMatter};
+use crate::parser::parse_md_str;
+use crate::slug::{header_slugs, text_content};
+use crate::tokens::*;
+use crate::tree::TreeIndex;
+
+/*
+ * Pandoc JSON AST target, for piping documents into `pandoc -f json`. Consecutive paragraph
+ * lines form one `Para` with soft breaks, text is split into `Str` and `Space` inlines. Tokens
+ * without a Pandoc element of their own use its conventions: admonitions are `Div`s with the kind
+ * as class and a "title" `Div`, directives `Div`s or `Span`s. The table of contents is left to
+ * pandoc's `--toc`.
+ */
+
+// Version of pandoc-types the output follows.
+pub const PANDOC_API_VERSION: [u32; 3] = [1, 23, 1];
+
+#[derive(Default)]
+pub struct PandocOptions {
+    // Document metadata, eg. the title. Values are text.
+    pub meta: FrontMatter
+}
+
+pub fn compile_all(input: MdSyntaxTree) -> String {
+    return compile_all_with(&input, &PandocOptions::default());
+}
+
+pub fn compile_all_with(input: &MdSyntaxTree, options: &PandocOptions) -> String {
+    return to_pandoc(input, options).to_string();
+}
+
+/**
+ Compile a document with front matter, which becomes the metadata of the Pandoc document.
+*/
+pub fn compile_document(source: &str) -> String {
+    let (meta, markdown) = split_front_matter(source);
+    return compile_all_with(&parse_md_str(markdown), &PandocOptions{meta});
+}
+
+/**
+ Pandoc document of the syntax tree as a JSON value.
+*/
+pub fn to_pandoc(input: &MdSyntaxTree, options: &PandocOptions) -> Value {
+    let mut writer = PandocWriter{doc: input, slugs: header_slugs(input).into_iter()};
+    let roots: Vec<(usize, usize)> = (0..input.len()).map(|line| (line, 0)).collect();
+    let meta: serde_json::Map<String, Value> = options.meta.iter()
+        .map(|(key, value)| (key.clone(), json!({"t": "MetaInlines", "c": text_inlines(value)})))
+        .collect();
+    return json!({
+        "pandoc-api-version": PANDOC_API_VERSION,
+        "meta": meta,
+        "blocks": writer.blocks(&roots)
+    });
+}
+
+struct PandocWriter<'a> {
+    doc: &'a MdSyntaxTree,
+    slugs: std::vec::IntoIter<String>
+}
+
+impl PandocWriter<'_> {
+    fn token(&self, (line, index): (usize, usize)) -> &Token {
+        &self.doc[line][TreeIndex::Arena(index)]
+    }
+
+    fn children(&self, (line, index): (usize, usize)) -> Vec<(usize, usize)> {
+        self.doc[line].get_node(TreeIndex::Arena(index))
+            .map(|n| n.children.iter().map(|c| (line, *c)).collect())
+            .unwrap_or_default()
+    }
+
+    fn text(&self, (line, index): (usize, usize)) -> String {
+        text_content(&self.doc[line], TreeIndex::Arena(index))
+    }
+
+    // Blocks with the lines of each paragraph joined into one `Para`.
+    fn blocks(&mut self, nodes: &[(usize, usize)]) -> Vec<Value> {
+        let mut blocks = Vec::new();
+        let mut paragraph: Option<(Vec<Value>, Attributes)> = None;
+        for node in nodes.iter().copied() {
+            let children = self.children(node);
+            match self.token(node).clone() {
+                Token::Paragraph(_) if children.is_empty() => {
+                    blocks.extend(paragraph.take().map(para));
+                },
+                Token::Paragraph(p) => {
+                    let inlines = self.inlines(&children);
+                    match paragraph.as_mut() {
+                        Some((open, attributes)) => {
+                            open.push(json!({"t": "SoftBreak"}));
+                            open.extend(inlines);
+                            attributes.extend(p.attributes);
+                        },
+                        None => paragraph = Some((inlines, p.attributes))
+                    }
+                },
+                _ => {
+                    blocks.extend(paragraph.take().map(para));
+                    blocks.extend(self.block(node));
+                }
+            }
+        }
+        blocks.extend(paragraph.map(para));
+        return blocks;
+    }
+
+    // Blocks of each list item, nested lists belong to the item before them.
+    fn items(&mut self, children: &[(usize, usize)]) -> Vec<Value> {
+        let mut items: Vec<Vec<Value>> = Vec::new();
+        for child in children {
+            if self.token(*child).is_list() {
+                let list = self.block(*child);
+                match items.last_mut() {
+                    Some(blocks) => blocks.extend(list),
+                    None => items.push(list.into_iter().collect())
+                }
+            } else {
+                items.push(vec![json!({"t": "Plain", "c": self.inlines(&self.children(*child))})]);
+            }
+        }
+        return items.into_iter().map(|blocks| json!(blocks)).collect();
+    }
+
+    fn block(&mut self, node: (usize, usize)) -> Option<Value> {
+        let children = self.children(node);
+        let block = match self.token(node).clone() {
+            Token::Header(h) => {
+                let mut attributes = h.attributes.clone();
+                if let Some(slug) = self.slugs.next() { attributes.insert("id".to_string(), slug); }
+                json!({"t": "Header", "c": [h.level, attr(&attributes), self.inlines(&children)]})
+            },
+            Token::List(l) => with_div(json!({"t": "BulletList", "c": self.items(&children)}), &l.attributes),
+            Token::OrderedList(l) => {
+                let list_attributes = json!([l.start, {"t": "Decimal"}, {"t": "Period"}]);
+                with_div(json!({"t": "OrderedList", "c": [list_attributes, self.items(&children)]}), &l.attributes)
+            },
+            Token::Code(c) => {
+                let mut attributes = c.attributes.clone();
+                if let Some(lang) = &c.lang {
+                    let class = attributes.get("class").map_or(lang.clone(), |class| format!("{} {}", lang, class));
+                    attributes.insert("class".to_string(), class);
+                }
+                let code = self.text(node);
+                json!({"t": "CodeBlock", "c": [attr(&attributes), code.strip_suffix('\n').unwrap_or(&code)]})
+            },
+            Token::DefinitionList(_) => {
+                // Definitions belong to the term before them.
+                let mut items: Vec<(Vec<Value>, Vec<Value>)> = Vec::new();
+                for child in children {
+                    match self.token(child) {
+                        Token::DefinitionTerm(_) => items.push((self.inlines(&self.children(child)), Vec::new())),
+                        _ => {
+                            let definition = self.blocks(&self.children(child));
+                            match items.last_mut() {
+                                Some((_, definitions)) => definitions.push(json!(definition)),
+                                None => items.push((Vec::new(), vec![json!(definition)]))
+                            }
+                        }
+                    }
+                }
+                let items: Vec<Value> = items.into_iter().map(|(term, definitions)| json!([term, definitions])).collect();
+                json!({"t": "DefinitionList", "c": items})
+            },
+            Token::DisplayMath(m) => json!({"t": "Para", "c": [math("DisplayMath", &m.tex)]}),
+            Token::Admonition(a) => {
+                let title = json!({"t": "Div", "c": [attr_classes(&["title"]), [{"t": "Para", "c": text_inlines(&a.display_title())}]]});
+                let mut content = vec![title];
+                content.extend(self.blocks(&children));
+                json!({"t": "Div", "c": [named_attr(a.kind.name(), &a.attributes), content]})
+            },
+            Token::Directive(d) => json!({"t": "Div", "c": [named_attr(&d.name, &d.attributes), self.blocks(&children)]}),
+            Token::TableOfContents(_) => return None,
+            Token::Table(t) => {
+                let empty = attr(&Attributes::new());
+                let colspecs: Vec<Value> = t.alignments.iter()
+                    .map(|a| json!([{"t": alignment(*a)}, {"t": "ColWidthDefault"}]))
+                    .collect();
+                let (mut head, mut body) = (Vec::new(), Vec::new());
+                for row in children {
+                    let cells: Vec<Value> = self.children(row).into_iter()
+                        .map(|cell| {
+                            let inlines = self.inlines(&self.children(cell));
+                            let blocks = if inlines.is_empty() { json!([]) } else { json!([{"t": "Plain", "c": inlines}]) };
+                            json!([empty, {"t": "AlignDefault"}, 1, 1, blocks])
+                        })
+                        .collect();
+                    let row_value = json!([empty, cells]);
+                    if matches!(self.token(row), Token::TableRow(r) if r.header) { head.push(row_value) } else { body.push(row_value) }
+                }
+                json!({"t": "Table", "c": [
+                    empty, [null, []], colspecs, [empty, head], [[empty, 0, [], body]], [empty, []]
+                ]})
+            },
+            // Blocks that only appear inside others, and inline tokens.
+            _ => json!({"t": "Plain", "c": self.inlines(&[node])})
+        };
+        return Some(block);
+    }
+
+    fn inlines(&mut self, nodes: &[(usize, usize)]) -> Vec<Value> {
+        let mut inlines = Vec::new();
+        for node in nodes.iter().copied() {
+            let children = self.children(node);
+            let inline = match self.token(node).clone() {
+                Token::PlainText(t) => {
+                    for inline in text_inlines(&t.text) { push_inline(&mut inlines, inline) }
+                    continue;
+                },
+                Token::Italic(_) => json!({"t": "Emph", "c": self.inlines(&children)}),
+                Token::Bold(_) => json!({"t": "Strong", "c": self.inlines(&children)}),
+                Token::InlineCode(_) => json!({"t": "Code", "c": [attr(&Attributes::new()), self.text(node)]}),
+                Token::Link(l) => json!({"t": "Link", "c": [attr(&Attributes::new()), self.inlines(&children), [l.url, ""]]}),
+                Token::InlineMath(m) => math("InlineMath", &m.tex),
+                Token::DisplayMath(m) => math("DisplayMath", &m.tex),
+                Token::Directive(d) => json!({"t": "Span", "c": [named_attr(&d.name, &d.attributes), self.inlines(&children)]}),
+                // Blocks in inline content keep their text.
+                _ => {
+                    for inline in self.inlines(&children) { push_inline(&mut inlines, inline) }
+                    continue;
+                }
+            };
+            push_inline(&mut inlines, inline);
+        }
+        return inlines;
+    }
+}
+
+// Add an inline, joining adjacent `Str`s and spaces like pandoc's readers do.
+fn push_inline(inlines: &mut Vec<Value>, inline: Value) {
+    if let Some(last) = inlines.last_mut() {
+        if last["t"] == "Str" && inline["t"] == "Str" {
+            last["c"] = Value::String(format!("{}{}", last["c"].as_str().unwrap_or(""), inline["c"].as_str().unwrap_or("")));
+            return;
+        }
+        if last["t"] == "Space" && inline["t"] == "Space" { return }
+    }
+    inlines.push(inline);
+}
+
+// `Str` words separated by `Space` and `SoftBreak`.
+fn text_inlines(text: &str) -> Vec<Value> {
+    let mut inlines = Vec::new();
+    let mut word = String::new();
+    for c in text.chars() {
+        let separator = match c {
+            '\n' => json!({"t": "SoftBreak"}),
+            c if c.is_whitespace() => json!({"t": "Space"}),
+            _ => {
+                word.push(c);
+                continue;
+            }
+        };
+        if !word.is_empty() { push_inline(&mut inlines, json!({"t": "Str", "c": std::mem::take(&mut word)})) }
+        push_inline(&mut inlines, separator);
+    }
+    if !word.is_empty() { push_inline(&mut inlines, json!({"t": "Str", "c": word})) }
+    return inlines;
+}
+
+fn math(kind: &str, tex: &str) -> Value {
+    json!({"t": "Math", "c": [{"t": kind}, tex]})
+}
+
+fn alignment(alignment: Alignment) -> &'static str {
+    match alignment {
+        Alignment::None => "AlignDefault",
+        Alignment::Left => "AlignLeft",
+        Alignment::Center => "AlignCenter",
+        Alignment::Right => "AlignRight"
+    }
+}
+
+// Pandoc attributes: identifier, classes and other key value pairs.
+fn attr(attributes: &Attributes) -> Value {
+    let id = attributes.get("id").cloned().unwrap_or_default();
+    let classes: Vec<&str> = attributes.get("class").map(|c| c.split_whitespace().collect()).unwrap_or_default();
+    let pairs: Vec<[&String; 2]> = attributes.iter()
+        .filter(|(key, _)| *key != "id" && *key != "class")
+        .map(|(key, value)| [key, value])
+        .collect();
+    return json!([id, classes, pairs]);
+}
+
+fn attr_classes(classes: &[&str]) -> Value {
+    json!(["", classes, []])
+}
+
+// The name of a directive or the kind of a callout is its first class.
+fn named_attr(name: &str, attributes: &Attributes) -> Value {
+    let mut attributes = attributes.clone();
+    let class = match attributes.get("class") {
+        Some(class) => format!("{} {}", name, class),
+        None => name.to_string()
+    };
+    attributes.insert("class".to_string(), class);
+    return attr(&attributes);
+}
+
+// Pandoc has no attributes on paragraphs and lists, they go on a `Div` around the block.
+fn with_div(block: Value, attributes: &Attributes) -> Value {
+    if attributes.is_empty() { return block }
+    return json!({"t": "Div", "c": [attr(attributes), [block]]});
+}
+
+fn para((inlines, attributes): (Vec<Value>, Attributes)) -> Value {
+    with_div(json!({"t": "Para", "c": inlines}), &attributes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_pandoc() {
+        let pandoc = to_pandoc(
+            &parse_md_str("# Title\nSome *new*  text\nnext [link](/a)\n\n- `one`\n"),
+            &PandocOptions::default()
+        );
+        assert_eq!(pandoc, json!({
+            "pandoc-api-version": [1, 23, 1],
+            "meta": {},
+            "blocks": [
+                {"t": "Header", "c": [1, ["title", [], []], [{"t": "Str", "c": "Title"}]]},
+                {"t": "Para", "c": [
+                    {"t": "Str", "c": "Some"}, {"t": "Space"}, {"t": "Emph", "c": [{"t": "Str", "c": "new"}]},
+                    {"t": "Space"}, {"t": "Str", "c": "text"}, {"t": "SoftBreak"}, {"t": "Str", "c": "next"},
+                    {"t": "Space"}, {"t": "Link", "c": [["", [], []], [{"t": "Str", "c": "link"}], ["/a", ""]]}
+                ]},
+                {"t": "BulletList", "c": [[{"t": "Plain", "c": [{"t": "Code", "c": [["", [], []], "one"]}]}]]}
+            ]
+        }));
+    }
+
+    #[test]
+    fn t_blocks_and_meta() {
+        let source = "---\ntitle: My doc\n---\n```rust\nfn main() {}\n```\nTerm\n: One\n: Two\n\n> [!NOTE]\n> Careful\n";
+        let pandoc: Value = serde_json::from_str(&compile_document(source)).unwrap();
+        assert_eq!(pandoc["meta"], json!({"title": {"t": "MetaInlines", "c": [
+            {"t": "Str", "c": "My"}, {"t": "Space"}, {"t": "Str", "c": "doc"}
+        ]}}));
+        assert_eq!(pandoc["blocks"][0], json!({"t": "CodeBlock", "c": [["", ["rust"], []], "fn main() {}"]}));
+        assert_eq!(pandoc["blocks"][1], json!({"t": "DefinitionList", "c": [[
+            [{"t": "Str", "c": "Term"}],
+            [[{"t": "Para", "c": [{"t": "Str", "c": "One"}]}], [{"t": "Para", "c": [{"t": "Str", "c": "Two"}]}]]
+        ]]}));
+        assert_eq!(pandoc["blocks"][2], json!({"t": "Div", "c": [["", ["note"], []], [
+            {"t": "Div", "c": [["", ["title"], []], [{"t": "Para", "c": [{"t": "Str", "c": "Note"}]}]]},
+            {"t": "Para", "c": [{"t": "Str", "c": "Careful"}]}
+        ]]}));
+    }
+
+    #[test]
+    fn t_block_attributes() {
+        let pandoc = to_pandoc(&parse_md_str("Text {.lead}\n- a\n{#items}\n"), &PandocOptions::default());
+        assert_eq!(pandoc["blocks"], json!([
+            {"t": "Div", "c": [["", ["lead"], []], [{"t": "Para", "c": [{"t": "Str", "c": "Text"}]}]]},
+            {"t": "Div", "c": [["items", [], []], [
+                {"t": "BulletList", "c": [[{"t": "Plain", "c": [{"t": "Str", "c": "a"}]}]]}
+            ]]}
+        ]));
+    }
+
+    #[test]
+    fn t_table() {
+        let pandoc = to_pandoc(&parse_md_str("| a | b |\n|:-:|---|\n| *c* |\n"), &PandocOptions::default());
+        let empty = json!(["", [], []]);
+        let cell = |blocks: Value| json!([empty, {"t": "AlignDefault"}, 1, 1, blocks]);
+        let plain = |inline: Value| json!([{"t": "Plain", "c": [inline]}]);
+        assert_eq!(pandoc["blocks"], json!([{"t": "Table", "c": [
+            empty, [null, []],
+            [[{"t": "AlignCenter"}, {"t": "ColWidthDefault"}], [{"t": "AlignDefault"}, {"t": "ColWidthDefault"}]],
+            [empty, [[empty, [cell(plain(json!({"t": "Str", "c": "a"}))), cell(plain(json!({"t": "Str", "c": "b"})))]]]],
+            [[empty, 0, [], [[empty, [cell(plain(json!({"t": "Emph", "c": [{"t": "Str", "c": "c"}]}))), cell(json!([]))]]]]],
+            [empty, []]
+        ]}]));
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashSet;
+
+use crate::slug::header_slugs;
+use crate::tokens::*;
+use super::to_asciidoc::adjacent_words;
+use super::renderer::{escape_line_start, indent, visible_width};
+use super::{render, BlockCollector, NodeContext, Renderer, Visit};
+
+/*
+ * reStructuredText target, eg. for Sphinx. Headers are underlined, emphasis becomes `*` and `**`,
+ * lists `-` items, tables list tables and code `code` directives with their language. Math,
+ * admonitions and the table of contents use the standard directives and roles. Headers that links
+ * point to get a target label so that the links resolve.
+ */
+
+pub struct RstOptions {
+    // Underline characters of header levels 1 to 6.
+    pub underlines: [char; 6],
+    // Spaces that the content of directives and definitions is indented by.
+    pub indent: usize
+}
+
+impl Default for RstOptions {
+    fn default() -> Self {
+        RstOptions{underlines: ['=', '-', '~', '^', '"', '\''], indent: 3}
+    }
+}
+
+pub fn compile_all(input: MdSyntaxTree) -> String {
+    return compile_all_with(&input, &RstOptions::default());
+}
+
+pub fn compile_all_with(input: &MdSyntaxTree, options: &RstOptions) -> String {
+    let mut renderer = RstRenderer::new(options);
+    render(input, &mut renderer);
+    return renderer.finish();
+}
+
+/**
+ Renderer for the reStructuredText target, see `BlockCollector`.
+*/
+pub struct RstRenderer<'a> {
+    options: &'a RstOptions,
+    blocks: BlockCollector,
+    slugs: std::vec::IntoIter<String>,
+    // Headers that internal links point to.
+    targets: HashSet<String>
+}
+
+impl<'a> RstRenderer<'a> {
+    pub fn new(options: &'a RstOptions) -> Self {
+        RstRenderer{
+            options,
+            blocks: BlockCollector::new(),
+            slugs: Vec::new().into_iter(),
+            targets: HashSet::new()
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.blocks.finish()
+    }
+
+    // Directive with its options and content indented below it.
+    fn directive(&self, head: &str, content: &[String]) -> String {
+        let content = indent(&content.join("\n\n"), &" ".repeat(self.options.indent));
+        if content.is_empty() { return head.to_string() }
+        return format!("{}\n\n{}", head, content);
+    }
+}
+
+impl Renderer for RstRenderer<'_> {
+    fn start_document(&mut self, doc: &MdSyntaxTree) {
+        self.slugs = header_slugs(doc).into_iter();
+        for line in doc {
+            for token in line.iter_dfs() {
+                if let Token::Link(l) = token {
+                    if let Some(target) = l.url.strip_prefix('#') { self.targets.insert(target.to_string()); }
+                }
+            }
+        }
+    }
+
+    fn enter(&mut self, token: &Token, _: &NodeContext) -> Visit {
+        let container = token.is_container() || matches!(token, Token::Definition(_));
+        self.blocks.enter(container.then_some(0));
+        return Visit::Children;
+    }
+
+    fn exit(&mut self, token: &Token, context: &NodeContext) {
+        let content = self.blocks.exit();
+        if context.inside(|t| matches!(t, Token::Code(_) | Token::InlineCode(_))) {
+            // Code is literal, inline tokens in it keep only their text.
+            if let Token::PlainText(t) = token { self.blocks.push_inline(&t.text()) } else { self.blocks.push_inline(&content) }
+            return;
+        }
+        // Inline markup next to a word needs an escaped space, which is removed from the output.
+        let (word_before, word_after) = adjacent_words(context);
+        let inline = |markup: String| {
+            format!("{}{}{}", if word_before { "\\ " } else { "" }, markup, if word_after { "\\ " } else { "" })
+        };
+        match token {
+            Token::PlainText(t) => self.blocks.push_inline(&escape_rst(&t.text())),
+            Token::Italic(_) => self.blocks.push_inline(&inline(format!("*{}*", content))),
+            Token::Bold(_) => self.blocks.push_inline(&inline(format!("**{}**", content))),
+            Token::InlineCode(_) => self.blocks.push_inline(&inline(format!("``{}``", content))),
+            Token::Link(l) if content.is_empty() => self.blocks.push_inline(&l.url),
+            Token::Link(l) => {
+                // Internal links refer to the target label of the header.
+                let target = match l.url.strip_prefix('#') {
+                    Some(label) => format!("{}_", label),
+                    None => l.url.clone()
+                };
+                self.blocks.push_inline(&inline(format!("`{} <{}>`__", content.replace('<', "\\<"), target)));
+            },
+            Token::InlineMath(m) => self.blocks.push_inline(&inline(format!(":math:`{}`", m.src()))),
+            Token::DisplayMath(m) if context.depth > 0 => self.blocks.push_inline(&inline(format!(":math:`{}`", m.src()))),
+            Token::Directive(d) if d.inline => self.blocks.push_inline(&inline(format!(":{}:`{}`", d.name, content))),
+            Token::Paragraph(_) => self.blocks.push_line(&escape_enumerator(content.trim())),
+            Token::Header(h) => {
+                let text = content.trim();
+                let underline = self.options.underlines[(h.level.clamp(1, 6) - 1) as usize];
+                let slug = self.slugs.next().unwrap_or_default();
+                let label = match h.attributes.get("id") {
+                    Some(id) => format!(".. _{}:\n\n", id),
+                    None if self.targets.contains(&slug) => format!(".. _{}:\n\n", slug),
+                    None => String::new()
+                };
+                let width = visible_width(text).max(1);
+                self.blocks.push_block(format!("{}{}\n{}", label, text, underline.to_string().repeat(width)));
+            },
+            Token::Code(c) => {
+                let code = content.strip_suffix('\n').unwrap_or(&content).to_string();
+                let head = c.lang.as_ref().map(|l| format!(".. code:: {}", l)).unwrap_or_else(|| "::".to_string());
+                let block = self.directive(&head, &[code]);
+                self.blocks.push_block(block);
+            },
+            Token::DisplayMath(m) => {
+                let block = self.directive(".. math::", &[m.src()]);
+                self.blocks.push_block(block);
+            },
+            Token::TableOfContents(_) => self.blocks.push_block(".. contents::".to_string()),
+            Token::ListItem(_) => {
+                let marker = context.item_number().map(|n| format!("{}.", n)).unwrap_or_else(|| "-".to_string());
+                self.blocks.push_block(format!("{} {}", marker, content.trim()));
+            },
+            Token::List(_) | Token::OrderedList(_) => {
+                let items = self.blocks.pop_frame().join("\n");
+                // Nested lists line up with the text of the item before them, between blank lines.
+                match context.ancestors.iter().rev().find(|t| t.is_list()) {
+                    Some(parent) => {
+                        let width = if matches!(parent, Token::OrderedList(_)) { 3 } else { 2 };
+                        self.blocks.push_block(format!("\n{}\n", indent(&items, &" ".repeat(width))));
+                    },
+                    None => self.blocks.push_block(items.trim_end().to_string())
+                }
+            },
+            Token::DefinitionTerm(_) => self.blocks.push_block(content.trim().to_string()),
+            Token::Definition(_) => {
+                let definition = indent(&self.blocks.pop_frame().join("\n\n"), &" ".repeat(self.options.indent));
+                // Further definitions are paragraphs of the same entry.
+                let separator = if matches!(context.previous_sibling(), Some(Token::Definition(_))) { "\n" } else { "" };
+                self.blocks.push_block(format!("{}{}", separator, definition));
+            },
+            Token::DefinitionList(_) => {
+                let entries = self.blocks.pop_frame().join("\n");
+                self.blocks.push_block(entries);
+            },
+            Token::TableCell(_) => self.blocks.push_block(content.trim().to_string()),
+            Token::TableRow(_) => {
+                let cells: Vec<String> = self.blocks.pop_frame().iter().enumerate()
+                    .map(|(i, cell)| format!("{} - {}", if i == 0 { "*" } else { " " }, cell).trim_end().to_string())
+                    .collect();
+                self.blocks.push_block(cells.join("\n"));
+            },
+            Token::Table(_) => {
+                // Column alignments have no equivalent in list tables.
+                let rows = self.blocks.pop_frame().join("\n");
+                let header = context.children().any(|r| matches!(r, Token::TableRow(r) if r.header));
+                let head = if header {
+                    format!(".. list-table::\n{}:header-rows: 1", " ".repeat(self.options.indent))
+                } else {
+                    ".. list-table::".to_string()
+                };
+                self.blocks.push_block(self.directive(&head, &[rows]));
+            },
+            Token::Admonition(a) => {
+                let inner = self.blocks.pop_frame();
+                let head = match (&a.title, a.kind) {
+                    (None, AdmonitionKind::Info) => ".. admonition:: Info".to_string(),
+                    (None, kind) => format!(".. {}::", kind.name()),
+                    (Some(title), kind) => format!(".. admonition:: {}\n{}:class: {}", title, " ".repeat(self.options.indent), kind.name())
+                };
+                let block = self.directive(&head, &inner);
+                self.blocks.push_block(block);
+            },
+            Token::Directive(d) => {
+                let inner = self.blocks.pop_frame();
+                let block = self.directive(&format!(".. container:: {}", d.name), &inner);
+                self.blocks.push_block(block);
+            }
+        }
+    }
+}
+
+/**
+ Escape the characters that could start reStructuredText inline markup.
+*/
+pub fn escape_rst(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut escaped = String::with_capacity(text.len());
+    for (i, c) in chars.iter().enumerate() {
+        let inside_word = i > 0 && chars[i - 1].is_alphanumeric() && chars.get(i + 1).is_some_and(|c| c.is_alphanumeric());
+        // Underscores inside words, eg. snake_case, aren't references.
+        let special = matches!(c, '\\' | '*' | '`' | '|') || *c == '_' && !inside_word;
+        if special { escaped.push('\\') }
+        escaped.push(*c);
+    }
+    return escaped;
+}
+
+// Lines starting with these would be read as a list item, directive or underline.
+const LINE_START: &[&str] = &["-", "+", "#", ".", ":", ">", "=", "~", "^", "\"", "'"];
+
+// Escape a line that would start a block, the period of a number such as `1.` is escaped after it.
+fn escape_enumerator(line: &str) -> String {
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 && line[digits..].starts_with(['.', ')']) {
+        return format!("{}\\{}", &line[..digits], &line[digits..]);
+    }
+    return escape_line_start(line, LINE_START, "\\");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    #[test]
+    fn t_rst() {
+        let md_syntax = parse_md_str(
+            "# Title\n## Usage\nSome *new* **bold**text and [docs](https://gnu.org) or [back](#usage).\n\
+            a snake_case x*y |sub|\n\n- one\n- `a*b`\n\n```rust\nlet x = 1;\n```\nTerm\n: First\n: Second\n\
+            \n> [!WARNING]\n> Careful\n"
+        );
+        assert_eq!(
+            compile_all(md_syntax),
+            "Title\n=====\n\n.. _usage:\n\nUsage\n-----\n\n\
+                Some *new* **bold**\\ text and `docs <https://gnu.org>`__ or `back <usage_>`__.\n\
+                a snake_case x\\*y \\|sub\\|\n\n\
+                - one\n- ``a*b``\n\n.. code:: rust\n\n   let x = 1;\n\n\
+                Term\n   First\n\n   Second\n\n.. warning::\n\n   Careful\n"
+        );
+        // Wide characters take two columns of the underline.
+        assert_eq!(compile_all(parse_md_str("# 日本\n")), "日本\n====\n");
+    }
+
+    #[test]
+    fn t_line_start() {
+        assert_eq!(escape_enumerator("- not a list"), "\\- not a list");
+        assert_eq!(escape_enumerator("1. not a list"), "1\\. not a list");
+        assert_eq!(escape_enumerator(".. not a comment"), "\\.. not a comment");
+        assert_eq!(escape_rst("ref_ and _target"), "ref\\_ and \\_target");
+    }
+
+    #[test]
+    fn t_table() {
+        let md_syntax = parse_md_str("| Item | Price |\n|:--|--:|\n| *tea* | 3 |\n| coffee | |\n");
+        assert_eq!(
+            compile_all(md_syntax),
+            ".. list-table::\n   :header-rows: 1\n\n   * - Item\n     - Price\n   * - *tea*\n     - 3\n   * - coffee\n     -\n"
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use crate::toc::{build_toc, TocEntry, TocOptions};
+use crate::tokens::*;
+use super::renderer::{indent, text_table, visible_width, wrap, wrap_hanging};
+use super::{render, BlockCollector, NodeContext, Renderer, Visit};
+
+/*
+ * Plain text target for email bodies and search snippets. Formatting is dropped, headers are
+ * underlined, lists keep their bullets, table columns are aligned, code is indented and link urls
+ * follow the link text in brackets or are collected as numbered footnotes.
+ */
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LinkStyle {
+    // `text [url]`
+    Brackets,
+    // `text[1]` with `[1] url` listed at the end of the document.
+    Footnotes
+}
+
+pub struct TextOptions {
+    pub links: LinkStyle,
+    // Wrap text to lines of at most this many characters, code is never wrapped.
+    pub wrap: Option<usize>,
+    pub code_indent: usize,
+    // Spaces per level of nested lists.
+    pub list_indent: usize,
+    pub toc: TocOptions
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions{
+            links: LinkStyle::Brackets,
+            wrap: None,
+            code_indent: 4,
+            list_indent: 2,
+            toc: TocOptions::default()
+        }
+    }
+}
+
+pub fn compile_all(input: MdSyntaxTree) -> String {
+    return compile_all_with(&input, &TextOptions::default());
+}
+
+pub fn compile_all_with(input: &MdSyntaxTree, options: &TextOptions) -> String {
+    let mut renderer = TextRenderer::new(options);
+    render(input, &mut renderer);
+    return renderer.finish();
+}
+
+// Indentation of the blocks inside a container.
+const DEFINITION_INDENT: usize = 4;
+const ADMONITION_INDENT: usize = 2;
+
+/**
+ Renderer for the plain text target, see `BlockCollector`.
+*/
+pub struct TextRenderer<'a> {
+    options: &'a TextOptions,
+    blocks: BlockCollector,
+    // Urls of link footnotes in order of first appearance.
+    footnotes: Vec<String>,
+    toc: String
+}
+
+impl<'a> TextRenderer<'a> {
+    pub fn new(options: &'a TextOptions) -> Self {
+        TextRenderer{
+            options,
+            blocks: BlockCollector::new().wrap(options.wrap, wrap),
+            footnotes: Vec::new(),
+            toc: String::new()
+        }
+    }
+
+    pub fn finish(self) -> String {
+        let mut text = self.blocks.into_blocks().join("\n\n");
+        if !self.footnotes.is_empty() {
+            let notes: Vec<String> = self.footnotes.iter().enumerate()
+                .map(|(i, url)| format!("[{}] {}", i + 1, url))
+                .collect();
+            if !text.is_empty() { text.push_str("\n\n") }
+            text.push_str(&notes.join("\n"));
+        }
+        if !text.is_empty() { text.push('\n') }
+        return text;
+    }
+
+    fn link(&mut self, link: &Link, text: &str) -> String {
+        if link.url.is_empty() || link.url == text { return text.to_string() }
+        match self.options.links {
+            LinkStyle::Brackets => format!("{} [{}]", text, link.url),
+            LinkStyle::Footnotes => {
+                let number = match self.footnotes.iter().position(|url| *url == link.url) {
+                    Some(i) => i + 1,
+                    None => {
+                        self.footnotes.push(link.url.clone());
+                        self.footnotes.len()
+                    }
+                };
+                format!("{}[{}]", text, number)
+            }
+        }
+    }
+}
+
+impl Renderer for TextRenderer<'_> {
+    fn start_document(&mut self, doc: &MdSyntaxTree) {
+        self.toc = toc_to_text(&build_toc(doc, &self.options.toc), self.options.list_indent);
+    }
+
+    fn enter(&mut self, token: &Token, context: &NodeContext) -> Visit {
+        let indent = match token {
+            Token::List(_) | Token::OrderedList(_) if context.inside(Token::is_list) => Some(self.options.list_indent),
+            Token::List(_) | Token::OrderedList(_) | Token::DefinitionList(_) => Some(0),
+            Token::Definition(_) => Some(DEFINITION_INDENT),
+            Token::Admonition(_) => Some(ADMONITION_INDENT),
+            Token::Directive(d) if !d.inline => Some(0),
+            Token::Table(_) | Token::TableRow(_) => Some(0),
+            _ => None
+        };
+        self.blocks.enter(indent);
+        return Visit::Children;
+    }
+
+    fn exit(&mut self, token: &Token, context: &NodeContext) {
+        let content = self.blocks.exit();
+        match token {
+            Token::PlainText(t) => self.blocks.push_inline(&t.text()),
+            Token::Italic(_) | Token::Bold(_) | Token::InlineCode(_) => self.blocks.push_inline(&content),
+            Token::Directive(d) if d.inline => self.blocks.push_inline(&content),
+            Token::InlineMath(m) => self.blocks.push_inline(&m.src()),
+            Token::DisplayMath(m) if context.depth > 0 => self.blocks.push_inline(&m.src()),
+            Token::Link(l) => {
+                let link = self.link(l, &content);
+                self.blocks.push_inline(&link);
+            },
+            Token::Paragraph(_) => self.blocks.push_line(content.trim()),
+            Token::Header(h) => {
+                let text = content.trim().to_string();
+                let underline = match h.level {
+                    1 => format!("\n{}", "=".repeat(visible_width(&text))),
+                    2 => format!("\n{}", "-".repeat(visible_width(&text))),
+                    _ => String::new()
+                };
+                self.blocks.push_block(format!("{}{}", text, underline));
+            },
+            Token::Code(_) => {
+                let code = indent(content.trim_end_matches('\n'), &" ".repeat(self.options.code_indent));
+                self.blocks.push_block(code);
+            },
+            Token::DisplayMath(m) => self.blocks.push_block(indent(&m.src(), &" ".repeat(self.options.code_indent))),
+            Token::TableOfContents(_) if !self.toc.is_empty() => self.blocks.push_block(self.toc.clone()),
+            Token::ListItem(_) => {
+                let marker = context.item_number().map_or("-".to_string(), |n| format!("{}.", n));
+                let item = format!("{} {}", marker, content.trim());
+                let item = match self.blocks.width() {
+                    Some(width) => wrap_hanging(&item, width, &" ".repeat(marker.len() + 1)),
+                    None => item
+                };
+                self.blocks.push_block(item);
+            },
+            Token::DefinitionTerm(_) => self.blocks.push_block(content.trim().to_string()),
+            Token::TableCell(_) => self.blocks.push_block(content.trim().to_string()),
+            Token::TableRow(_) => self.blocks.push_row(),
+            Token::Table(t) => {
+                let rows = self.blocks.take_rows();
+                let header = context.children().any(|r| matches!(r, Token::TableRow(r) if r.header));
+                self.blocks.push_block(text_table(&rows, &t.alignments, header));
+            },
+            Token::List(_) | Token::OrderedList(_) | Token::DefinitionList(_) => {
+                let frame_indent = self.blocks.container_indent();
+                let items = self.blocks.pop_frame().join("\n");
+                self.blocks.push_block(indent(&items, &" ".repeat(frame_indent)));
+            },
+            Token::Definition(_) => {
+                let definition = self.blocks.pop_frame().join("\n");
+                self.blocks.push_block(indent(&definition, &" ".repeat(DEFINITION_INDENT)));
+            },
+            Token::Admonition(a) => {
+                let inner = self.blocks.pop_frame().join("\n\n");
+                let title = format!("{}:", a.display_title());
+                if inner.is_empty() {
+                    self.blocks.push_block(title);
+                } else {
+                    self.blocks.push_block(format!("{}\n{}", title, indent(&inner, &" ".repeat(ADMONITION_INDENT))));
+                }
+            },
+            Token::Directive(_) => {
+                let inner = self.blocks.pop_frame().join("\n\n");
+                if !inner.is_empty() { self.blocks.push_block(inner) }
+            },
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn toc_to_text(entries: &[TocEntry], list_indent: usize) -> String {
+    entries.iter()
+        .map(|e| {
+            let children = toc_to_text(&e.children, list_indent);
+            if children.is_empty() { return format!("- {}", e.text) }
+            format!("- {}\n{}", e.text, indent(&children, &" ".repeat(list_indent)))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    #[test]
+    fn t_text() {
+        let md_syntax = parse_md_str(
+            "# Title\nSome *styled* **text** and [a link](https://gnu.org).\n\
+            - one\n- `two`\n\n```rust\nfn main() {}\n```\nTerm\n: Definition\n"
+        );
+        assert_eq!(
+            compile_all(md_syntax),
+            "Title\n=====\n\nSome styled text and a link [https://gnu.org].\n\n- one\n- two\n\n\
+                \x20   fn main() {}\n\nTerm\n    Definition\n"
+        );
+        assert_eq!(compile_all(parse_md_str("## 日本\n")), "日本\n----\n");
+    }
+
+    #[test]
+    fn t_footnotes_and_wrap() {
+        let md_syntax = parse_md_str(
+            "See [one](/a) and [two](/b) then [one again](/a) for more details.\n- a list item that wraps\n"
+        );
+        let options = TextOptions{links: LinkStyle::Footnotes, wrap: Some(20), ..Default::default()};
+        assert_eq!(
+            compile_all_with(&md_syntax, &options),
+            "See one[1] and\ntwo[2] then one\nagain[1] for more\ndetails.\n\n- a list item that\n  wraps\n\n[1] /a\n[2] /b\n"
+        );
+    }
+
+    #[test]
+    fn t_numbered_items() {
+        // Nested lists don't count as items, numbered items wrap under their text.
+        let md_syntax = parse_md_str("9. nine\n   - sub\n10. ten items that wrap\n");
+        let options = TextOptions{wrap: Some(16), ..Default::default()};
+        assert_eq!(
+            compile_all_with(&md_syntax, &options),
+            "9. nine\n  - sub\n10. ten items\n    that wrap\n"
+        );
+    }
+
+    #[test]
+    fn t_table() {
+        let md_syntax = parse_md_str("| Item | Price |\n|:--|--:|\n| *tea* | 3 |\n| coffee |\n");
+        assert_eq!(
+            compile_all(md_syntax),
+            "Item    Price\n------  -----\ntea         3\ncoffee\n"
+        );
+    }
+}
@@ -0,0 +1,318 @@
+use crate::slug::{header_slugs, text_content};
+use crate::tokens::*;
+use crate::tree::TreeIndex;
+use super::directives::DirectiveRegistry;
+use super::typst_math::tex_to_typst;
+use super::{render, NodeContext, Renderer, Visit};
+
+/*
+ * Typst target. Headers become headings labeled with their slugs, emphasis `_` and `*`, lists `-`
+ * items, definition lists term lists, tables `#table` calls and code raw blocks with their
+ * language. Plain text is escaped, code is copied as is. Math is converted from TeX, formulas
+ * using commands without a Typst equivalent are kept as raw TeX after a comment naming the
+ * command.
+ */
+
+pub type TypstTags = (String, String);
+
+/**
+ Template applied to the document with a show rule, eg. `#show: report.with(title: "...")`.
+*/
+pub struct TypstTemplate {
+    // File the template function is imported from.
+    pub path: String,
+    pub function: String
+}
+
+#[derive(Default)]
+pub struct TypstOptions {
+    // Typst source placed before the document, eg. `#set` rules.
+    pub preamble: Option<String>,
+    pub template: Option<TypstTemplate>,
+    // Passed to the template, or set as the document title without one.
+    pub title: Option<String>,
+    // Handlers return the code around the directive's content, which is otherwise output as is.
+    pub directives: DirectiveRegistry<TypstTags>
+}
+
+pub fn compile_all(input: MdSyntaxTree) -> String {
+    return compile_all_with(&input, &TypstOptions::default());
+}
+
+pub fn compile_all_with(input: &MdSyntaxTree, options: &TypstOptions) -> String {
+    let mut renderer = TypstRenderer::new(options);
+    render(input, &mut renderer);
+    return renderer.finish();
+}
+
+/**
+ Renderer for the Typst target. Markup of a token is written when it is entered and closed once
+ its children are done.
+*/
+pub struct TypstRenderer<'a> {
+    options: &'a TypstOptions,
+    slugs: std::vec::IntoIter<String>,
+    closing: Vec<String>,
+    out: String
+}
+
+impl<'a> TypstRenderer<'a> {
+    pub fn new(options: &'a TypstOptions) -> Self {
+        TypstRenderer{options, slugs: Vec::new().into_iter(), closing: Vec::new(), out: String::new()}
+    }
+
+    pub fn finish(self) -> String {
+        return format!("{}{}", preamble(self.options), self.out);
+    }
+
+    fn tags(&mut self, token: &Token, context: &NodeContext) -> TypstTags {
+        let tags = |open: &str, close: &str| (open.to_string(), close.to_string());
+        let in_code = context.inside(|t| matches!(t, Token::Code(_) | Token::InlineCode(_)));
+        match token {
+            Token::PlainText(t) if in_code => (t.text(), String::new()),
+            // Text starting a line could be read as a heading or a list item.
+            Token::PlainText(t) if context.is_first() && matches!(context.parent(), Some(Token::Paragraph(_) | Token::TableCell(_))) => {
+                (escape_line_start(&escape_typst(&t.text())), String::new())
+            },
+            Token::PlainText(t) => (escape_typst(&t.text()), String::new()),
+            // Raw text has no markup of its own.
+            _ if in_code => tags("", ""),
+            Token::Paragraph(_) if !context.has_children() => tags("\n", ""),
+            Token::Paragraph(_) => tags("", "\n"),
+            Token::Header(h) => {
+                let label = self.slugs.next().map(|s| format!(" <{}>", s)).unwrap_or_default();
+                (format!("{} ", "=".repeat(h.level as usize)), format!("{}\n", label))
+            },
+            Token::Italic(_) => tags("_", "_"),
+            Token::Bold(_) => tags("*", "*"),
+            Token::InlineCode(_) => {
+                let code = text_content(context.line, TreeIndex::Arena(context.raw_idx));
+                let fence = raw_fence(&code, 1);
+                // Raw text starting or ending with a backtick needs space between it and the fence.
+                let pad = if fence.len() > 1 { " " } else { "" };
+                (format!("{}{}", fence, pad), format!("{}{}", pad, fence))
+            },
+            Token::Link(l) if l.url.is_empty() => tags("", ""),
+            Token::Link(l) => {
+                let target = match l.url.strip_prefix('#') {
+                    Some(label) => format!("<{}>", label),
+                    None => format!("\"{}\"", escape_string(&l.url))
+                };
+                if context.has_children() {
+                    (format!("#link({})[", target), "]".to_string())
+                } else {
+                    (format!("#link({})", target), String::new())
+                }
+            },
+            Token::List(_) | Token::OrderedList(_) => tags("", ""),
+            Token::ListItem(_) => {
+                // Items of nested lists are indented past the items of their parent.
+                let nesting = context.ancestors.iter().filter(|t| t.is_list()).count();
+                let marker = context.item_number().map_or("-".to_string(), |n| format!("{}.", n));
+                (format!("{}{} ", "  ".repeat(nesting.saturating_sub(1)), marker), "\n".to_string())
+            },
+            Token::DefinitionList(_) => tags("", ""),
+            Token::DefinitionTerm(_) => tags("/ ", ": "),
+            // Further definitions of a term are indented to continue its item.
+            Token::Definition(_) if matches!(context.previous_sibling(), Some(Token::Definition(_))) => tags("  ", ""),
+            Token::Definition(_) => tags("", ""),
+            Token::Code(c) => {
+                let code = text_content(context.line, TreeIndex::Arena(context.raw_idx));
+                let fence = raw_fence(&code, 3);
+                (format!("{}{}\n", fence, c.lang.clone().unwrap_or_default()), format!("{}\n", fence))
+            },
+            Token::InlineMath(m) => (math(&m.src(), false), String::new()),
+            Token::DisplayMath(m) if context.depth == 0 => (format!("{}\n", math(&m.src(), true)), String::new()),
+            Token::DisplayMath(m) => (math(&m.src(), true), String::new()),
+            Token::Admonition(a) => (
+                format!("#block(inset: 8pt, stroke: 0.5pt)[\n*{}*\n\n", escape_typst(&a.display_title())),
+                "]\n".to_string()
+            ),
+            Token::Directive(d) => self.options.directives.render(d).unwrap_or_default(),
+            Token::TableOfContents(_) => tags("#outline()\n", ""),
+            Token::Table(t) => {
+                let mut open = format!("#table(\n  columns: {},\n", t.alignments.len());
+                if t.alignments.iter().any(|a| *a != Alignment::None) {
+                    let alignments: Vec<&str> = t.alignments.iter().map(|a| a.name().unwrap_or("auto")).collect();
+                    open.push_str(&format!("  align: ({}),\n", alignments.join(", ")));
+                }
+                (open, ")\n".to_string())
+            },
+            Token::TableRow(r) if r.header => tags("  table.header(", "),\n"),
+            Token::TableRow(_) => tags("  ", ",\n"),
+            Token::TableCell(_) if context.is_first() => tags("[", "]"),
+            Token::TableCell(_) => tags(", [", "]")
+        }
+    }
+}
+
+impl Renderer for TypstRenderer<'_> {
+    fn start_document(&mut self, doc: &MdSyntaxTree) {
+        self.slugs = header_slugs(doc).into_iter();
+    }
+
+    fn enter(&mut self, token: &Token, context: &NodeContext) -> Visit {
+        let (open, close) = self.tags(token, context);
+        self.out.push_str(&open);
+        self.closing.push(close);
+        return Visit::Children;
+    }
+
+    fn exit(&mut self, token: &Token, _: &NodeContext) {
+        // The closing fence of a raw block has to be on a line of its own.
+        if matches!(token, Token::Code(_)) && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        let close = self.closing.pop().unwrap_or_default();
+        self.out.push_str(&close);
+    }
+}
+
+// Typst math for a TeX formula, or the TeX as raw text when it can't be converted.
+fn math(tex: &str, display: bool) -> String {
+    match tex_to_typst(tex) {
+        Ok(math) if display => return format!("$ {} $", math),
+        Ok(math) => return format!("${}$", math),
+        Err(command) => {
+            let warning = format!("/* TeX math not converted, {} is not supported */", command.replace("*/", "* /"));
+            if display {
+                let fence = raw_fence(tex, 3);
+                return format!("{}\n{}latex\n{}\n{}", warning, fence, tex, fence);
+            }
+            let fence = raw_fence(tex, 1);
+            let pad = if fence.len() > 1 { " " } else { "" };
+            return format!("{} {}{}{}{}{}", warning, fence, pad, tex, pad, fence);
+        }
+    }
+}
+
+fn preamble(options: &TypstOptions) -> String {
+    let mut preamble = String::new();
+    if let Some(template) = &options.template {
+        preamble.push_str(&format!("#import \"{}\": {}\n", escape_string(&template.path), template.function));
+    }
+    if let Some(text) = &options.preamble {
+        preamble.push_str(text);
+        if !text.ends_with('\n') { preamble.push('\n') }
+    }
+    let title = options.title.as_ref().map(|t| format!("\"{}\"", escape_string(t)));
+    match (&options.template, &title) {
+        (Some(template), Some(title)) => {
+            preamble.push_str(&format!("#show: {}.with(title: {})\n", template.function, title));
+        },
+        (Some(template), None) => preamble.push_str(&format!("#show: {}\n", template.function)),
+        (None, Some(title)) => preamble.push_str(&format!("#set document(title: {})\n", title)),
+        (None, None) => {}
+    }
+    if !preamble.is_empty() { preamble.push('\n') }
+    return preamble;
+}
+
+/**
+ Escape the characters with a special meaning in Typst markup.
+*/
+pub fn escape_typst(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let special = match c {
+            '\\' | '*' | '_' | '`' | '$' | '#' | '@' | '<' | '>' | '[' | ']' | '~' => true,
+            // Start of a comment.
+            '/' => matches!(chars.peek(), Some('/') | Some('*')),
+            _ => false
+        };
+        if special { escaped.push('\\') }
+        escaped.push(c);
+    }
+    return escaped;
+}
+
+// Escape markers that only have a meaning at the start of a line.
+fn escape_line_start(text: &str) -> String {
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 && text[digits..].starts_with('.') {
+        return format!("{}\\{}", &text[..digits], &text[digits..]);
+    }
+    if text.starts_with(['=', '-', '+', '/']) {
+        return format!("\\{}", text);
+    }
+    return text.to_string();
+}
+
+// Contents of a Typst string literal.
+fn escape_string(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Backticks around raw text, longer than any run of backticks inside it.
+fn raw_fence(code: &str, min: usize) -> String {
+    let longest = code.split(|c| c != '`').map(|run| run.len()).max().unwrap_or(0);
+    // Two backticks would be an empty raw text.
+    let len = if longest == 0 { min } else { (longest + 1).max(3) };
+    return "`".repeat(len.max(min));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    #[test]
+    fn t_typst() {
+        let md_syntax = parse_md_str(
+            "# Results & notes\nSome *new* **bold** #1 [link](https://gnu.org) and [back](#results--notes)\n\
+            - one\n- `a_b`\n\n```rust\nlet x = 1;\n```\nTerm\n: First\n: Second\n"
+        );
+        assert_eq!(
+            compile_all(md_syntax),
+            "= Results & notes <results--notes>\n\
+                Some _new_ *bold* \\#1 #link(\"https://gnu.org\")[link] and #link(<results--notes>)[back]\n\
+                - one\n- `a_b`\n\n\
+                ```rust\nlet x = 1;\n```\n\n\
+                / Term: First\n  Second\n"
+        );
+    }
+
+    #[test]
+    fn t_escape() {
+        assert_eq!(escape_typst("a_b *c* <d> // e"), "a\\_b \\*c\\* \\<d\\> \\// e");
+        assert_eq!(escape_line_start("- no list"), "\\- no list");
+        assert_eq!(escape_line_start("2. no list"), "2\\. no list");
+        assert_eq!(raw_fence("a`b", 1), "```");
+    }
+
+    #[test]
+    fn t_table() {
+        let md_syntax = parse_md_str("| Name | Cost |\n|:-:|--|\n| -a | *5* |\n| b |\n");
+        assert_eq!(
+            compile_all(md_syntax),
+            "#table(\n  columns: 2,\n  align: (center, auto),\n  table.header([Name], [Cost]),\n\
+                \x20 [\\-a], [_5_],\n  [b], [],\n)\n"
+        );
+    }
+
+    #[test]
+    fn t_math() {
+        let md_syntax = parse_md_str("Area $\\pi r^2$ and $\\foo{x}$\n\n$$\n\\frac{a}{b}\n$$\n");
+        assert_eq!(
+            compile_all(md_syntax),
+            "Area $pi r^2$ and /* TeX math not converted, \\foo is not supported */ `\\foo{x}`\n\n$ a/b $\n"
+        );
+    }
+
+    #[test]
+    fn t_template() {
+        let md_syntax = parse_md_str("Body\n");
+        let options = TypstOptions{
+            template: Some(TypstTemplate{path: "report.typ".to_string(), function: "report".to_string()}),
+            preamble: Some("#set text(lang: \"fi\")".to_string()),
+            title: Some("Q3 \"draft\"".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            compile_all_with(&md_syntax, &options),
+            "#import \"report.typ\": report\n#set text(lang: \"fi\")\n\
+                #show: report.with(title: \"Q3 \\\"draft\\\"\")\n\nBody\n"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+/*
+ * Converts a practical subset of TeX math into Typst math.
+ *
+ * Supported: numbers, identifiers, operators, groups, sub/superscripts, primes, `\frac`, `\sqrt`,
+ * Greek letters, common operators and relations, named functions, `\text` and font commands,
+ * `\left`/`\right` fences, spacing commands and the matrix, `cases` and `aligned` environments.
+ * Anything else is an error naming the command, so that the caller can keep the TeX as is.
+ */
+
+use super::tex::{greek, is_function, space, symbol, TexScanner};
+
+struct TexParser {
+    tex: TexScanner
+}
+
+/**
+ Typst math for TeX, or the first command or environment that has no Typst equivalent.
+*/
+pub fn tex_to_typst(tex: &str) -> Result<String, String> {
+    let mut parser = TexParser{tex: TexScanner::new(tex)};
+    let mut rows = Vec::new();
+    loop {
+        rows.push(parser.parse_row()?);
+        match parser.tex.peek() {
+            None => break,
+            // Line break, alignment point or stray group closer on the top level.
+            Some('\\') => { parser.tex.read_command(); rows.push("\\".to_string()) },
+            Some('&') => { parser.tex.pos += 1; rows.push("&".to_string()) },
+            Some(_) => parser.tex.pos += 1
+        }
+    }
+    return Ok(join(rows));
+}
+
+// Atoms separated by spaces, so that adjacent letters aren't read as one identifier.
+fn join(atoms: Vec<String>) -> String {
+    atoms.into_iter().filter(|a| !a.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+// Arguments of scripts and fractions need parentheses unless they are a single atom.
+fn group(atom: String) -> String {
+    let simple = atom.chars().all(char::is_alphanumeric) && !atom.is_empty()
+        && (atom.chars().count() == 1 || atom.chars().all(|c| c.is_ascii_digit()));
+    if simple { return atom }
+    return format!("({})", atom);
+}
+
+// Text in a Typst string literal.
+fn string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Commas and semicolons inside function arguments would separate the arguments.
+fn argument(atom: &str) -> String {
+    let mut escaped = String::with_capacity(atom.len());
+    let mut in_string = false;
+    let mut previous = ' ';
+    for c in atom.chars() {
+        if c == '"' && previous != '\\' { in_string = !in_string }
+        if matches!(c, ',' | ';') && !in_string { escaped.push('\\') }
+        escaped.push(c);
+        previous = c;
+    }
+    return escaped;
+}
+
+fn font(name: &str) -> Option<&'static str> {
+    let function = match name {
+        "mathbf" => "bold", "mathit" => "italic", "mathbb" => "bb", "mathcal" => "cal",
+        "mathfrak" => "frak", "mathsf" => "sans", "mathtt" => "mono",
+        _ => return None
+    };
+    return Some(function);
+}
+
+fn matrix_delimiter(env: &str) -> Option<&'static str> {
+    let delimiter = match env {
+        "matrix" | "smallmatrix" => "#none",
+        "pmatrix" => "\"(\"",
+        "bmatrix" => "\"[\"",
+        "Bmatrix" => "\"{\"",
+        "vmatrix" => "\"|\"",
+        "Vmatrix" => "\"||\"",
+        _ => return None
+    };
+    return Some(delimiter);
+}
+
+impl TexParser {
+
+    fn parse_row(&mut self) -> Result<String, String> {
+        let mut atoms: Vec<String> = Vec::new();
+        loop {
+            self.tex.skip_whitespace();
+            if self.tex.at_row_end() { break }
+            match self.tex.peek() {
+                Some('^') | Some('_') => {
+                    let base = atoms.pop().unwrap_or_else(|| "\"\"".to_string());
+                    let scripts = self.parse_scripts()?;
+                    atoms.push(format!("{}{}", base, scripts));
+                },
+                // Primes attach to the atom before them.
+                Some('\'') => {
+                    self.tex.pos += 1;
+                    let base = atoms.pop().unwrap_or_else(|| "\"\"".to_string());
+                    atoms.push(format!("{}'", base));
+                },
+                _ => {
+                    let atom = self.parse_atom()?;
+                    atoms.push(atom);
+                }
+            }
+        }
+        return Ok(join(atoms));
+    }
+
+    // Argument of a command or script: a braced group or a single character or command.
+    fn parse_argument(&mut self) -> Result<String, String> {
+        self.tex.skip_whitespace();
+        if self.tex.at_row_end() { return Ok("\"\"".to_string()) }
+        if self.tex.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.tex.pos += 1;
+            return Ok(self.tex.chars[self.tex.pos - 1].to_string());
+        }
+        return self.parse_atom();
+    }
+
+    fn parse_scripts(&mut self) -> Result<String, String> {
+        let mut scripts = String::new();
+        loop {
+            self.tex.skip_whitespace();
+            match self.tex.peek() {
+                Some(c) if c == '_' || c == '^' => {
+                    self.tex.pos += 1;
+                    let script = self.parse_argument()?;
+                    scripts.push(c);
+                    scripts.push_str(&group(script));
+                },
+                _ => break
+            }
+        }
+        return Ok(scripts);
+    }
+
+    fn parse_atom(&mut self) -> Result<String, String> {
+        let c = self.tex.peek().unwrap();
+        if c == '{' {
+            self.tex.pos += 1;
+            let group = self.parse_row()?;
+            if self.tex.peek() == Some('}') { self.tex.pos += 1 }
+            return Ok(group);
+        }
+        if c == '\\' {
+            return self.parse_command();
+        }
+        if let Some(number) = self.tex.read_number() {
+            return Ok(number);
+        }
+        self.tex.pos += 1;
+        let atom = match c {
+            '~' => "space".to_string(),
+            // A slash would be a fraction and the others have a meaning in Typst math.
+            '/' | '"' | '#' | '$' | '@' | '*' => format!("\\{}", c),
+            _ => c.to_string()
+        };
+        return Ok(atom);
+    }
+
+    fn parse_command(&mut self) -> Result<String, String> {
+        let name = self.tex.read_command();
+        let name = name.as_str();
+
+        if let Some(symbol) = greek(name).or_else(|| symbol(name)) { return Ok(symbol.typst.to_string()) }
+        if is_function(name) { return Ok(name.to_string()) }
+        if let Some(space) = space(name) { return Ok(space.typst.to_string()) }
+        if let Some(function) = font(name) {
+            let argument = self.parse_argument()?;
+            return Ok(format!("{}({})", function, argument));
+        }
+
+        match name {
+            "mathrm" => return Ok(format!("upright({})", string(&self.tex.read_raw_group()))),
+            "operatorname" => return Ok(format!("op({})", string(&self.tex.read_raw_group()))),
+            "text" | "textrm" | "mbox" => return Ok(string(&self.tex.read_raw_group())),
+            "frac" | "dfrac" | "tfrac" => {
+                let num = self.parse_argument()?;
+                let den = self.parse_argument()?;
+                return Ok(format!("{}/{}", group(num), group(den)));
+            },
+            "sqrt" => {
+                if let Some(index) = self.tex.read_optional() {
+                    let radicand = self.parse_argument()?;
+                    let index = tex_to_typst(&index)?;
+                    return Ok(format!("root({}, {})", argument(&index), argument(&radicand)));
+                }
+                let radicand = self.parse_argument()?;
+                return Ok(format!("sqrt({})", argument(&radicand)));
+            },
+            // Typst scales matching delimiters on its own.
+            "left" | "right" | "big" | "Big" | "bigg" | "Bigg" => {
+                self.tex.skip_whitespace();
+                if self.tex.peek() == Some('\\') {
+                    let fence = self.tex.read_command();
+                    return symbol(&fence).map(|s| s.typst.to_string()).ok_or(format!("\\{}", fence));
+                }
+                return Ok(match self.tex.peek() {
+                    Some('.') => { self.tex.pos += 1; String::new() },
+                    Some(_) => self.parse_atom()?,
+                    None => String::new()
+                });
+            },
+            "begin" => return self.parse_environment(),
+            _ => return Err(format!("\\{}", name))
+        }
+    }
+
+    fn parse_environment(&mut self) -> Result<String, String> {
+        let env = self.tex.read_raw_group();
+        let delimiter = matrix_delimiter(&env);
+        if delimiter.is_none() && !matches!(env.as_str(), "cases" | "aligned" | "align" | "align*") {
+            return Err(format!("\\begin{{{}}}", env));
+        }
+
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut cells = Vec::new();
+        loop {
+            cells.push(self.parse_row()?);
+            if self.tex.peek() == Some('&') {
+                self.tex.pos += 1;
+                continue;
+            }
+            let ended = self.tex.peek().is_none() || self.tex.rest_starts_with("\\end");
+            if self.tex.rest_starts_with("\\\\") { self.tex.pos += 2 }
+            else if self.tex.peek() == Some('}') { self.tex.pos += 1 }
+            rows.push(std::mem::take(&mut cells));
+            if ended { break }
+        }
+        if self.tex.rest_starts_with("\\end") {
+            self.tex.read_command();
+            self.tex.read_raw_group();
+        }
+        // A line break before `\end` doesn't start another row.
+        if rows.len() > 1 && rows.last().is_some_and(|r| r.iter().all(String::is_empty)) { rows.pop(); }
+
+        let markup = match delimiter {
+            Some(delimiter) => {
+                let rows: Vec<String> = rows.iter()
+                    .map(|r| r.iter().map(|c| argument(c)).collect::<Vec<_>>().join(", "))
+                    .collect();
+                format!("mat(delim: {}, {})", delimiter, rows.join("; "))
+            },
+            None if env == "cases" => {
+                let rows: Vec<String> = rows.iter().map(|r| argument(&r.join(" & "))).collect();
+                format!("cases({})", rows.join(", "))
+            },
+            None => rows.iter().map(|r| r.join(" & ")).collect::<Vec<_>>().join(" \\ ")
+        };
+        return Ok(markup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_atoms() {
+        assert_eq!(tex_to_typst("x").unwrap(), "x");
+        assert_eq!(tex_to_typst("xy+12.5").unwrap(), "x y + 12.5");
+        assert_eq!(tex_to_typst("\\alpha \\le \\infty").unwrap(), "alpha <= infinity");
+        assert_eq!(tex_to_typst("a/b, f'").unwrap(), "a \\/ b , f'");
+    }
+
+    #[test]
+    fn t_scripts_and_fractions() {
+        assert_eq!(tex_to_typst("x^2").unwrap(), "x^2");
+        assert_eq!(tex_to_typst("a_{ij}^n").unwrap(), "a_(i j)^n");
+        assert_eq!(tex_to_typst("x_12").unwrap(), "x_1 2");
+        assert_eq!(tex_to_typst("\\frac{1}{x+1}").unwrap(), "1/(x + 1)");
+        assert_eq!(tex_to_typst("\\sqrt[3]{y} \\cdot \\sqrt 2").unwrap(), "root(3, y) dot.op sqrt(2)");
+        assert_eq!(tex_to_typst("\\sum_{i=0}^n \\text{for all} i").unwrap(), "sum_(i = 0)^n \"for all\" i");
+    }
+
+    #[test]
+    fn t_environments() {
+        assert_eq!(
+            tex_to_typst("\\begin{pmatrix} a & b \\\\ c & d \\end{pmatrix}").unwrap(),
+            "mat(delim: \"(\", a, b; c, d)"
+        );
+        assert_eq!(
+            tex_to_typst("f(x) = \\begin{cases} 1 & x > 0 \\\\ 0 & \\text{else} \\end{cases}").unwrap(),
+            "f ( x ) = cases(1 & x > 0, 0 & \"else\")"
+        );
+        assert_eq!(tex_to_typst("\\left( \\frac{a}{b} \\right.").unwrap(), "( a/b");
+        assert_eq!(tex_to_typst("\\sqrt{a, \\text{b, c}}").unwrap(), "sqrt(a \\, \"b, c\")");
+    }
+
+    #[test]
+    fn t_unsupported() {
+        assert_eq!(tex_to_typst("x + \\foo{y}"), Err("\\foo".to_string()));
+        assert_eq!(tex_to_typst("\\begin{tikzcd} a \\end{tikzcd}"), Err("\\begin{tikzcd}".to_string()));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::compilation_targets::to_markdown::{attributes, block_attributes, delimiter_cell};
+use crate::parser::consume_lines_with_source;
+use crate::tokens::*;
+use crate::tree::TreeIndex;
+
+/*
+ * Lossless concrete syntax tree. Every token keeps the byte range of the source it was parsed
+ * from, so the document prints back exactly as it was read. When tokens are modified only their
+ * own markup is regenerated. Everything else, the trivia between tokens such as whitespace, quote
+ * markers and indentation as well as the markers of unchanged tokens, is copied from the source.
+ */
+
+// Byte range in the source.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize
+}
+
+// Where a token came from: all of its source, the part its children were parsed from and the
+// token as it was parsed.
+struct NodeSource {
+    span: Span,
+    inner: Option<Span>,
+    original: Token
+}
+
+pub struct LosslessDocument {
+    source: String,
+    lines: MdSyntaxTree,
+    // By line and arena index of the token.
+    sources: Vec<Vec<NodeSource>>
+}
+
+/**
+ Parse a document keeping the source of every token, see `LosslessDocument::print`.
+*/
+pub fn parse_lossless(source: &str) -> LosslessDocument {
+    let mut lines = Vec::new();
+    let mut sources = Vec::new();
+    let mut rem = source;
+    while let Ok((next, (line, line_sources))) = consume_lines_with_source(rem) {
+        let nodes = line_sources.iter().enumerate()
+            .map(|(i, (consumed, children))| NodeSource{
+                span: span_of(source, consumed).unwrap_or(Span{start: 0, end: 0}),
+                inner: span_of(source, children),
+                original: line[TreeIndex::Arena(i)].clone()
+            })
+            .collect();
+        lines.push(line);
+        sources.push(nodes);
+        if next.len() == rem.len() { break }
+        rem = next;
+    }
+    return LosslessDocument{source: source.to_string(), lines, sources};
+}
+
+// Position of a slice of the source, None for strings that aren't part of it.
+fn span_of(source: &str, part: &str) -> Option<Span> {
+    let start = (part.as_ptr() as usize).checked_sub(source.as_ptr() as usize)?;
+    if part.is_empty() || start + part.len() > source.len() { return None }
+    return Some(Span{start, end: start + part.len()});
+}
+
+impl LosslessDocument {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn lines(&self) -> &MdSyntaxTree {
+        &self.lines
+    }
+
+    // Source range of a token, by line and arena index.
+    pub fn span(&self, line: usize, index: usize) -> Option<Span> {
+        self.sources.get(line)?.get(index).map(|s| s.span)
+    }
+
+    /**
+     Token to modify in place. Adding or removing tokens isn't supported, the tree has to keep
+     the shape it was parsed with.
+    */
+    pub fn token_mut(&mut self, line: usize, index: usize) -> Option<&mut Token> {
+        self.lines.get_mut(line)?.get_mut(TreeIndex::Arena(index))
+    }
+
+    /**
+     Print the document. Without modifications this is the source byte for byte, otherwise only
+     the spans of modified tokens differ.
+    */
+    pub fn print(&self) -> String {
+        let mut printed = String::with_capacity(self.source.len());
+        let mut cursor = 0;
+        for (line, sources) in self.sources.iter().enumerate() {
+            let Some(root) = sources.first() else { continue };
+            printed.push_str(&self.source[cursor..root.span.start.max(cursor)]);
+            printed.push_str(&self.print_node(line, 0));
+            cursor = cursor.max(root.span.end);
+        }
+        printed.push_str(&self.source[cursor..]);
+        return printed;
+    }
+
+    fn changed(&self, line: usize, index: usize) -> bool {
+        let Some(node) = self.lines[line].get_node(TreeIndex::Arena(index)) else { return false };
+        return node.val != self.sources[line][index].original
+            || node.children.iter().any(|c| self.changed(line, *c));
+    }
+
+    fn print_node(&self, line: usize, index: usize) -> String {
+        let source = &self.sources[line][index];
+        let span = source.span;
+        if !self.changed(line, index) {
+            return self.source[span.start..span.end].to_string();
+        }
+        let node = self.lines[line].get_node(TreeIndex::Arena(index)).unwrap();
+        let inner = source.inner.filter(|i| i.start >= span.start && i.end <= span.end);
+        let inner_text = inner.map(|i| self.print_children(line, &node.children, i)).unwrap_or_default();
+        let (inner_start, inner_end) = inner.map_or((span.end, span.end), |i| (i.start, i.end));
+
+        let original = &self.source[span.start..span.end];
+        if let Token::List(List{attributes: list_attributes, ..}) | Token::OrderedList(OrderedList{attributes: list_attributes, ..}) = &node.val {
+            // Attributes of a list are on a line of their own after its items.
+            let items = format!("{}{}", &self.source[span.start..inner_start], inner_text);
+            let tail = &self.source[inner_end..span.end];
+            let line_end = if tail.trim().is_empty() { "\n" } else { &tail[tail.trim_end().len()..] };
+            return match attributes(list_attributes) {
+                a if a.is_empty() => items,
+                a if items.ends_with('\n') => format!("{}{}{}", items, a, line_end),
+                a => format!("{}\n{}{}", items, a, line_end.trim_end_matches('\n'))
+            };
+        }
+        if let Token::Table(table) = &node.val {
+            // The delimiter row below the header holds the alignments of the columns.
+            let rows = format!("{}{}{}", &self.source[span.start..inner_start], inner_text, &self.source[inner_end..span.end]);
+            if node.val == source.original { return rows }
+            let mut lines: Vec<&str> = rows.split_inclusive('\n').collect();
+            let cells: Vec<String> = table.alignments.iter().map(|a| format!(" {} ", delimiter_cell(*a, 3))).collect();
+            let delimiter = format!("|{}|\n", cells.join("|"));
+            if lines.len() > 1 { lines[1] = &delimiter }
+            return lines.concat();
+        }
+        let Some((prefix, suffix)) = markup(&node.val, original) else {
+            // Tokens without data of their own keep their markers.
+            return format!(
+                "{}{}{}",
+                &self.source[span.start..inner_start], inner_text, &self.source[inner_end..span.end]
+            );
+        };
+        // Blocks keep the indentation and the line break they start and end with.
+        let head = &self.source[span.start..inner_start];
+        let tail = &self.source[inner.map_or(span.start, |i| i.end)..span.end];
+        let (indent, line_end) = match node.val.is_block() {
+            true => (&head[..head.len() - head.trim_start().len()], &tail[tail.trim_end().len()..]),
+            false => ("", "")
+        };
+        return format!("{}{}{}{}{}", indent, prefix, inner_text, suffix, line_end);
+    }
+
+    // Source of a region with the children in it printed, trivia between them is copied.
+    fn print_children(&self, line: usize, children: &[usize], region: Span) -> String {
+        let mut printed = String::new();
+        let mut cursor = region.start;
+        for child in children {
+            let span = self.sources[line][*child].span;
+            if span.start < cursor || span.end > region.end { continue }
+            printed.push_str(&self.source[cursor..span.start]);
+            printed.push_str(&self.print_node(line, *child));
+            cursor = span.end;
+        }
+        printed.push_str(&self.source[cursor..region.end]);
+        return printed;
+    }
+}
+
+// Markup before and after the children of a token with data, eg. the url of a link. The original
+// source of the token decides between alternative syntaxes.
+fn markup(token: &Token, original: &str) -> Option<(String, String)> {
+    let title = |t: &Option<String>| t.as_ref().map(|t| format!(" {}", t)).unwrap_or_default();
+    let markup = match token {
+        Token::PlainText(t) => (t.text(), String::new()),
+        Token::InlineMath(m) => (format!("${}$", m.src()), String::new()),
+        Token::DisplayMath(m) => (format!("$${}$$", m.src()), String::new()),
+        Token::Link(l) => ("[".to_string(), format!("]({})", l.url)),
+        Token::Header(h) if h.level <= 2 && !original.starts_with('#') => {
+            let underline = if h.level == 1 { "=" } else { "-" };
+            let width = original.trim_end().lines().last().map_or(3, |l| l.trim_end().chars().count());
+            (String::new(), format!("{}\n{}", block_attributes(&h.attributes), underline.repeat(width)))
+        },
+        Token::Header(h) => (format!("{} ", "#".repeat(h.level as usize)), block_attributes(&h.attributes)),
+        Token::Code(c) => {
+            let info = format!("{}{}", c.lang.clone().unwrap_or_default(), block_attributes(&c.attributes));
+            (format!("```{}\n", info.trim_start()), "```".to_string())
+        },
+        Token::Paragraph(p) => (String::new(), block_attributes(&p.attributes)),
+        Token::Admonition(a) if original.starts_with(":::") => {
+            (format!(":::{}{}{}\n", a.kind.name(), title(&a.title), block_attributes(&a.attributes)), ":::".to_string())
+        },
+        Token::Admonition(a) => (
+            format!("> [!{}]{}{}\n", a.kind.name().to_uppercase(), title(&a.title), block_attributes(&a.attributes)),
+            String::new()
+        ),
+        Token::Directive(d) if d.inline => (format!(":{}[", d.name), format!("]{}", attributes(&d.attributes))),
+        Token::Directive(d) => (format!(":::{}{}\n", d.name, attributes(&d.attributes)), ":::".to_string()),
+        _ => return None
+    };
+    return Some(markup);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "Intro  \n==\n\nSee [the docs](http://old.example)  and _this_.\n\
+        *  item   one\n+ two\n\n> [!tip]   Hint\n>   quoted  text\n";
+
+    #[test]
+    fn t_round_trip() {
+        let document = parse_lossless(SOURCE);
+        assert_eq!(document.print(), SOURCE);
+        let link = document.span(2, 2).unwrap();
+        assert_eq!(&SOURCE[link.start..link.end], "[the docs](http://old.example)");
+    }
+
+    #[test]
+    fn t_modify() {
+        let mut document = parse_lossless(SOURCE);
+        if let Some(Token::Link(link)) = document.token_mut(2, 2) {
+            link.url = "https://new.example".to_string();
+        }
+        assert_eq!(document.print(), SOURCE.replace("http://old.example", "https://new.example"));
+
+        if let Some(Token::PlainText(text)) = document.token_mut(2, 7) {
+            text.text = "then".to_string();
+        }
+        if let Some(Token::Header(header)) = document.token_mut(0, 0) {
+            header.level = 3;
+        }
+        assert_eq!(
+            document.print(),
+            SOURCE.replace("http://old.example", "https://new.example")
+                .replace("Intro  \n==\n", "### Intro\n")
+                .replace("_this_", "_then_")
+        );
+    }
+
+    #[test]
+    fn t_modify_attributes() {
+        let source = "  Text  {.lead}\n- a\n{#items}\n> [!NOTE] Title {.wide}\n> c\n";
+        let mut document = parse_lossless(source);
+        assert_eq!(document.print(), source);
+        if let Some(Token::Paragraph(p)) = document.token_mut(0, 0) {
+            p.attributes.insert("id".to_string(), "first".to_string());
+        }
+        if let Some(Token::List(l)) = document.token_mut(1, 0) {
+            l.attributes.insert("class".to_string(), "compact".to_string());
+        }
+        if let Some(Token::Admonition(a)) = document.token_mut(2, 0) {
+            a.attributes.clear();
+        }
+        assert_eq!(
+            document.print(),
+            "  Text {#first .lead}\n- a\n{#items .compact}\n> [!NOTE] Title\n> c\n"
+        );
+    }
+
+    #[test]
+    fn t_modify_table() {
+        let source = "| a |  b |\n|---|:--|\n| *c* | d |\n";
+        let mut document = parse_lossless(source);
+        assert_eq!(document.print(), source);
+        if let Some(Token::PlainText(text)) = document.token_mut(0, 8) {
+            text.text = "e".to_string();
+        }
+        assert_eq!(document.print(), source.replace(" b ", " e "));
+        if let Some(Token::Table(table)) = document.token_mut(0, 0) {
+            table.alignments[0] = Alignment::Right;
+        }
+        assert_eq!(document.print(), "| a |  e |\n| --: | :-- |\n| *c* | d |\n");
+    }
+}
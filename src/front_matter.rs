@@ -0,0 +1,66 @@
+use std::collections::BTreeMap;
+
+/*
+ * YAML style front matter at the start of a document, between two `---` lines. Only flat
+ * `key: value` pairs are read, quotes around values are dropped. Other lines are ignored.
+ */
+
+pub type FrontMatter = BTreeMap<String, String>;
+
+/**
+ Split a document into its front matter and the Markdown after it. Documents without front
+ matter are returned whole with an empty map.
+*/
+pub fn split_front_matter(source: &str) -> (FrontMatter, &str) {
+    let mut front_matter = FrontMatter::new();
+    let Some(rest) = source.strip_prefix("---\n").or_else(|| source.strip_prefix("---\r\n")) else {
+        return (front_matter, source);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let content = line.trim_end();
+        if content == "---" || content == "..." {
+            return (front_matter, &rest[offset..]);
+        }
+        // Comments and nested values.
+        if content.starts_with('#') || content.starts_with(char::is_whitespace) { continue }
+        if let Some((key, value)) = content.split_once(':') {
+            let key = key.trim();
+            if key.is_empty() { continue }
+            front_matter.insert(key.to_string(), unquote(value.trim()).to_string());
+        }
+    }
+    // Never closed, so it was a thematic break rather than front matter.
+    return (FrontMatter::new(), source);
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    return value;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_front_matter() {
+        let (front_matter, rest) = split_front_matter(
+            "---\ntitle: \"Hello: World\"\n# comment\nauthor: Ilmari\n---\n# Body\n"
+        );
+        assert_eq!(front_matter["title"], "Hello: World");
+        assert_eq!(front_matter["author"], "Ilmari");
+        assert_eq!(front_matter.len(), 2);
+        assert_eq!(rest, "# Body\n");
+
+        assert_eq!(split_front_matter("# No front matter").1, "# No front matter");
+        let (front_matter, rest) = split_front_matter("---\ntitle: open\n");
+        assert!(front_matter.is_empty());
+        assert_eq!(rest, "---\ntitle: open\n");
+    }
+}
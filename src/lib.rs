@@ -1,13 +1,19 @@
 #![allow(dead_code)]
 #![allow(non_upper_case_globals)]
+#![allow(clippy::needless_return)]
 
 use wasm_bindgen::prelude::*;
 
 //mod js_bridge;
-mod tokens;
-mod tree;
-mod compilation_targets;
-mod parser;
+pub mod tokens;
+pub mod tree;
+pub mod compilation_targets;
+pub mod parser;
+pub mod slug;
+pub mod toc;
+pub mod front_matter;
+pub mod cst;
+pub mod mdast;
 
 
 #[wasm_bindgen]
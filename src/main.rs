@@ -1,10 +1,7 @@
 use std::env;
 use std::fs;
-mod tokens;
-mod parser;
-mod tree;
-mod compilation_targets;
-use compilation_targets::to_html::*;
+use rustmd::parser;
+use rustmd::compilation_targets::to_html::*;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -0,0 +1,583 @@
+use std::fmt;
+use serde_json::{json, Value};
+
+use crate::cst::LosslessDocument;
+use crate::tokens::*;
+use crate::tree::{Tree, TreeIndex};
+
+/*
+ * JSON export of the syntax tree following the unist/mdast schema, so that remark tools can work
+ * on documents, and the matching import back into tokens.
+ *
+ * Tokens that mdast doesn't have map to the nodes of the common remark extensions: math to
+ * `math` and `inlineMath`, definition lists to `defList`, admonitions and directives to
+ * `containerDirective` and `textDirective`. Tables are GFM `table` nodes. Attributes are kept as
+ * `data.hProperties`, the table of contents is a `toc` node. Consecutive lines of a paragraph form
+ * one paragraph with line breaks in its text, as in mdast. Blank lines aren't nodes of their own.
+ */
+
+#[derive(Debug)]
+pub enum MdastError {
+    Json(serde_json::Error),
+    // Node type without a token, eg. "footnoteDefinition".
+    Unsupported(String),
+    // Node missing a field it needs, eg. a heading without a depth.
+    Invalid(String)
+}
+
+impl fmt::Display for MdastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MdastError::Json(e) => write!(f, "invalid JSON: {}", e),
+            MdastError::Unsupported(kind) => write!(f, "unsupported mdast node: {}", kind),
+            MdastError::Invalid(reason) => write!(f, "invalid mdast: {}", reason)
+        }
+    }
+}
+
+impl std::error::Error for MdastError {}
+
+/**
+ mdast root node of a document.
+*/
+pub fn to_mdast(doc: &MdSyntaxTree) -> Value {
+    return Exporter{lines: doc, source: None}.root();
+}
+
+/**
+ mdast root node of a document with the source position of every node that has one.
+*/
+pub fn to_mdast_with_positions(doc: &LosslessDocument) -> Value {
+    let line_starts = std::iter::once(0)
+        .chain(doc.source().match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    return Exporter{lines: doc.lines(), source: Some((doc, line_starts))}.root();
+}
+
+pub fn to_mdast_json(doc: &MdSyntaxTree) -> String {
+    return serde_json::to_string_pretty(&to_mdast(doc)).unwrap_or_default();
+}
+
+/**
+ Document of an mdast root node, eg. one exported with `to_mdast`.
+*/
+pub fn from_mdast(root: &Value) -> Result<MdSyntaxTree, MdastError> {
+    if node_type(root)? != "root" { return Err(MdastError::Invalid("expected a root node".to_string())) }
+    return lines(children(root)).iter().map(line).collect();
+}
+
+pub fn from_mdast_json(json: &str) -> Result<MdSyntaxTree, MdastError> {
+    let root: Value = serde_json::from_str(json).map_err(MdastError::Json)?;
+    return from_mdast(&root);
+}
+
+// ----------------------------------------------------------------------------
+// EXPORT
+// ----------------------------------------------------------------------------
+
+struct Exporter<'a> {
+    lines: &'a MdSyntaxTree,
+    // Document and the offsets its lines start at, for positions.
+    source: Option<(&'a LosslessDocument, Vec<usize>)>
+}
+
+impl Exporter<'_> {
+    fn root(&self) -> Value {
+        let blocks: Vec<(usize, usize)> = (0..self.lines.len()).map(|line| (line, 0)).collect();
+        let mut root = json!({"type": "root", "children": self.blocks(&blocks)});
+        if let Some((doc, _)) = &self.source {
+            root["position"] = json!({"start": self.point(0), "end": self.point(doc.source().len())});
+        }
+        return root;
+    }
+
+    fn token(&self, line: usize, index: usize) -> &Token {
+        &self.lines[line][TreeIndex::Arena(index)]
+    }
+
+    fn children_of(&self, line: usize, index: usize) -> Vec<(usize, usize)> {
+        self.lines[line].get_node(TreeIndex::Arena(index))
+            .map(|n| n.children.iter().map(|c| (line, *c)).collect())
+            .unwrap_or_default()
+    }
+
+    // Items of a list, a nested list goes into the item before it.
+    fn items(&self, nodes: &[(usize, usize)]) -> Vec<Value> {
+        let mut items: Vec<Value> = Vec::new();
+        for (line, index) in nodes.iter().copied() {
+            let node = self.convert(line, index);
+            match items.last_mut() {
+                Some(item) if self.token(line, index).is_list() => {
+                    item["children"].as_array_mut().unwrap().push(node);
+                },
+                _ => items.push(node)
+            }
+        }
+        return items;
+    }
+
+    // Blocks with the lines of each paragraph joined.
+    fn blocks(&self, nodes: &[(usize, usize)]) -> Vec<Value> {
+        let mut blocks = Vec::new();
+        let mut paragraph: Option<Value> = None;
+        for (line, index) in nodes.iter().copied() {
+            let has_children = !self.children_of(line, index).is_empty();
+            match self.token(line, index) {
+                Token::Paragraph(_) if !has_children => blocks.extend(paragraph.take()),
+                Token::Paragraph(_) => {
+                    let next = self.convert(line, index);
+                    let Some(open) = paragraph.as_mut() else {
+                        paragraph = Some(next);
+                        continue;
+                    };
+                    let inlines = open["children"].as_array_mut().unwrap();
+                    push_inline(inlines, json!({"type": "text", "value": "\n"}));
+                    for inline in next["children"].as_array().cloned().unwrap_or_default() {
+                        push_inline(inlines, inline);
+                    }
+                    if let Some(end) = next.pointer("/position/end") {
+                        open["position"]["end"] = end.clone();
+                    }
+                    // Any line can give attributes of the paragraph.
+                    for (key, value) in next.pointer("/data/hProperties").and_then(|p| p.as_object()).into_iter().flatten() {
+                        open["data"]["hProperties"][key] = value.clone();
+                    }
+                },
+                _ => {
+                    blocks.extend(paragraph.take());
+                    blocks.push(self.convert(line, index));
+                }
+            }
+        }
+        blocks.extend(paragraph);
+        return blocks;
+    }
+
+    fn inlines(&self, nodes: &[(usize, usize)]) -> Vec<Value> {
+        let mut inlines = Vec::new();
+        for (line, index) in nodes {
+            push_inline(&mut inlines, self.convert(*line, *index));
+        }
+        return inlines;
+    }
+
+    fn text(&self, line: usize, index: usize) -> String {
+        crate::slug::text_content(&self.lines[line], TreeIndex::Arena(index))
+    }
+
+    fn convert(&self, line: usize, index: usize) -> Value {
+        let children = self.children_of(line, index);
+        let mut node = match self.token(line, index) {
+            Token::Paragraph(p) => with_attributes(
+                json!({"type": "paragraph", "children": self.inlines(&children)}),
+                &p.attributes
+            ),
+            Token::Header(h) => with_attributes(
+                json!({"type": "heading", "depth": h.level, "children": self.inlines(&children)}),
+                &h.attributes
+            ),
+            Token::PlainText(t) => json!({"type": "text", "value": t.text}),
+            Token::Italic(_) => json!({"type": "emphasis", "children": self.inlines(&children)}),
+            Token::Bold(_) => json!({"type": "strong", "children": self.inlines(&children)}),
+            Token::InlineCode(_) => json!({"type": "inlineCode", "value": self.text(line, index)}),
+            Token::Link(l) => json!({"type": "link", "url": l.url, "title": null, "children": self.inlines(&children)}),
+            Token::List(l) => with_attributes(
+                json!({"type": "list", "ordered": false, "spread": false, "children": self.items(&children)}),
+                &l.attributes
+            ),
+            Token::OrderedList(l) => with_attributes(
+                json!({"type": "list", "ordered": true, "start": l.start, "spread": false, "children": self.items(&children)}),
+                &l.attributes
+            ),
+            // Items hold blocks in mdast, the text of an item is its paragraph.
+            Token::ListItem(_) => json!({
+                "type": "listItem", "spread": false,
+                "children": [{"type": "paragraph", "children": self.inlines(&children)}]
+            }),
+            Token::Code(c) => {
+                let code = self.text(line, index);
+                with_attributes(
+                    json!({"type": "code", "lang": c.lang, "meta": null, "value": code.strip_suffix('\n').unwrap_or(&code)}),
+                    &c.attributes
+                )
+            },
+            Token::DefinitionList(_) => json!({
+                "type": "defList",
+                "children": children.iter().map(|(l, i)| self.convert(*l, *i)).collect::<Vec<_>>()
+            }),
+            Token::DefinitionTerm(_) => json!({"type": "defListTerm", "children": self.inlines(&children)}),
+            Token::Definition(_) => json!({"type": "defListDescription", "children": self.blocks(&children)}),
+            Token::InlineMath(m) => json!({"type": "inlineMath", "value": m.tex}),
+            Token::DisplayMath(m) if self.lines[line].get_node(TreeIndex::Arena(index)).is_some_and(|n| n.level > 0) => {
+                json!({"type": "inlineMath", "value": m.tex, "data": {"display": true}})
+            },
+            Token::DisplayMath(m) => json!({"type": "math", "meta": null, "value": m.tex}),
+            Token::Admonition(a) => {
+                // The title is the label of the directive, a paragraph marked in its data.
+                let mut blocks: Vec<Value> = a.title.iter()
+                    .map(|t| json!({"type": "paragraph", "data": {"directiveLabel": true}, "children": [{"type": "text", "value": t}]}))
+                    .collect();
+                blocks.extend(self.blocks(&children));
+                json!({"type": "containerDirective", "name": a.kind.name(), "attributes": a.attributes, "children": blocks})
+            },
+            Token::Directive(d) => json!({
+                "type": if d.inline { "textDirective" } else { "containerDirective" },
+                "name": d.name,
+                "attributes": d.attributes,
+                "children": if d.inline { self.inlines(&children) } else { self.blocks(&children) }
+            }),
+            Token::TableOfContents(_) => json!({"type": "toc"}),
+            // The first row of an mdast table is its header.
+            Token::Table(t) => json!({
+                "type": "table",
+                "align": t.alignments.iter().map(|a| a.name()).collect::<Vec<_>>(),
+                "children": children.iter().map(|(l, i)| self.convert(*l, *i)).collect::<Vec<_>>()
+            }),
+            Token::TableRow(_) => json!({
+                "type": "tableRow",
+                "children": children.iter().map(|(l, i)| self.convert(*l, *i)).collect::<Vec<_>>()
+            }),
+            Token::TableCell(_) => json!({"type": "tableCell", "children": self.inlines(&children)})
+        };
+        if let Some(position) = self.position(line, index) {
+            node["position"] = position;
+        }
+        return node;
+    }
+
+    fn position(&self, line: usize, index: usize) -> Option<Value> {
+        let (doc, _) = self.source.as_ref()?;
+        let span = doc.span(line, index)?;
+        // Blocks end before the line break they consumed.
+        let end = span.start + doc.source()[span.start..span.end].trim_end_matches(['\n', '\r']).len();
+        return Some(json!({"start": self.point(span.start), "end": self.point(end)}));
+    }
+
+    // unist point of a byte offset, lines and columns count from 1.
+    fn point(&self, offset: usize) -> Value {
+        let Some((doc, line_starts)) = &self.source else { return Value::Null };
+        let line = line_starts.partition_point(|start| *start <= offset);
+        let line_start = line_starts[line - 1];
+        let column = doc.source()[line_start..offset].chars().count() + 1;
+        return json!({"line": line, "column": column, "offset": offset});
+    }
+}
+
+// Add an inline node, joining adjacent text like mdast does.
+fn push_inline(inlines: &mut Vec<Value>, inline: Value) {
+    if let Some(last) = inlines.last_mut() {
+        if last["type"] == "text" && inline["type"] == "text" {
+            let joined = format!("{}{}", last["value"].as_str().unwrap_or(""), inline["value"].as_str().unwrap_or(""));
+            last["value"] = Value::String(joined);
+            if let Some(end) = inline.pointer("/position/end") { last["position"]["end"] = end.clone() }
+            return;
+        }
+    }
+    inlines.push(inline);
+}
+
+fn with_attributes(mut node: Value, attributes: &Attributes) -> Value {
+    if !attributes.is_empty() {
+        node["data"] = json!({"hProperties": attributes});
+    }
+    return node;
+}
+
+// ----------------------------------------------------------------------------
+// IMPORT
+// ----------------------------------------------------------------------------
+
+fn node_type(node: &Value) -> Result<&str, MdastError> {
+    node["type"].as_str().ok_or_else(|| MdastError::Invalid(format!("node without a type: {}", node)))
+}
+
+fn children(node: &Value) -> &[Value] {
+    node["children"].as_array().map(|c| c.as_slice()).unwrap_or(&[])
+}
+
+fn string(node: &Value, field: &str) -> Result<String, MdastError> {
+    node[field].as_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| MdastError::Invalid(format!("{} node without a {}", node["type"], field)))
+}
+
+fn text_node(text: &str) -> Value {
+    json!({"type": "text", "value": text})
+}
+
+fn text_of(node: &Value) -> String {
+    match node["value"].as_str() {
+        Some(value) => value.to_string(),
+        None => children(node).iter().map(text_of).collect()
+    }
+}
+
+// Attributes from `data.hProperties` or the attributes of a directive.
+fn attributes_of(node: &Value) -> Attributes {
+    let properties = match node["attributes"].as_object() {
+        Some(attributes) => Some(attributes),
+        None => node.pointer("/data/hProperties").and_then(|p| p.as_object())
+    };
+    let mut attributes = Attributes::new();
+    for (key, value) in properties.into_iter().flatten() {
+        let key = if key == "className" { "class" } else { key.as_str() };
+        let value = match value {
+            Value::String(s) => s.clone(),
+            // hast class names are a list.
+            Value::Array(items) => items.iter().filter_map(|i| i.as_str()).collect::<Vec<_>>().join(" "),
+            Value::Null => continue,
+            other => other.to_string()
+        };
+        attributes.insert(key.to_string(), value);
+    }
+    return attributes;
+}
+
+// Items of a list with the lists nested in them following each item.
+fn list_children(list: &Value) -> Vec<Value> {
+    let mut nodes = Vec::new();
+    for item in children(list) {
+        let (lists, blocks): (Vec<Value>, Vec<Value>) = children(item).iter().cloned().partition(|b| b["type"] == "list");
+        let mut item = item.clone();
+        item["children"] = json!(blocks);
+        nodes.push(item);
+        nodes.extend(lists);
+    }
+    return nodes;
+}
+
+// Blocks as lines: a paragraph per line of text, and blank lines between paragraphs that would
+// otherwise continue each other.
+fn lines(blocks: &[Value]) -> Vec<Value> {
+    let mut lines = Vec::new();
+    let mut after_paragraph = false;
+    for block in blocks {
+        if block["type"] != "paragraph" {
+            lines.push(block.clone());
+            after_paragraph = false;
+            continue;
+        }
+        if after_paragraph { lines.push(json!({"type": "paragraph", "children": []})) }
+        let mut line: Vec<Value> = Vec::new();
+        for inline in children(block) {
+            let Some(text) = inline["value"].as_str().filter(|_| inline["type"] == "text") else {
+                line.push(inline.clone());
+                continue;
+            };
+            for (i, part) in text.split('\n').enumerate() {
+                if i > 0 { lines.push(json!({"type": "paragraph", "children": std::mem::take(&mut line)})) }
+                if !part.is_empty() { line.push(text_node(part)) }
+            }
+        }
+        // Attributes of the paragraph go on its last line.
+        let mut last = json!({"type": "paragraph", "children": line});
+        if let Some(data) = block.get("data") { last["data"] = data.clone() }
+        lines.push(last);
+        after_paragraph = true;
+    }
+    return lines;
+}
+
+fn line(node: &Value) -> Result<MdLine, MdastError> {
+    let (token, children) = token(node)?;
+    let mut tree = Tree::new(token);
+    for child in &children {
+        add(&mut tree, 0, child)?;
+    }
+    return Ok(tree);
+}
+
+fn add(tree: &mut MdLine, parent: usize, node: &Value) -> Result<(), MdastError> {
+    let (token, children) = token(node)?;
+    let position = tree.add_node_by_index(TreeIndex::Arena(parent), token)
+        .ok_or_else(|| MdastError::Invalid("missing parent node".to_string()))?;
+    for child in &children {
+        add(tree, position.raw_idx, child)?;
+    }
+    return Ok(());
+}
+
+// Token of a node and the nodes of its children.
+fn token(node: &Value) -> Result<(Token, Vec<Value>), MdastError> {
+    let inline_children = children(node).to_vec();
+    let token = match node_type(node)? {
+        "paragraph" => (Token::Paragraph(Paragraph{attributes: attributes_of(node)}), inline_children),
+        "heading" => {
+            let level = node["depth"].as_u64().ok_or_else(|| MdastError::Invalid("heading without a depth".to_string()))?;
+            if !(1..=6).contains(&level) { return Err(MdastError::Invalid(format!("heading depth {} out of range", level))) }
+            (Token::Header(Header{level: level as u32, attributes: attributes_of(node)}), inline_children)
+        },
+        "text" => (Token::PlainText(PlainText{text: string(node, "value")?}), Vec::new()),
+        "emphasis" => (Token::Italic(Italic{}), inline_children),
+        "strong" => (Token::Bold(Bold{}), inline_children),
+        "inlineCode" => (Token::InlineCode(InlineCode{}), vec![text_node(&string(node, "value")?)]),
+        "link" => (Token::Link(Link{url: string(node, "url")?}), inline_children),
+        "list" if node["ordered"] == true => {
+            let start = node["start"].as_u64().unwrap_or(1);
+            let start = u32::try_from(start).map_err(|_| MdastError::Invalid(format!("list start {} out of range", start)))?;
+            (Token::OrderedList(OrderedList{level: 0, start, attributes: attributes_of(node)}), list_children(node))
+        },
+        "list" => (Token::List(List{level: 0, attributes: attributes_of(node)}), list_children(node)),
+        "listItem" => {
+            let mut inlines = Vec::new();
+            for block in children(node) {
+                if block["type"] != "paragraph" { return Err(MdastError::Unsupported(format!("{} in a list item", block["type"]))) }
+                if !inlines.is_empty() { inlines.push(text_node(" ")) }
+                inlines.extend(children(block).iter().cloned());
+            }
+            (Token::ListItem(ListItem{}), inlines)
+        },
+        "code" => {
+            let code = Code{lang: node["lang"].as_str().map(|l| l.to_string()), attributes: attributes_of(node)};
+            (Token::Code(code), vec![text_node(&format!("{}\n", string(node, "value")?))])
+        },
+        "defList" => (Token::DefinitionList(DefinitionList{}), inline_children),
+        "defListTerm" => (Token::DefinitionTerm(DefinitionTerm{}), inline_children),
+        "defListDescription" => (Token::Definition(Definition{}), lines(children(node))),
+        "math" => (Token::DisplayMath(DisplayMath{tex: string(node, "value")?}), Vec::new()),
+        "inlineMath" if node.pointer("/data/display") == Some(&Value::Bool(true)) => {
+            (Token::DisplayMath(DisplayMath{tex: string(node, "value")?}), Vec::new())
+        },
+        "inlineMath" => (Token::InlineMath(InlineMath{tex: string(node, "value")?}), Vec::new()),
+        "containerDirective" => {
+            let name = string(node, "name")?;
+            let (label, blocks): (Vec<Value>, Vec<Value>) = children(node).iter()
+                .cloned()
+                .partition(|c| c.pointer("/data/directiveLabel") == Some(&Value::Bool(true)));
+            match AdmonitionKind::from_name(&name) {
+                Some(kind) => {
+                    let title = label.first().map(text_of);
+                    (Token::Admonition(Admonition{kind, title, attributes: attributes_of(node)}), lines(&blocks))
+                },
+                None => {
+                    let directive = Directive{name, attributes: attributes_of(node), inline: false};
+                    (Token::Directive(directive), lines(&blocks))
+                }
+            }
+        },
+        "textDirective" => {
+            let directive = Directive{name: string(node, "name")?, attributes: attributes_of(node), inline: true};
+            (Token::Directive(directive), inline_children)
+        },
+        "toc" => (Token::TableOfContents(TableOfContents{}), Vec::new()),
+        "table" => {
+            let alignments = node["align"].as_array().map(|a| a.as_slice()).unwrap_or(&[]).iter()
+                .map(|a| match a.as_str() {
+                    Some("left") => Alignment::Left,
+                    Some("center") => Alignment::Center,
+                    Some("right") => Alignment::Right,
+                    _ => Alignment::None
+                })
+                .collect();
+            // Rows don't know their position, the header is marked for them.
+            let mut rows = inline_children;
+            if let Some(header) = rows.first_mut() { header["header"] = Value::Bool(true) }
+            (Token::Table(Table{alignments}), rows)
+        },
+        "tableRow" => (Token::TableRow(TableRow{header: node["header"] == true}), inline_children),
+        "tableCell" => (Token::TableCell(TableCell{}), inline_children),
+        other => return Err(MdastError::Unsupported(other.to_string()))
+    };
+    return Ok(token);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cst::parse_lossless;
+    use crate::parser::parse_md_str;
+
+    const SOURCE: &str = "# Title {#top}\nSome *styled* [link](/a)\nnext line\n\n- one\n- `two`\n\n\
+        ```rust\nfn main() {}\n```\nTerm\n: Definition\n\n:::tip Hint\nText $x$\n:::\n";
+
+    #[test]
+    fn t_export() {
+        let mdast = to_mdast(&parse_md_str("# Title {#top}\nSome *styled*\nnext line\n\n- one\n"));
+        assert_eq!(mdast, json!({"type": "root", "children": [
+            {"type": "heading", "depth": 1, "data": {"hProperties": {"id": "top"}}, "children": [
+                {"type": "text", "value": "Title"}
+            ]},
+            {"type": "paragraph", "children": [
+                {"type": "text", "value": "Some "},
+                {"type": "emphasis", "children": [{"type": "text", "value": "styled"}]},
+                {"type": "text", "value": "\nnext line"}
+            ]},
+            {"type": "list", "ordered": false, "spread": false, "children": [
+                {"type": "listItem", "spread": false, "children": [
+                    {"type": "paragraph", "children": [{"type": "text", "value": "one"}]}
+                ]}
+            ]}
+        ]}));
+    }
+
+    #[test]
+    fn t_round_trip() {
+        let mdast = to_mdast(&parse_md_str(SOURCE));
+        let imported = from_mdast_json(&serde_json::to_string(&mdast).unwrap()).unwrap();
+        assert_eq!(to_mdast(&imported), mdast);
+        assert_eq!(imported[1][TreeIndex::Arena(0)], Token::Paragraph(Paragraph{attributes: Attributes::new()}));
+        assert_eq!(imported[2][TreeIndex::Arena(1)], Token::PlainText(PlainText{text: "next line".to_string()}));
+
+        let error = from_mdast(&json!({"type": "root", "children": [{"type": "footnoteDefinition", "children": []}]}));
+        assert!(matches!(error, Err(MdastError::Unsupported(kind)) if kind == "footnoteDefinition"));
+        let error = from_mdast(&json!({"type": "root", "children": [{"type": "heading", "depth": 7, "children": []}]}));
+        assert!(matches!(error, Err(MdastError::Invalid(_))));
+        let error = from_mdast(&json!({"type": "root", "children": [
+            {"type": "list", "ordered": true, "start": 1u64 << 32, "children": []}
+        ]}));
+        assert!(matches!(error, Err(MdastError::Invalid(_))));
+    }
+
+    #[test]
+    fn t_table() {
+        let md_syntax = parse_md_str("| a | *b* |\n|:--|---|\n| c |\n");
+        let mdast = to_mdast(&md_syntax);
+        let cell = |inline: Value| json!({"type": "tableCell", "children": [inline]});
+        assert_eq!(mdast["children"][0], json!({"type": "table", "align": ["left", null], "children": [
+            {"type": "tableRow", "children": [
+                cell(json!({"type": "text", "value": "a"})),
+                cell(json!({"type": "emphasis", "children": [{"type": "text", "value": "b"}]}))
+            ]},
+            {"type": "tableRow", "children": [
+                cell(json!({"type": "text", "value": "c"})),
+                {"type": "tableCell", "children": []}
+            ]}
+        ]}));
+        let imported = from_mdast(&mdast).unwrap();
+        assert_eq!(imported[0][TreeIndex::Arena(1)], Token::TableRow(TableRow{header: true}));
+        assert_eq!(to_mdast(&imported), mdast);
+    }
+
+    #[test]
+    fn t_attributes() {
+        let mdast = to_mdast(&parse_md_str("Text {.lead}\n- a\n{#items}\n> [!NOTE] {.wide}\n> b\n"));
+        assert_eq!(mdast["children"][0]["data"], json!({"hProperties": {"class": "lead"}}));
+        assert_eq!(mdast["children"][1]["data"], json!({"hProperties": {"id": "items"}}));
+        assert_eq!(mdast["children"][2]["attributes"], json!({"class": "wide"}));
+        let imported = from_mdast(&mdast).unwrap();
+        assert_eq!(to_mdast(&imported), mdast);
+    }
+
+    #[test]
+    fn t_ordered_list() {
+        let mdast = to_mdast(&parse_md_str("2. two\n   - nested\n3. three\n"));
+        assert_eq!(mdast["children"][0]["ordered"], json!(true));
+        assert_eq!(mdast["children"][0]["start"], json!(2));
+        assert_eq!(mdast["children"][0]["children"][0]["children"][1]["type"], json!("list"));
+        let imported = from_mdast(&mdast).unwrap();
+        assert_eq!(imported[0][TreeIndex::Arena(0)], Token::OrderedList(OrderedList{level: 0, start: 2, attributes: Attributes::new()}));
+        assert_eq!(to_mdast(&imported), mdast);
+    }
+
+    #[test]
+    fn t_positions() {
+        let mdast = to_mdast_with_positions(&parse_lossless("# Hi\nSome *em*\n"));
+        assert_eq!(mdast["position"], json!({
+            "start": {"line": 1, "column": 1, "offset": 0}, "end": {"line": 3, "column": 1, "offset": 15}
+        }));
+        assert_eq!(mdast["children"][0]["position"]["end"], json!({"line": 1, "column": 5, "offset": 4}));
+        assert_eq!(mdast["children"][1]["children"][1]["position"], json!({
+            "start": {"line": 2, "column": 6, "offset": 10}, "end": {"line": 2, "column": 10, "offset": 14}
+        }));
+    }
+}
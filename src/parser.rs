@@ -2,23 +2,33 @@ use nom::{
     IResult,
     error::Error,
     Err,
-    sequence::{delimited,preceded,tuple, terminated},
-    character::complete::char,
-    character::{complete::{newline, digit1}, is_space},
+    sequence::{delimited,preceded,tuple, terminated, pair},
+    character::complete::{char, one_of},
+    character::{complete::newline, is_space},
     character::{is_newline, complete::anychar},
     bytes::complete::is_not,
     bytes::complete::tag,
     bytes::complete::take_till,
     bytes::complete::take_until,
-    bytes::complete::{take_while, take_while_m_n},
-    multi::{many1_count, many1, many_till, many0, fold_many0 },
-    error::ErrorKind, Parser, Slice
+    bytes::complete::{take_while, take_while1, take_while_m_n},
+    multi::{many1_count, many_till, many0, fold_many0 },
+    combinator::{rest, opt, map_res},
+    branch::alt,
+    error::ErrorKind
 };
 use std::{collections::VecDeque, vec};
 
 //#[macro_use]
 use crate::{tokens::*, tree::{Tree, TreeIndex}};
 
+pub type TokenParser = fn(&str,usize) -> IResult<&str, (&str,Token)>;
+
+// Children source, the token and all of the source it was parsed from.
+type Parsed<'a> = (&'a str, Token, &'a str);
+
+// Source of each token in a line by arena index: all of its text and the text of its children.
+pub type LineSources<'a> = Vec<(&'a str, &'a str)>;
+
 /**
  Takes a line from a string of one or more lines. Returns Err if input is empty.
 */
@@ -33,9 +43,9 @@ fn take_line(s: &str) -> IResult<&str, &str> {
 }
 
 pub fn parse_md_str(input: &str) -> MdSyntaxTree {
-    println!("Parsing lines...\n");
-    let lines = many1(consume_lines)(input);
-    return lines.unwrap().1;
+    // Empty input, eg. a document with only front matter, has no lines.
+    let lines = many0(consume_lines)(input);
+    return lines.map(|(_, lines)| lines).unwrap_or_default();
 }
 /*
 
@@ -44,47 +54,119 @@ pub fn parse_line(line_str: IResult<&str,(&str,Token)>) -> MdLine {
 */
 
 pub fn consume_lines(input: &str) -> IResult<&str,MdLine> {
+    consume_lines_with_source(input).map(|(rem, (tree, _))| (rem, tree))
+}
+
+/**
+ Same as `consume_lines`, also returning the source of each token in the line by arena index:
+ all of the text the token was parsed from and the part its children were parsed from.
+*/
+pub fn consume_lines_with_source(input: &str) -> IResult<&str,(MdLine, LineSources<'_>)> {
 
     let (rem,(c_str, token)) = parse_line_consuming_token(input)?;
+    let consumed = &input[..input.len() - rem.len()];
+    let mut sources = vec![(consumed, c_str)];
 
     // Fill children in syntax tree by folding over each line consumed by the parent Token.
     let mut tree = Tree::new(token.clone());
-    let mut stack: VecDeque<(usize, &str, Token)> =
-    parse_children( token.child_parsers(), c_str, 1)
-        .iter()
-        .map(|c| (0,c.0,c.1.clone()))
-        .collect();
+    let mut stack: VecDeque<(usize, Parsed)> = VecDeque::new();
+    if is_quoted(&token, consumed) {
+        add_quoted_children(&mut tree, &mut sources, 0, c_str);
+    } else {
+        stack = table_children(token.kind(), None, parse_children( token.child_parsers(), c_str, child_depth(&token, input, c_str, 1)))
+            .into_iter()
+            .map(|c| (0,c))
+            .collect();
+    }
 
 
-    while let Some((p_idx,unconsumed, child)) = stack.pop_front() {
+    while let Some((p_idx,(unconsumed, child, consumed))) = stack.pop_front() {
         let parsers = child.child_parsers();
+        let quoted = is_quoted(&child, consumed);
+        let list_depth = child.is_list().then(|| child_depth(&child, input, unconsumed, 0));
+        let columns = match tree.get(TreeIndex::Arena(p_idx)) {
+            Some(Token::Table(t)) => Some(t.alignments.len()),
+            _ => None
+        };
+        let kind = child.kind();
         let added = tree.add_node_by_index(TreeIndex::Arena(p_idx), child);
         let idx = added.as_ref().unwrap().raw_idx;
         let depth = added.unwrap().depth;
-        let stack_extended: VecDeque<(usize, &str, Token)> =
-        parse_children(parsers, unconsumed, depth + 1)
-            .iter()
-            .map(|c| (idx,c.0,c.1.clone()))
+        sources.push((consumed, unconsumed));
+        if quoted {
+            add_quoted_children(&mut tree, &mut sources, idx, unconsumed);
+            continue;
+        }
+        let stack_extended: VecDeque<(usize, Parsed)> =
+        table_children(kind, columns, parse_children(parsers, unconsumed, list_depth.unwrap_or(depth + 1)))
+            .into_iter()
+            .map(|c| (idx,c))
             .collect();
         stack.extend(stack_extended);
     }
-    print!("{}",tree);
-    return Ok((rem,tree));
+    unescape_cell_pipes(&mut tree);
+    return Ok((rem,(tree, sources)));
+
+}
+
+/**
+ Children of a table as GFM reads them: the first row is the header and the delimiter row below it
+ only gives the alignments. Every row has as many cells as the table has columns, missing cells
+ are empty and the cells past the last column are left out.
+*/
+fn table_children<'a>(kind: TokenKind, columns: Option<usize>, mut children: Vec<Parsed<'a>>) -> Vec<Parsed<'a>> {
+    match (kind, columns) {
+        (TokenKind::Table, _) => {
+            if children.len() > 1 { children.remove(1); }
+            if let Some((_, row, _)) = children.first_mut() { *row = Token::TableRow(TableRow{header: true}) }
+        },
+        (TokenKind::TableRow, Some(columns)) => {
+            children.truncate(columns);
+            children.resize_with(columns, || ("", Token::TableCell(TableCell{}), ""));
+        },
+        _ => {}
+    }
+    return children;
+}
 
+// A pipe escaped to stay in its table cell is text.
+fn unescape_cell_pipes(tree: &mut MdLine) {
+    let mut stack: Vec<usize> = tree.nodes_dfs()
+        .filter(|node| matches!(node.val, Token::TableCell(_)))
+        .map(|node| node.index())
+        .collect();
+    while let Some(index) = stack.pop() {
+        let Some(node) = tree.get_mut_node(TreeIndex::Arena(index)) else { continue };
+        if let Token::PlainText(t) = &mut node.val { t.text = t.text.replace("\\|", "|") }
+        stack.extend(node.children.iter().copied());
+    }
 }
 
+// Depth passed to the child parsers of a token. Nested lists are told apart by their indentation,
+// so lists pass on the column of their items instead.
+fn child_depth(token: &Token, input: &str, children: &str, depth: usize) -> usize {
+    if !token.is_list() { return depth }
+    let indentation = children.len() - children.trim_start_matches(' ').len();
+    // Children of quoted callouts are parsed from a copy of the input, their lines start at column 0.
+    let start = (children.as_ptr() as usize).checked_sub(input.as_ptr() as usize)
+        .filter(|start| *start <= input.len())
+        .unwrap_or(0);
+    let column = start - input[..start].rfind('\n').map_or(0, |i| i + 1);
+    return column + indentation + 1;
+}
 
 fn try_all_parsers(
-    allowed_parsers: Vec<fn(&str,usize) -> IResult<&str, (&str,Token)>>,
+    allowed_parsers: Vec<TokenParser>,
     source: &str,
     depth: usize)
--> IResult<&str,(&str,Token)>{
+-> IResult<&str,Parsed<'_>>{
     if source.is_empty() {
         return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
     }
     for parse in allowed_parsers {
-        let res = parse(source,depth);
-        if res.is_ok() { return res }
+        if let Ok((rem, (children, token))) = parse(source,depth) {
+            return Ok((rem, (children, token, &source[..source.len() - rem.len()])));
+        }
     }
     return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
 }
@@ -94,10 +176,10 @@ fn try_all_parsers(
  Token is found or consume all input and return just PlainText.
 */
 fn take_tokens_with_leading_plaintext(
-    token_parsers: Vec<fn(&str,usize) -> IResult<&str, (&str,Token)>>,
+    token_parsers: Vec<TokenParser>,
     src: &str,
     depth:usize)
--> IResult<&str,Vec<(&str,Token)>>{
+-> IResult<&str,Vec<Parsed<'_>>>{
 
     if src.is_empty(){
             return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}))
@@ -112,10 +194,11 @@ fn take_tokens_with_leading_plaintext(
             Ok((remt, (chars,tk))) => {
 
                 let plain_text: String = chars.into_iter().collect();
+                let text_src = &src[..plain_text.len()];
                 return Ok((
                     remt,
                     vec![
-                        ("",Token::PlainText(PlainText{text: plain_text})), // Empty string since plain text has no children
+                        ("",Token::PlainText(PlainText{text: plain_text}), text_src), // Empty string since plain text has no children
                         tk]
                 ));
             },
@@ -123,7 +206,7 @@ fn take_tokens_with_leading_plaintext(
             Err(_) => Ok((
                 "",
                 vec![
-                    ("",Token::PlainText(PlainText{text: src.to_string()}))] // Empty string since plain text has no children
+                    ("",Token::PlainText(PlainText{text: src.to_string()}), src)] // Empty string since plain text has no children
             ))
         }
     }
@@ -132,10 +215,10 @@ fn take_tokens_with_leading_plaintext(
 }
 
 fn parse_children(
-    allowed_children: Vec<fn(&str, usize) -> IResult<&str, (&str,Token)>>,
+    allowed_children: Vec<TokenParser>,
     src: &str,
     depth: usize)
--> Vec<(&str,Token)> {
+-> Vec<Parsed<'_>> {
 
     let lines = fold_many0(
         |s| take_tokens_with_leading_plaintext(allowed_children.clone(), s, depth),
@@ -156,8 +239,15 @@ fn parse_children(
 fn parse_line_consuming_token(source: &str) -> IResult<&str, (&str,Token)> {
     let line_consuming_tokens = [
         Code::parse_lines,
+        DisplayMath::parse_lines,
+        Admonition::parse_lines,
+        Directive::parse_lines,
         List::parse_lines,
+        OrderedList::parse_lines,
+        Table::parse_lines,
         Header::parse_lines,
+        DefinitionList::parse_lines,
+        TableOfContents::parse_lines,
         Paragraph::parse_lines,
     ];
     for parse in line_consuming_tokens {
@@ -170,6 +260,148 @@ fn parse_line_consuming_token(source: &str) -> IResult<&str, (&str,Token)> {
     return Err(Err::Error(err));
 }
 
+/**
+ Parse a block nested inside another block, eg. the content of a definition.
+ Leading indentation is skipped so that continuation lines parse like top level lines.
+*/
+fn parse_block(source: &str, _: usize) -> IResult<&str, (&str,Token)> {
+    let (unindented, _) = take_while(|c| c == ' ' || c == '\t')(source)?;
+    parse_line_consuming_token(unindented)
+}
+
+// Callouts quoted with `>`, as opposed to fenced ones.
+fn is_quoted(token: &Token, source: &str) -> bool {
+    matches!(token, Token::Admonition(_)) && source.trim_start().starts_with('>')
+}
+
+/**
+ Quoted lines with the `>` markers and a space after them removed. Also returns, for every byte
+ of the result, the offset of the same byte in the quoted lines.
+*/
+fn unquote(quoted: &str) -> (String, Vec<usize>) {
+    let mut content = String::with_capacity(quoted.len());
+    let mut offsets = Vec::with_capacity(quoted.len());
+    let mut start = 0;
+    for line in quoted.split_inclusive('\n') {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let mut marker = 0;
+        if indent <= 3 && line[indent..].starts_with('>') {
+            marker = indent + 1;
+            if line[marker..].starts_with(' ') { marker += 1 }
+        }
+        content.push_str(&line[marker..]);
+        offsets.extend(start + marker..start + line.len());
+        start += line.len();
+    }
+    return (content, offsets);
+}
+
+/**
+ Add the content of a callout quoted with `>` under the token at `parent`. The markers are removed
+ from all of the quoted lines before the content is parsed, so that blocks spanning several lines,
+ eg. lists and fenced code, parse as a whole. Sources of the added tokens point into the quoted
+ lines, including the markers between their lines.
+*/
+fn add_quoted_children<'a>(tree: &mut MdLine, sources: &mut LineSources<'a>, parent: usize, quoted: &'a str) {
+    let (content, offsets) = unquote(quoted);
+    let original = |part: &str| -> &'a str {
+        let Some(start) = (part.as_ptr() as usize).checked_sub(content.as_ptr() as usize) else { return "" };
+        if part.is_empty() || start + part.len() > content.len() { return "" }
+        return &quoted[offsets[start]..offsets[start + part.len() - 1] + 1];
+    };
+    let mut rem = content.as_str();
+    while let Ok((next, (line, line_sources))) = consume_lines_with_source(rem) {
+        // Parent of each token of the line in the tree, parents come before their children.
+        let mut parents = vec![parent; line.size()];
+        for (i, (consumed, children)) in line_sources.iter().enumerate() {
+            let Some(node) = line.get_node(TreeIndex::Arena(i)) else { continue };
+            let added = tree.add_node_by_index(TreeIndex::Arena(parents[i]), node.val.clone()).unwrap();
+            for child in &node.children { parents[*child] = added.raw_idx }
+            sources.push((original(consumed), original(children)));
+        }
+        if next.len() == rem.len() { break }
+        rem = next;
+    }
+}
+
+/**
+ Take a container fenced with `:::`. Returns the text after the opening fence and the content
+ between the fences. Containers may be nested, every opening fence needs a closing `:::` line.
+*/
+fn take_fenced_container(source: &str) -> IResult<&str, (&str, &str)> {
+    let (content_start, info) = preceded(tag(":::"), take_line)(source)?;
+    if info.trim().is_empty() {
+        return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+    }
+    let mut depth = 1;
+    let mut cursor = content_start;
+    while let Ok((next, line)) = take_line(cursor) {
+        if let Some(fence_info) = line.trim_end().strip_prefix(":::") {
+            if fence_info.trim().is_empty() { depth -= 1 } else { depth += 1 }
+            if depth == 0 {
+                let content = &content_start[..content_start.len() - cursor.len()];
+                return Ok((next, (info.trim(), content)));
+            }
+        }
+        cursor = next;
+    }
+    return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+}
+
+// Name of a directive or admonition: letters, digits, dashes and underscores.
+fn take_name(source: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_alphanumeric() || c == '-' || c == '_')(source)
+}
+
+fn attribute_value(source: &str) -> IResult<&str, &str> {
+    alt((
+        delimited(char('"'), take_till(|c| c == '"'), char('"')),
+        take_till(|c: char| c.is_whitespace() || c == '}')
+    ))(source)
+}
+
+/**
+ Parse attributes in curly braces: `{#id .class key=value key="quoted value"}`.
+*/
+pub fn parse_attributes(source: &str) -> IResult<&str, Attributes> {
+    let (mut rem, _) = char('{')(source)?;
+    let mut attributes = Attributes::new();
+    loop {
+        rem = rem.trim_start();
+        if let Ok((after, _)) = char::<&str, Error<&str>>('}')(rem) {
+            return Ok((after, attributes));
+        }
+        if let Ok((after, id)) = preceded(char('#'), take_name)(rem) {
+            attributes.insert("id".to_string(), id.to_string());
+            rem = after;
+        } else if let Ok((after, class)) = preceded(char('.'), take_name)(rem) {
+            let classes = attributes.entry("class".to_string()).or_default();
+            if !classes.is_empty() { classes.push(' ') }
+            classes.push_str(class);
+            rem = after;
+        } else {
+            let (after, (key, value)) = pair(take_name, preceded(char('='), attribute_value))(rem)?;
+            attributes.insert(key.to_string(), value.to_string());
+            rem = after;
+        }
+    }
+}
+
+/**
+ Split attributes in curly braces from the end of a line, eg. `Title {#custom-id}`.
+ The line is returned as is with empty attributes if it doesn't end in valid attributes.
+*/
+fn split_trailing_attributes(line: &str) -> (&str, Attributes) {
+    let trimmed = line.trim_end();
+    if !trimmed.ends_with('}') { return (line, Attributes::new()) }
+    for (i, _) in trimmed.match_indices('{').collect::<Vec<_>>().into_iter().rev() {
+        if let Ok(("", attributes)) = parse_attributes(&trimmed[i..]) {
+            return (trimmed[..i].trim_end(), attributes);
+        }
+    }
+    return (line, Attributes::new());
+}
+
 pub trait LineConsumingParse {
 
     /**
@@ -188,17 +420,41 @@ pub trait Parse {
     fn parse(source: &str, depth: usize) -> IResult<&str,(&str,Token)>;
 }
 
+impl Header {
+
+    // A line of text underlined with `=` for level 1 or `-` for level 2.
+    fn parse_setext(source: &str) -> IResult<&str,(&str,Token)> {
+        let (rem, text) = take_line(source)?;
+        let (rem_l, underline) = take_line(rem)?;
+        let underline = underline.trim_end();
+        let level = match underline.chars().next() {
+            Some('=') => 1,
+            Some('-') => 2,
+            _ => return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}))
+        };
+        let marker = if level == 1 { '=' } else { '-' };
+        if text.trim().is_empty() || text.starts_with(' ') || !underline.chars().all(|c| c == marker) {
+            return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        }
+        let (text, attributes) = split_trailing_attributes(text.trim_end());
+        return Ok((rem_l, (text, Token::Header(Header{level, attributes}))));
+    }
+}
+
 impl LineConsumingParse for Header {
     fn parse_lines(source: &str) -> IResult<&str,(&str,Token)> {
+        if let Ok(setext) = Header::parse_setext(source) { return Ok(setext) }
 
         let (rem_l,consumed) = take_line(source)?;
         let (rem,count) = terminated(many1_count(tag("#")), tag(" "))(consumed)?;
+        let (text, attributes) = split_trailing_attributes(rem);
         return Ok((
             rem_l, // Remaining lines
             (
-                rem, // Possible children
+                text, // Possible children
                 Token::Header(Header{
-                    level: count as u32
+                    level: count as u32,
+                    attributes
                 })
             )
         ))
@@ -208,11 +464,18 @@ impl LineConsumingParse for Header {
 impl LineConsumingParse for Paragraph {
     fn parse_lines(source: &str) -> IResult<&str,(&str,Token)> {
         let (rem_l, consumed) = take_line(source)?;
+
+        // Attributes are separated from the text by whitespace, braces right after the text
+        // belong to it, eg. an inline directive `:abbr[HTML]{title="..."}`.
+        let (text, attributes) = split_trailing_attributes(consumed);
+        if text.is_empty() || !consumed[text.len()..].starts_with(char::is_whitespace) {
+            return Ok((rem_l, (consumed, Token::Paragraph(Paragraph{attributes: Attributes::new()}))));
+        }
         return Ok((
             rem_l, // Remaining lines
             (
-                consumed, // Possible children
-                Token::Paragraph(Paragraph{})
+                text, // Possible children
+                Token::Paragraph(Paragraph{attributes})
             )
         ));
     }
@@ -223,84 +486,510 @@ impl LineConsumingParse for Code {
 
         let (rem_l,consumed) = preceded( tag("```"), take_until("```"))(source)?;
 
+        // Info string on the opening line: an optional language and attributes,
+        // eg. ```rust {#example .numbered}
+        let (info, content) = match consumed.split_once('\n') {
+            Some((info, content)) => (info.trim(), content),
+            None => ("", consumed)
+        };
+        let (lang, attributes) = split_trailing_attributes(info);
+        let lang = lang.split_whitespace().next().map(|l| l.to_string());
+
         return Ok((
             &rem_l[3..], // Remaining lines
             (
-                consumed, // Possible children
-                Token::Code(Code{})
+                content, // Possible children
+                Token::Code(Code{lang, attributes})
+            )
+        ));
+    }
+}
+
+impl LineConsumingParse for TableOfContents {
+    fn parse_lines(source: &str) -> IResult<&str,(&str,Token)> {
+        let (rem_l, line) = take_line(source)?;
+        let placeholder = line.trim().to_lowercase();
+        if placeholder != "[toc]" && placeholder != "[[toc]]" {
+            return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        }
+        return Ok((rem_l, ("", Token::TableOfContents(TableOfContents{}))));
+    }
+}
+
+impl LineConsumingParse for DefinitionList {
+    fn parse_lines(source: &str) -> IResult<&str,(&str,Token)> {
+
+        // A definition list consists of groups of one or more term lines, each group followed
+        // by one or more definitions. The list ends on a blank line or a line that isn't part
+        // of a group.
+        let mut rem = source;
+        loop {
+            let mut cursor = rem;
+            let mut terms = 0;
+            while let Ok((next, _)) = DefinitionTerm::parse(cursor, 0) {
+                cursor = next;
+                terms += 1;
+            }
+            let mut definitions = 0;
+            while let Ok((next, _)) = Definition::parse(cursor, 0) {
+                cursor = next;
+                definitions += 1;
+            }
+            if terms == 0 || definitions == 0 { break }
+            rem = cursor;
+        }
+
+        if rem.len() == source.len() {
+            return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        }
+        return Ok((
+            rem, // Remaining lines
+            (
+                &source[..source.len() - rem.len()], // Terms and definitions
+                Token::DefinitionList(DefinitionList{})
+            )
+        ));
+    }
+}
+
+impl Table {
+
+    // Cells of a row are separated by pipes that aren't escaped.
+    fn cell_end(source: &str) -> Option<usize> {
+        let mut escaped = false;
+        for (i, c) in source.char_indices() {
+            if c == '|' && !escaped { return Some(i) }
+            escaped = c == '\\' && !escaped;
+        }
+        return None;
+    }
+
+    // Row without the pipes at its start and end.
+    fn row(line: &str) -> &str {
+        let row = line.trim();
+        let row = row.strip_prefix('|').unwrap_or(row);
+        if row.ends_with('|') && !row.ends_with("\\|") { return &row[..row.len() - 1] }
+        return row;
+    }
+
+    fn cell_count(line: &str) -> usize {
+        let mut row = Table::row(line);
+        let mut count = 1;
+        while let Some(end) = Table::cell_end(row) {
+            row = &row[end + 1..];
+            count += 1;
+        }
+        return count;
+    }
+
+    /**
+     Alignments of the columns of a delimiter row, eg. `| :-- | :-: | --: |`. Every cell has at
+     least one dash and optional colons at its ends.
+    */
+    fn alignments(line: &str) -> Option<Vec<Alignment>> {
+        if !line.contains('|') { return None }
+        return Table::row(line).split('|').map(|cell| {
+            let cell = cell.trim();
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') { return None }
+            let alignment = match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => Alignment::Center,
+                (true, false) => Alignment::Left,
+                (false, true) => Alignment::Right,
+                (false, false) => Alignment::None
+            };
+            return Some(alignment);
+        }).collect();
+    }
+}
+
+// A header row with pipes, a delimiter row with as many cells and the rows below them until a
+// blank line, a line without pipes or the start of another block.
+impl LineConsumingParse for Table {
+    fn parse_lines(source: &str) -> IResult<&str,(&str,Token)> {
+        let (rem, header) = take_line(source)?;
+        let (mut rem, delimiter) = take_line(rem)?;
+        let alignments = Table::alignments(delimiter).filter(|a| a.len() == Table::cell_count(header));
+        let Some(alignments) = alignments.filter(|_| header.contains('|')) else {
+            return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        };
+        while let Ok((next, line)) = take_line(rem) {
+            if line.trim().is_empty() || !line.contains('|') || starts_block(rem) { break }
+            rem = next;
+        }
+        return Ok((
+            rem, // Remaining lines
+            (
+                &source[..source.len() - rem.len()], // Rows
+                Token::Table(Table{alignments})
             )
         ));
     }
 }
 
+// Consumes a line of the table, see `table_children` for the header.
+impl Parse for TableRow {
+    fn parse(source: &str, _:usize) -> IResult<&str,(&str,Token)> {
+        let (rem, line) = take_line(source)?;
+        return Ok((rem, (Table::row(line), Token::TableRow(TableRow{header: false}))));
+    }
+}
+
+// Consumes the text of a cell and the pipe after it.
+impl Parse for TableCell {
+    fn parse(source: &str, _:usize) -> IResult<&str,(&str,Token)> {
+        let (content, rem) = match Table::cell_end(source) {
+            Some(end) => (&source[..end], &source[end + 1..]),
+            None => (source, "")
+        };
+        return Ok((rem, (content.trim(), Token::TableCell(TableCell{}))));
+    }
+}
+
+impl Definition {
+
+    // Leading whitespace followed by a colon and at least one space or tab.
+    fn marker(source: &str) -> IResult<&str, &str> {
+        preceded(
+            take_while_m_n(0, 3, |c| c == ' '),
+            terminated(tag(":"), take_while1(|c| c == ' ' || c == '\t')))(source)
+    }
+}
+
+// Whether a block other than a paragraph or definition list starts at the beginning of source.
+fn starts_block(source: &str) -> bool {
+    let blocks = [
+        Code::parse_lines, DisplayMath::parse_lines, Admonition::parse_lines, Directive::parse_lines,
+        List::parse_lines, OrderedList::parse_lines, Table::parse_lines, Header::parse_lines,
+        TableOfContents::parse_lines
+    ];
+    blocks.iter().any(|parse| parse(source).is_ok())
+}
+
+// Consumes a single line that is not blank, indented, a definition or the start of another block.
+impl Parse for DefinitionTerm {
+    fn parse(source: &str, _:usize) -> IResult<&str,(&str,Token)> {
+        let (rem, line) = take_line(source)?;
+        let indented = line.starts_with(' ') || line.starts_with('\t');
+        if line.trim().is_empty() || indented || Definition::marker(line).is_ok() || starts_block(source) {
+            return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        }
+        return Ok((rem, (line, Token::DefinitionTerm(DefinitionTerm{}))));
+    }
+}
+
+// Consumes the marked line and any indented continuation lines following it.
+impl Parse for Definition {
+    fn parse(source: &str, _:usize) -> IResult<&str,(&str,Token)> {
+        let (content, _) = Definition::marker(source)?;
+        let (mut rem, _) = take_line(content)?;
+        while let Ok((next, line)) = take_line(rem) {
+            let indented = line.starts_with("  ") || line.starts_with('\t');
+            if !indented || line.trim().is_empty() { break }
+            rem = next;
+        }
+        return Ok((
+            rem,
+            (&content[..content.len() - rem.len()], Token::Definition(Definition{}))
+        ));
+    }
+}
+
+impl Admonition {
+
+    // `:::kind Optional title` followed by block content and a closing `:::`.
+    fn parse_fenced(source: &str) -> IResult<&str,(&str,Token)> {
+        let (rem, (info, content)) = take_fenced_container(source)?;
+        let (title, name) = take_name(info)?;
+        if !title.is_empty() && !title.starts_with(char::is_whitespace) {
+            return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        }
+        let Some(kind) = AdmonitionKind::from_name(name) else {
+            return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        };
+        let (title, attributes) = split_trailing_attributes(title);
+        let title = title.trim();
+        return Ok((
+            rem, // Remaining lines
+            (
+                content, // Possible children
+                Token::Admonition(Admonition{
+                    kind,
+                    title: if title.is_empty() { None } else { Some(title.to_string()) },
+                    attributes
+                })
+            )
+        ));
+    }
+
+    // `> [!KIND] Optional title` followed by lines quoted with `>`.
+    fn parse_quoted(source: &str) -> IResult<&str,(&str,Token)> {
+        let (content_start, first_line) = take_line(source)?;
+        let marker = tuple((take_while_m_n(0, 3, |c| c == ' '), tag(">"), take_while(|c| c == ' '), tag("[!")));
+        let (_, (name, title)) = tuple((
+            preceded(marker, take_till(|c| c == ']')),
+            preceded(tag("]"), rest)))(first_line)?;
+        let Some(kind) = AdmonitionKind::from_name(name) else {
+            return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        };
+
+        let mut rem = content_start;
+        while let Ok((next, line)) = take_line(rem) {
+            if !line.trim_start().starts_with('>') { break }
+            rem = next;
+        }
+        let (title, attributes) = split_trailing_attributes(title);
+        let title = title.trim();
+        return Ok((
+            rem, // Remaining lines
+            (
+                &content_start[..content_start.len() - rem.len()], // Quoted lines
+                Token::Admonition(Admonition{
+                    kind,
+                    title: if title.is_empty() { None } else { Some(title.to_string()) },
+                    attributes
+                })
+            )
+        ));
+    }
+}
+
+impl LineConsumingParse for Admonition {
+    fn parse_lines(source: &str) -> IResult<&str,(&str,Token)> {
+        Admonition::parse_fenced(source).or_else(|_| Admonition::parse_quoted(source))
+    }
+}
+
+// `:::name{attrs}` followed by block content and a closing `:::`.
+impl LineConsumingParse for Directive {
+    fn parse_lines(source: &str) -> IResult<&str,(&str,Token)> {
+        let (rem, (info, content)) = take_fenced_container(source)?;
+        let (after_name, name) = take_name(info)?;
+        let (trailing, attributes) = opt(parse_attributes)(after_name)?;
+        if !trailing.trim().is_empty() {
+            return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        }
+        return Ok((
+            rem, // Remaining lines
+            (
+                content, // Possible children
+                Token::Directive(Directive{
+                    name: name.to_string(),
+                    attributes: attributes.unwrap_or_default(),
+                    inline: false
+                })
+            )
+        ));
+    }
+}
+
+// `:name[content]{attrs}`, attributes are optional.
+impl Parse for Directive {
+    fn parse(source: &str, _:usize) -> IResult<&str,(&str,Token)> {
+        let (rem, (name, content, attributes)) = tuple((
+            preceded(char(':'), take_name),
+            delimited(char('['), take_till(|c| c == ']'), char(']')),
+            opt(parse_attributes)))(source)?;
+        return Ok((
+            rem,
+            (content, Token::Directive(Directive{
+                name: name.to_string(),
+                attributes: attributes.unwrap_or_default(),
+                inline: true
+            }))
+        ));
+    }
+}
+
+impl DisplayMath {
+    fn delimited(source: &str) -> IResult<&str, &str> {
+        delimited(tag("$$"), take_until("$$"), tag("$$"))(source)
+    }
+}
+
+// Only consumes when nothing but whitespace follows the closing delimiter, otherwise the line is
+// a paragraph containing display math.
+impl LineConsumingParse for DisplayMath {
+    fn parse_lines(source: &str) -> IResult<&str,(&str,Token)> {
+        let (rem, tex) = DisplayMath::delimited(source)?;
+        let (rem_l, trailing) = if rem.is_empty() { ("", "") } else { take_line(rem)? };
+        if !trailing.trim().is_empty() {
+            return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        }
+        return Ok((
+            rem_l, // Remaining lines
+            (
+                "", // TeX is kept in the token, no children
+                Token::DisplayMath(DisplayMath{tex: tex.trim().to_string()})
+            )
+        ));
+    }
+}
+
+impl Parse for DisplayMath {
+    fn parse(source: &str, _:usize) -> IResult<&str,(&str,Token)> {
+        let (rem, tex) = DisplayMath::delimited(source)?;
+        Ok((
+            rem,
+            ("",Token::DisplayMath(DisplayMath{tex: tex.trim().to_string()}))
+        ))
+    }
+}
+
+impl Parse for InlineMath {
+    fn parse(source: &str, _:usize) -> IResult<&str,(&str,Token)> {
+        let (rem, tex) = delimited(char('$'), is_not("$"), char('$'))(source)?;
+
+        // Same rules as pandoc: no whitespace inside the delimiters and no digit directly after
+        // the closing one, so that eg. "$5 and $10" stays plain text.
+        let padded = tex.starts_with(char::is_whitespace) || tex.ends_with(char::is_whitespace);
+        if padded || rem.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        }
+        Ok((
+            rem,
+            ("",Token::InlineMath(InlineMath{tex: tex.to_string()}))
+        ))
+    }
+}
+
 impl List {
 
-    // Require a depth-amount of leading whitespace to parse a new list.
-    fn parse_by_depth(source: &str, depth: usize) -> IResult<&str,(&str,Token)> {
+    // Bullet of a list item, `-`, `*` or `+` followed by a space.
+    fn bullet(source: &str) -> IResult<&str, char> {
+        terminated(one_of("-*+"), char(' '))(source)
+    }
+
+    // Number of an ordered list item, up to nine digits followed by `.` or `)` and a space.
+    fn number(source: &str) -> IResult<&str, u32> {
+        let digits = map_res(take_while_m_n(1, 9, |c: char| c.is_ascii_digit()), str::parse);
+        terminated(terminated(digits, one_of(".)")), char(' '))(source)
+    }
+
+    // Marker of an item of a bulleted or an ordered list, gives the number of ordered items.
+    fn marker(source: &str, ordered: bool) -> IResult<&str, u32> {
+        if ordered {
+            return List::number(source);
+        }
+        return List::bullet(source).map(|(rem, _)| (rem, 1));
+    }
+
+    // Lines of a list start with a marker of its own kind, indented lines may start nested lists
+    // of either kind.
+    fn continues(line: &str, ordered: bool) -> bool {
+        let unindented = line.trim_start_matches(' ');
+        if unindented.len() < line.len() {
+            return List::marker(unindented, true).is_ok() || List::marker(unindented, false).is_ok();
+        }
+        return List::marker(line, ordered).is_ok();
+    }
+
+    fn token(depth: usize, start: u32, ordered: bool) -> Token {
+        if ordered {
+            return Token::OrderedList(OrderedList{level: depth, start, attributes: Attributes::new()});
+        }
+        return Token::List(List{level: depth, attributes: Attributes::new()});
+    }
+
+    // Top level list, up to the first line that is blank or does not continue it.
+    fn parse_items(source: &str, ordered: bool) -> IResult<&str,(&str,Token)> {
 
         // List can only be terminated bV
         //   1. Two newlines in a row
-        //   2. A newline and a sequence other than "- "
-        //   3. "- " preceded by less than depth -amount of whitespace
+        //   2. A newline and a sequence other than a marker
 
         // Check if the first line exists
-        let res: IResult<&str, &str> = preceded(
-            terminated(take_while_m_n(0,depth,|x| is_space(x as u8)), tag("- ")),
-            take_until("\n\n"))(source);
+        let (_, start) = List::marker(source, ordered)?;
 
         // NOTE: Manual parser!
-        if let Ok((_, _)) = res {
-
-            // Take input until terminated by
-            //   1. Two newlines in a row
-            //   2. A newline and a sequence other than "- "
-            let mut previous_newline = false;
-            let mut leading_whitespace = 0;
-            let mut i = 0;
-            for c in source.chars() {
-                if previous_newline && ( is_newline(c as u8) || c != '-' ) {
-                    if is_space(c as u8) {  // allow leading spaces before lines
-                        i+=1;
-                        leading_whitespace +=1;
-                        continue;
-                    }
-
-                    if leading_whitespace < depth {
-                        continue;
-                    }
-                    let consumed_s: &str = &source[0..i];
-                    let rem_s: &str = &source[i..];
-                    return Ok((
-                        rem_s,
-                        (
-                            consumed_s,
-                            Token::List(List{level: depth})
-                        )
-                    ))
-                }
-                i+=1;
-                previous_newline = is_newline(c as u8);
+        let mut previous_newline = false;
+        for (i, c) in source.char_indices() {
+            if previous_newline && ( is_newline(c as u8) || !List::continues(&source[i..], ordered) ) {
+                return Ok((&source[i..], (&source[0..i], List::token(0, start, ordered))));
             }
+            previous_newline = is_newline(c as u8);
         }
-        return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        return Ok(("", (source, List::token(0, start, ordered))));
+    }
+
+    // Nested list indented by at least a depth-amount of whitespace. It ends before the first line
+    // that is indented less, or is an item of the other kind at the same indentation.
+    fn parse_nested(source: &str, depth: usize, ordered: bool) -> IResult<&str,(&str,Token)> {
+        let indentation = source.len() - source.trim_start_matches(' ').len();
+        if indentation < depth {
+            return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+        }
+        let (_, start) = List::marker(&source[indentation..], ordered)?;
+        let mut end = 0;
+        for line in source.split_inclusive('\n') {
+            let unindented = line.trim_start_matches(' ');
+            let belongs = match line.len() - unindented.len() {
+                i if i > indentation => List::continues(unindented, true) || List::continues(unindented, false),
+                i if i == indentation => List::continues(unindented, ordered),
+                _ => false
+            };
+            if !belongs { break }
+            end += line.len();
+        }
+        return Ok((&source[end..], (&source[..end], List::token(depth, start, ordered))));
+    }
+
+    // Top level list, attributes of the whole list go on the line right after its last item, eg.
+    // `{.compact}`.
+    fn parse_top_level(source: &str, ordered: bool) -> IResult<&str,(&str,Token)> {
+        let (rem, (consumed, token)) = List::parse_items(source, ordered)?;
+        if let Ok((after, line)) = take_line(rem) {
+            if let Ok(("", attributes)) = parse_attributes(line.trim()) {
+                let token = match token {
+                    Token::OrderedList(list) => Token::OrderedList(OrderedList{attributes, ..list}),
+                    Token::List(list) => Token::List(List{attributes, ..list}),
+                    _ => unreachable!()
+                };
+                return Ok((after, (consumed, token)));
+            }
+        }
+        return Ok((rem, (consumed, token)));
     }
 }
 
 impl LineConsumingParse for List {
     fn parse_lines(source: &str) -> IResult<&str,(&str,Token)> {
-        List::parse_by_depth(source,0)
+        List::parse_top_level(source, false)
     }
 }
 
 impl Parse for List {
     fn parse(source: &str,depth:usize) -> IResult<&str,(&str,Token)> {
-        List::parse_by_depth(source,depth)
+        List::parse_nested(source, depth, false)
+    }
+}
+
+impl LineConsumingParse for OrderedList {
+    fn parse_lines(source: &str) -> IResult<&str,(&str,Token)> {
+        List::parse_top_level(source, true)
+    }
+}
+
+impl Parse for OrderedList {
+    fn parse(source: &str,depth:usize) -> IResult<&str,(&str,Token)> {
+        List::parse_nested(source, depth, true)
     }
 }
 
+// Underscore emphasis can't end inside a word, so that eg. snake_case_names stay plain text.
+fn not_intraword(rem: &str) -> IResult<&str, ()> {
+    if rem.starts_with(char::is_alphanumeric) {
+        return Err(Err::Error(Error{input: "", code: ErrorKind::Satisfy}));
+    }
+    return Ok((rem, ()));
+}
+
 impl Parse for Italic {
     fn parse(source: &str,_:usize) -> IResult<&str,(&str,Token)> {
-        let (rem,consumed) = delimited( char('*'), is_not("*"), char('*'))(source)?;
+        let (rem,consumed) = alt((
+            delimited( char('*'), is_not("*"), char('*')),
+            terminated(delimited( char('_'), is_not("_"), char('_')), not_intraword)
+        ))(source)?;
         Ok((
             rem,
             (consumed,Token::Italic(Italic{}))
@@ -339,9 +1028,10 @@ impl Parse for InlineCode {
 
 impl Parse for Bold {
     fn parse(source: &str, _:usize) -> IResult<&str,(&str,Token)> {
-        let (rem,consumed) = terminated(
-            preceded(tag("**"), take_till(|c| c == '*')),
-            tag("**"))(source)?;
+        let (rem,consumed) = alt((
+            terminated(preceded(tag("**"), take_till(|c| c == '*')), tag("**")),
+            terminated(terminated(preceded(tag("__"), take_till(|c| c == '_')), tag("__")), not_intraword)
+        ))(source)?;
         Ok((rem,
             (consumed,Token::Bold(Bold{}))
         ))
@@ -351,7 +1041,8 @@ impl Parse for Bold {
 // Consumes everything as any input passed to this will be a complete line
 impl Parse for ListItem {
     fn parse(source: &str,_:usize) -> IResult<&str,(&str,Token)> {
-        let preciding_whitespace = terminated(take_while(|x| is_space(x as u8)), tag("- "));
+        let marker = alt((|s| List::marker(s, false), |s| List::marker(s, true)));
+        let preciding_whitespace = terminated(take_while(|x| is_space(x as u8)), marker);
         let mut list_line = preceded(
             preciding_whitespace, take_line);
         let (rem, consumed) = list_line(source)?;
@@ -359,22 +1050,38 @@ impl Parse for ListItem {
     }
 }
 
+// Parsers for the inline content of block level tokens.
+fn inline_parsers() -> Vec<TokenParser> {
+    vec![
+        DisplayMath::parse, InlineMath::parse, Directive::parse,
+        Italic::parse, Bold::parse, Link::parse, InlineCode::parse
+    ]
+}
+
 pub trait HigherLevel {
-    fn child_parsers(&self) -> Vec<fn(&str,usize) -> IResult<&str, (&str,Token)>>;
+    fn child_parsers(&self) -> Vec<TokenParser>;
 }
 
 impl HigherLevel for Token {
-    fn child_parsers(&self) -> Vec<fn(&str,usize) -> IResult<&str, (&str,Token)>> {
+    fn child_parsers(&self) -> Vec<TokenParser> {
         match self {
-            Token::Header(_) => vec![Italic::parse, Bold::parse, Link::parse, InlineCode::parse],
-            Token::Paragraph(_) => vec![Italic::parse, Bold::parse, Link::parse, InlineCode::parse],
-            Token::List(_) => vec![List::parse,ListItem::parse],
+            Token::Header(_) => inline_parsers(),
+            Token::Paragraph(_) => inline_parsers(),
+            Token::List(_) | Token::OrderedList(_) => vec![List::parse,OrderedList::parse,ListItem::parse],
             Token::Code(_) => vec![],
-            Token::ListItem(_) => vec![Italic::parse, Bold::parse, Link::parse, InlineCode::parse],
+            Token::ListItem(_) => inline_parsers(),
             Token::Link(_) => vec![Italic::parse, Bold::parse],
             Token::Bold(_) => vec![Italic::parse, Link::parse],
             Token::Italic(_) => vec![Bold::parse, Link::parse],
             Token::InlineCode(_) => vec![Italic::parse, Bold::parse],
+            Token::DefinitionList(_) => vec![Definition::parse, DefinitionTerm::parse],
+            Token::Table(_) => vec![TableRow::parse],
+            Token::TableRow(_) => vec![TableCell::parse],
+            Token::TableCell(_) => inline_parsers(),
+            Token::DefinitionTerm(_) => inline_parsers(),
+            Token::Definition(_) => vec![parse_block],
+            Token::Admonition(_) => vec![parse_block],
+            Token::Directive(d) => if d.inline { inline_parsers() } else { vec![parse_block] },
             _ => vec![]
         }
     }
@@ -403,16 +1110,83 @@ mod tests {
 ### Third header"
         );
         let expected_order: Vec<Token> = Vec::from([
-            Token::Header(Header{level: 1}),
+            Token::Header(Header{level: 1, attributes: Attributes::new()}),
             Token::PlainText(PlainText{text: String::from("First header")}),
-            Token::Header(Header{level: 2}),
+            Token::Header(Header{level: 2, attributes: Attributes::new()}),
             Token::PlainText(PlainText{text: String::from("Second header")}),
-            Token::Header(Header{level: 3}),
+            Token::Header(Header{level: 3, attributes: Attributes::new()}),
             Token::PlainText(PlainText{text: String::from("Third header")}),
         ]);
         match_syntax(md_syntax, expected_order);
     }
 
+    #[test]
+    fn t_header_attributes() {
+        let md_syntax = parse_md_str(
+"## Install *now* {#setup .wide data-x=1}
+# Sets {a, b}"
+        );
+        let attributes = Attributes::from([
+            ("id".to_string(), "setup".to_string()),
+            ("class".to_string(), "wide".to_string()),
+            ("data-x".to_string(), "1".to_string()),
+        ]);
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Header(Header{level: 2, attributes}),
+            Token::PlainText(PlainText{text: String::from("Install ")}),
+            Token::Italic(Italic{}),
+            Token::PlainText(PlainText{text: String::from("now")}),
+            Token::Header(Header{level: 1, attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Sets {a, b}")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_block_attributes() {
+        let md_syntax = parse_md_str(
+"Some text {.lead}
+- item
+{#items}
+:::warning Careful {.wide}
+Inside
+:::"
+        );
+        let class = |c: &str| Attributes::from([("class".to_string(), c.to_string())]);
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Paragraph(Paragraph{attributes: class("lead")}),
+            Token::PlainText(PlainText{text: String::from("Some text")}),
+            Token::List(List{level: 0, attributes: Attributes::from([("id".to_string(), "items".to_string())])}),
+            Token::ListItem(ListItem{}),
+            Token::PlainText(PlainText{text: String::from("item")}),
+            Token::Admonition(Admonition{
+                kind: AdmonitionKind::Warning,
+                title: Some(String::from("Careful")),
+                attributes: class("wide")
+            }),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Inside")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_code_info() {
+        let md_syntax = parse_md_str(
+"```rust {#main}
+fn main() {}
+```"
+        );
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Code(Code{
+                lang: Some(String::from("rust")),
+                attributes: Attributes::from([("id".to_string(), "main".to_string())])
+            }),
+            Token::PlainText(PlainText{text: String::from("fn main() {}\n")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
     #[test]
     fn t_links() {
         let md_syntax = parse_md_str(
@@ -421,17 +1195,17 @@ mod tests {
 **[Bold link](http://gnu.org)**"
         );
         let expected_order: Vec<Token> = Vec::from([
-            Token::Paragraph(Paragraph{}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
             Token::Link(Link{url: String::from("http://gnu.org")}),
             Token::PlainText(PlainText{text: String::from("Link")}),
 
-            Token::Paragraph(Paragraph{}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
 
             Token::Italic(Italic{}),
             Token::Link(Link{url: String::from("http://gnu.org")}),
             Token::PlainText(PlainText{text: String::from("Italic link")}),
 
-            Token::Paragraph(Paragraph{}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
 
             Token::Bold(Bold{}),
             Token::Link(Link{url: String::from("http://gnu.org")}),
@@ -450,14 +1224,339 @@ mod tests {
 "
         );
         let expected_order: Vec<Token> = Vec::from([
-            Token::List(List{level: 0}),
+            Token::List(List{level: 0, attributes: Attributes::new()}),
             Token::ListItem(ListItem{}),
             Token::PlainText(PlainText{text: String::from("First item")}),
             Token::ListItem(ListItem{}),
             Token::PlainText(PlainText{text: String::from("Second item")}),
             Token::ListItem(ListItem{}),
             Token::PlainText(PlainText{text: String::from("Third item")}),
-            Token::Paragraph(Paragraph{}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_list_bullets() {
+        // Any bullet continues the list, which may end with the input.
+        let md_syntax = parse_md_str("* First\n+ Second\n- Third");
+        let expected_order: Vec<Token> = Vec::from([
+            Token::List(List{level: 0, attributes: Attributes::new()}),
+            Token::ListItem(ListItem{}),
+            Token::PlainText(PlainText{text: String::from("First")}),
+            Token::ListItem(ListItem{}),
+            Token::PlainText(PlainText{text: String::from("Second")}),
+            Token::ListItem(ListItem{}),
+            Token::PlainText(PlainText{text: String::from("Third")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_ordered_lists() {
+        // The first number is the start of the list, a bulleted line starts another list. Nested
+        // lists are indented past the items of their parent list.
+        let md_syntax = parse_md_str("3. Third\n4) Fourth\n   - Nested\n- Bullet\n");
+        let expected_order: Vec<Token> = Vec::from([
+            Token::OrderedList(OrderedList{level: 0, start: 3, attributes: Attributes::new()}),
+            Token::ListItem(ListItem{}),
+            Token::PlainText(PlainText{text: String::from("Third")}),
+            Token::ListItem(ListItem{}),
+            Token::PlainText(PlainText{text: String::from("Fourth")}),
+            Token::List(List{level: 1, attributes: Attributes::new()}),
+            Token::ListItem(ListItem{}),
+            Token::PlainText(PlainText{text: String::from("Nested")}),
+            Token::List(List{level: 0, attributes: Attributes::new()}),
+            Token::ListItem(ListItem{}),
+            Token::PlainText(PlainText{text: String::from("Bullet")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_setext_headers() {
+        let md_syntax = parse_md_str("First {#one}\n===\nSecond\n-\n");
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Header(Header{level: 1, attributes: Attributes::from([("id".to_string(), "one".to_string())])}),
+            Token::PlainText(PlainText{text: String::from("First")}),
+            Token::Header(Header{level: 2, attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Second")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_not_setext_headers() {
+        // Indented text, a blank line or mixed markers don't make a header.
+        let md_syntax = parse_md_str(" Indented\n===\n\n==\nMixed\n=-=\n");
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from(" Indented")}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("===")}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("==")}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Mixed")}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("=-=")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_list_at_end_of_input() {
+        // Nested lists also end with the input.
+        let md_syntax = parse_md_str("Text\n- a\n  - b");
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Text")}),
+            Token::List(List{level: 0, attributes: Attributes::new()}),
+            Token::ListItem(ListItem{}),
+            Token::PlainText(PlainText{text: String::from("a")}),
+            Token::List(List{level: 1, attributes: Attributes::new()}),
+            Token::ListItem(ListItem{}),
+            Token::PlainText(PlainText{text: String::from("b")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_bullet_needs_space() {
+        let md_syntax = parse_md_str("*not a list*\n+1");
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::Italic(Italic{}),
+            Token::PlainText(PlainText{text: String::from("not a list")}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("+1")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_underscore_emphasis() {
+        let md_syntax = parse_md_str("_it_ __bold__ snake_case_name");
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::Italic(Italic{}),
+            Token::PlainText(PlainText{text: String::from("it")}),
+            Token::PlainText(PlainText{text: String::from(" ")}),
+            Token::Bold(Bold{}),
+            Token::PlainText(PlainText{text: String::from("bold")}),
+            Token::PlainText(PlainText{text: String::from(" snake_case_name")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_definition_lists() {
+        let md_syntax = parse_md_str(
+"Apple
+: A fruit
+: A *company*
+Orange
+: Citrus
+  with a second line"
+        );
+        let expected_order: Vec<Token> = Vec::from([
+            Token::DefinitionList(DefinitionList{}),
+            Token::DefinitionTerm(DefinitionTerm{}),
+            Token::PlainText(PlainText{text: String::from("Apple")}),
+            Token::Definition(Definition{}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("A fruit")}),
+            Token::Definition(Definition{}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("A ")}),
+            Token::Italic(Italic{}),
+            Token::PlainText(PlainText{text: String::from("company")}),
+
+            Token::DefinitionTerm(DefinitionTerm{}),
+            Token::PlainText(PlainText{text: String::from("Orange")}),
+            Token::Definition(Definition{}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Citrus")}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("with a second line")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_math() {
+        let md_syntax = parse_md_str(
+"Euler: $e^{i\\pi} + 1 = 0$, costs $5 and $10
+$$
+\\frac{*a*}{b}
+$$"
+        );
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Euler: ")}),
+            Token::InlineMath(InlineMath{tex: String::from("e^{i\\pi} + 1 = 0")}),
+            Token::PlainText(PlainText{text: String::from(", costs $5 and $10")}),
+            Token::DisplayMath(DisplayMath{tex: String::from("\\frac{*a*}{b}")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_admonitions() {
+        let md_syntax = parse_md_str(
+"> [!WARNING]
+> Do **not** restart
+>
+> > [!TIP] Nested
+:::note Read this
+Fenced *content*
+:::
+:::unknown
+:::"
+        );
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Admonition(Admonition{kind: AdmonitionKind::Warning, title: None, attributes: Attributes::new()}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Do ")}),
+            Token::Bold(Bold{}),
+            Token::PlainText(PlainText{text: String::from("not")}),
+            Token::PlainText(PlainText{text: String::from(" restart")}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::Admonition(Admonition{kind: AdmonitionKind::Tip, title: Some(String::from("Nested")), attributes: Attributes::new()}),
+
+            Token::Admonition(Admonition{kind: AdmonitionKind::Note, title: Some(String::from("Read this")), attributes: Attributes::new()}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Fenced ")}),
+            Token::Italic(Italic{}),
+            Token::PlainText(PlainText{text: String::from("content")}),
+
+            Token::Directive(Directive{name: String::from("unknown"), attributes: Attributes::new(), inline: false}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_directives() {
+        let md_syntax = parse_md_str(
+":::tabs{#install .wide title=\"Install it\"}
+Press :kbd[Ctrl]{.key} now
+:::"
+        );
+        let attributes = Attributes::from([
+            ("id".to_string(), "install".to_string()),
+            ("class".to_string(), "wide".to_string()),
+            ("title".to_string(), "Install it".to_string()),
+        ]);
+        let key = Attributes::from([("class".to_string(), "key".to_string())]);
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Directive(Directive{name: String::from("tabs"), attributes, inline: false}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Press ")}),
+            Token::Directive(Directive{name: String::from("kbd"), attributes: key, inline: true}),
+            Token::PlainText(PlainText{text: String::from("Ctrl")}),
+            Token::PlainText(PlainText{text: String::from(" now")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_admonition_blocks() {
+        // Blocks spanning several quoted lines parse as a whole.
+        let md_syntax = parse_md_str(
+"> [!NOTE]
+> - one
+> - two
+>
+> ```rust
+> let x = 1;
+> ```"
+        );
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Admonition(Admonition{kind: AdmonitionKind::Note, title: None, attributes: Attributes::new()}),
+            Token::List(List{level: 0, attributes: Attributes::new()}),
+            Token::ListItem(ListItem{}),
+            Token::PlainText(PlainText{text: String::from("one")}),
+            Token::ListItem(ListItem{}),
+            Token::PlainText(PlainText{text: String::from("two")}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::Code(Code{lang: Some(String::from("rust")), attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("let x = 1;\n")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_toc_placeholder() {
+        let md_syntax = parse_md_str("[TOC]\n[[toc]]\n[toc] here");
+        let expected_order: Vec<Token> = Vec::from([
+            Token::TableOfContents(TableOfContents{}),
+            Token::TableOfContents(TableOfContents{}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("[toc] here")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_definition_list_requires_definition() {
+        let md_syntax = parse_md_str(
+"Apple
+Orange"
+        );
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Apple")}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Orange")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_definition_term_before_block() {
+        // A line starting another block isn't a term, even when a definition follows it.
+        let md_syntax = parse_md_str("# Title\n: Not a definition\n");
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Header(Header{level: 1, attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from("Title")}),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::PlainText(PlainText{text: String::from(": Not a definition")}),
+        ]);
+        match_syntax(md_syntax, expected_order);
+    }
+
+    #[test]
+    fn t_tables() {
+        let md_syntax = parse_md_str(
+            "| a | *b* |\n|:--|--:|\n| 1 | x \\| y |\n||z\n| one |\n| 1 | 2 | 3 |\n| - | - |\n\nNo | table\n---\n"
+        );
+        let cell = || Token::TableCell(TableCell{});
+        let text = |t: &str| Token::PlainText(PlainText{text: String::from(t)});
+        let expected_order: Vec<Token> = Vec::from([
+            Token::Table(Table{alignments: vec![Alignment::Left, Alignment::Right]}),
+            Token::TableRow(TableRow{header: true}),
+            cell(), text("a"),
+            cell(), Token::Italic(Italic{}), text("b"),
+            Token::TableRow(TableRow{header: false}),
+            cell(), text("1"),
+            cell(), text("x | y"),
+            Token::TableRow(TableRow{header: false}),
+            cell(),
+            cell(), text("z"),
+            // Rows get as many cells as the header, only the row after the table start is a header.
+            Token::TableRow(TableRow{header: false}),
+            cell(), text("one"),
+            cell(),
+            Token::TableRow(TableRow{header: false}),
+            cell(), text("1"),
+            cell(), text("2"),
+            Token::TableRow(TableRow{header: false}),
+            cell(), text("-"),
+            cell(), text("-"),
+            Token::Paragraph(Paragraph{attributes: Attributes::new()}),
+            Token::Header(Header{level: 2, attributes: Attributes::new()}),
+            text("No | table"),
         ]);
         match_syntax(md_syntax, expected_order);
     }
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use crate::tokens::*;
+use crate::tree::TreeIndex;
+
+/*
+ * GitHub compatible slugs for header anchors. Slugs are lowercase, keep Unicode letters and
+ * numbers, turn spaces into dashes and drop other punctuation. Repeated slugs in a document get
+ * `-1`, `-2`, ... suffixes, custom ids are kept as they are.
+ */
+
+pub fn slugify(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None
+        })
+        .collect()
+}
+
+/**
+ Hands out unique slugs within a document.
+*/
+#[derive(Default)]
+pub struct Slugger {
+    occurrences: HashMap<String, usize>
+}
+
+impl Slugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn slug(&mut self, text: &str) -> String {
+        self.reserve(&slugify(text))
+    }
+
+    /**
+     Mark an id used as it is, eg. a custom id, so that generated slugs never collide with it.
+    */
+    pub fn claim(&mut self, id: &str) {
+        self.occurrences.entry(id.to_string()).or_insert(0);
+    }
+
+    // Make a slug unique and mark it used.
+    pub fn reserve(&mut self, slug: &str) -> String {
+        let mut result = slug.to_string();
+        while self.occurrences.contains_key(&result) {
+            let count = self.occurrences.get_mut(slug).unwrap();
+            *count += 1;
+            result = format!("{}-{}", slug, count);
+        }
+        self.occurrences.insert(result.clone(), 0);
+        return result;
+    }
+}
+
+/**
+ Text of a token and its children, eg. "Install now" for `## Install *now*`.
+*/
+pub fn text_content(line: &MdLine, index: TreeIndex) -> String {
+    line.subtree_dfs(index)
+        .filter_map(|token| match token {
+            Token::PlainText(t) => Some(t.text()),
+            Token::InlineMath(m) => Some(m.src()),
+            Token::DisplayMath(m) => Some(m.src()),
+            _ => None
+        })
+        .collect()
+}
+
+/**
+ Ids of all headers in document order. Headers with a custom id keep it, generated slugs make way
+ for custom ids anywhere in the document.
+*/
+pub fn header_slugs(doc: &MdSyntaxTree) -> Vec<String> {
+    let mut slugger = Slugger::new();
+    let headers: Vec<(&MdLine, usize, &Header)> = doc.iter()
+        .flat_map(|line| line.nodes_dfs().filter_map(move |node| match &node.val {
+            Token::Header(h) => Some((line, node.index(), h)),
+            _ => None
+        }))
+        .collect();
+    for (_, _, header) in &headers {
+        if let Some(id) = header.attributes.get("id") { slugger.claim(id) }
+    }
+    return headers.iter()
+        .map(|(line, index, header)| match header.attributes.get("id") {
+            Some(id) => id.clone(),
+            None => slugger.slug(&text_content(line, TreeIndex::Arena(*index)))
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    #[test]
+    fn t_slugify() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("Über  straße_2 -x"), "über--straße_2--x");
+        assert_eq!(slugify("日本語 テスト"), "日本語-テスト");
+    }
+
+    #[test]
+    fn t_deduplication() {
+        let mut slugger = Slugger::new();
+        assert_eq!(slugger.slug("Foo"), "foo");
+        assert_eq!(slugger.slug("Foo"), "foo-1");
+        assert_eq!(slugger.slug("foo-1"), "foo-1-1");
+        assert_eq!(slugger.slug("Foo"), "foo-2");
+    }
+
+    #[test]
+    fn t_header_slugs() {
+        let doc = parse_md_str("# Intro\n## Setup `cargo`\n# Intro\n## Custom {#intro-1}");
+        assert_eq!(header_slugs(&doc), ["intro", "setup-cargo", "intro-2", "intro-1"]);
+        let doc = parse_md_str("# Setup\n## Install {#setup}");
+        assert_eq!(header_slugs(&doc), ["setup-1", "setup"]);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::tokens::*;
+use crate::tree::TreeIndex;
+use crate::slug::{header_slugs, text_content};
+use crate::compilation_targets::to_html::escape_html;
+
+/*
+ * Table of contents built from the headers of a document.
+ */
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct TocEntry {
+    pub level: u32,
+    pub text: String,
+    pub slug: String,
+    pub children: Vec<TocEntry>
+}
+
+// Headers outside of the level range are left out of the table of contents.
+#[derive(Clone, Copy, Debug)]
+pub struct TocOptions {
+    pub min_level: u32,
+    pub max_level: u32
+}
+
+impl Default for TocOptions {
+    fn default() -> Self {
+        TocOptions{min_level: 1, max_level: 6}
+    }
+}
+
+/**
+ Build a nested table of contents. An entry is a child of the closest preceding entry with a
+ lower level.
+*/
+pub fn build_toc(doc: &MdSyntaxTree, options: &TocOptions) -> Vec<TocEntry> {
+
+    // Slugs are taken in the same order as headers are visited here.
+    let mut slugs = header_slugs(doc).into_iter();
+    let mut flat = Vec::new();
+    for line in doc {
+        for node in line.nodes_dfs() {
+            if let Token::Header(h) = &node.val {
+                let slug = slugs.next().unwrap_or_default();
+                if h.level < options.min_level || h.level > options.max_level { continue }
+                flat.push(TocEntry{
+                    level: h.level,
+                    text: text_content(line, TreeIndex::Arena(node.index())).trim().to_string(),
+                    slug,
+                    children: Vec::new()
+                });
+            }
+        }
+    }
+
+    // Stack of open entries, each waiting for children with a higher level.
+    let mut roots: Vec<TocEntry> = Vec::new();
+    let mut stack: Vec<TocEntry> = Vec::new();
+    for entry in flat {
+        while stack.last().is_some_and(|open| open.level >= entry.level) {
+            close_entry(&mut stack, &mut roots);
+        }
+        stack.push(entry);
+    }
+    while !stack.is_empty() { close_entry(&mut stack, &mut roots) }
+    return roots;
+}
+
+fn close_entry(stack: &mut Vec<TocEntry>, roots: &mut Vec<TocEntry>) {
+    let closed = stack.pop().unwrap();
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(closed),
+        None => roots.push(closed)
+    }
+}
+
+pub fn toc_to_html(entries: &[TocEntry]) -> String {
+    fn items(entries: &[TocEntry]) -> String {
+        entries.iter()
+            .map(|e| {
+                let children = if e.children.is_empty() { "".to_string() } else { format!("<ul>{}</ul>", items(&e.children)) };
+                format!("<li><a href=\"#{}\">{}</a>{}</li>", escape_html(&e.slug), escape_html(&e.text), children)
+            })
+            .collect()
+    }
+    if entries.is_empty() { return "".to_string() }
+    return format!("<ul class=\"toc\">{}</ul>", items(entries));
+}
+
+pub fn toc_to_markdown(entries: &[TocEntry]) -> String {
+    fn items(entries: &[TocEntry], depth: usize, out: &mut String) {
+        for e in entries {
+            let text = e.text.replace('[', "\\[").replace(']', "\\]");
+            out.push_str(&format!("{}- [{}](#{})\n", "  ".repeat(depth), text, e.slug));
+            items(&e.children, depth + 1, out);
+        }
+    }
+    let mut out = String::new();
+    items(entries, 0, &mut out);
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_md_str;
+
+    fn entry(level: u32, text: &str, slug: &str, children: Vec<TocEntry>) -> TocEntry {
+        TocEntry{level, text: text.to_string(), slug: slug.to_string(), children}
+    }
+
+    #[test]
+    fn t_nesting() {
+        let doc = parse_md_str("# Guide\n## Install\n### Linux\n## Use\n# FAQ");
+        assert_eq!(build_toc(&doc, &TocOptions::default()), vec![
+            entry(1, "Guide", "guide", vec![
+                entry(2, "Install", "install", vec![entry(3, "Linux", "linux", vec![])]),
+                entry(2, "Use", "use", vec![]),
+            ]),
+            entry(1, "FAQ", "faq", vec![]),
+        ]);
+    }
+
+    #[test]
+    fn t_level_filter_and_rendering() {
+        let doc = parse_md_str("# Guide\n## Install\n### Linux\n## Use");
+        let toc = build_toc(&doc, &TocOptions{min_level: 2, max_level: 2});
+        assert_eq!(toc_to_markdown(&toc), "- [Install](#install)\n- [Use](#use)\n");
+        assert_eq!(
+            toc_to_html(&toc),
+            "<ul class=\"toc\"><li><a href=\"#install\">Install</a></li><li><a href=\"#use\">Use</a></li></ul>"
+        );
+        // Ids from other sources than the parser may hold any text.
+        assert_eq!(
+            toc_to_html(&[entry(1, "A <b>", "a<b>", vec![])]),
+            "<ul class=\"toc\"><li><a href=\"#a&lt;b&gt;\">A &lt;b&gt;</a></li></ul>"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::collections::BTreeMap;
 
 use crate::tree::*;
 
@@ -19,6 +20,7 @@ pub enum Token {
     Header(Header),
     Paragraph(Paragraph),
     List(List),
+    OrderedList(OrderedList),
     Code(Code),
     PlainText(PlainText),
     Italic(Italic),
@@ -26,8 +28,56 @@ pub enum Token {
     Link(Link),
     Bold(Bold),
     ListItem(ListItem),
+    DefinitionList(DefinitionList),
+    DefinitionTerm(DefinitionTerm),
+    Definition(Definition),
+    InlineMath(InlineMath),
+    DisplayMath(DisplayMath),
+    Admonition(Admonition),
+    Directive(Directive),
+    TableOfContents(TableOfContents),
+    Table(Table),
+    TableRow(TableRow),
+    TableCell(TableCell),
 }
 
+/**
+ Variant of a Token without its data, eg. for looking up per token configuration.
+*/
+#[derive(PartialEq, Eq, Hash)]
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum TokenKind {
+    Header,
+    Paragraph,
+    List,
+    OrderedList,
+    Code,
+    PlainText,
+    Italic,
+    InlineCode,
+    Link,
+    Bold,
+    ListItem,
+    DefinitionList,
+    DefinitionTerm,
+    Definition,
+    InlineMath,
+    DisplayMath,
+    Admonition,
+    Directive,
+    TableOfContents,
+    Table,
+    TableRow,
+    TableCell
+}
+
+/**
+ Attributes given in curly braces eg. `{#id .class key=value}`. Ids are stored under "id" and
+ classes are joined with spaces under "class".
+*/
+pub type Attributes = BTreeMap<String, String>;
+
 pub trait Leveled {
     fn level(&self) -> u32;
 }
@@ -50,32 +100,134 @@ pub trait TextComponent {
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct List {
-    pub level: usize
+    pub level: usize,
+    pub attributes: Attributes
 }
 
 #[derive(PartialEq)]
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct OrderedList {
+    pub level: usize,
+    // Number of the first item.
+    pub start: u32,
+    pub attributes: Attributes
 }
 
 #[derive(PartialEq)]
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct Header {
-    pub level: u32
+    pub level: u32,
+    pub attributes: Attributes
 }
 
 #[derive(PartialEq)]
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct Paragraph {
+    pub attributes: Attributes
 }
 
 #[derive(PartialEq)]
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct Code {
+    pub lang: Option<String>,
+    pub attributes: Attributes
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct DefinitionList {
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct DefinitionTerm {
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Definition {
+}
+
+// `[TOC]` or `[[toc]]` placeholder, replaced by the table of contents when compiled.
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct TableOfContents {
+}
+
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum Alignment {
+    None,
+    Left,
+    Center,
+    Right
+}
+
+/**
+ GFM table, the alignments of its columns are given by the delimiter row below the header.
+*/
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Table {
+    pub alignments: Vec<Alignment>
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct TableRow {
+    pub header: bool
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct TableCell {
+}
+
+#[derive(PartialEq, Eq, Hash)]
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum AdmonitionKind {
+    Note,
+    Tip,
+    Info,
+    Important,
+    Warning,
+    Caution,
+    Danger
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Admonition {
+    pub kind: AdmonitionKind,
+    pub title: Option<String>,
+    pub attributes: Attributes
+}
+
+/**
+ Generic directive. Either a `:::name{attrs}` container with block content or an inline
+ `:name[content]{attrs}`.
+*/
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Directive {
+    pub name: String,
+    pub attributes: Attributes,
+    pub inline: bool
 }
 
 // Lower level / Inline
@@ -118,6 +270,22 @@ pub struct Link {
 pub struct ListItem {
 }
 
+// TeX source is kept verbatim, math has no children.
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct InlineMath {
+    pub tex: String
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct DisplayMath {
+    pub tex: String
+}
+
 // ----------------------------------------------------------------------------
 // TRAIT IMPLEMENTATIONS FOR TYPES
 // ----------------------------------------------------------------------------
@@ -135,12 +303,133 @@ impl Leveled for List {
     }
 }
 
+impl Leveled for OrderedList {
+    fn level(&self) -> u32 {
+        self.level as u32
+    }
+}
+
+impl Token {
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Token::Header(_) => TokenKind::Header,
+            Token::Paragraph(_) => TokenKind::Paragraph,
+            Token::List(_) => TokenKind::List,
+            Token::OrderedList(_) => TokenKind::OrderedList,
+            Token::Code(_) => TokenKind::Code,
+            Token::PlainText(_) => TokenKind::PlainText,
+            Token::Italic(_) => TokenKind::Italic,
+            Token::InlineCode(_) => TokenKind::InlineCode,
+            Token::Link(_) => TokenKind::Link,
+            Token::Bold(_) => TokenKind::Bold,
+            Token::ListItem(_) => TokenKind::ListItem,
+            Token::DefinitionList(_) => TokenKind::DefinitionList,
+            Token::DefinitionTerm(_) => TokenKind::DefinitionTerm,
+            Token::Definition(_) => TokenKind::Definition,
+            Token::InlineMath(_) => TokenKind::InlineMath,
+            Token::DisplayMath(_) => TokenKind::DisplayMath,
+            Token::Admonition(_) => TokenKind::Admonition,
+            Token::Directive(_) => TokenKind::Directive,
+            Token::TableOfContents(_) => TokenKind::TableOfContents,
+            Token::Table(_) => TokenKind::Table,
+            Token::TableRow(_) => TokenKind::TableRow,
+            Token::TableCell(_) => TokenKind::TableCell,
+        }
+    }
+
+    // Block tokens take up whole lines, inline tokens are part of the text of a block.
+    pub fn is_block(&self) -> bool {
+        match self {
+            Token::Header(_) | Token::Paragraph(_) | Token::List(_) | Token::OrderedList(_) | Token::ListItem(_)
+            | Token::Code(_) | Token::DefinitionList(_) | Token::DefinitionTerm(_)
+            | Token::Definition(_) | Token::Admonition(_) | Token::TableOfContents(_) | Token::Table(_)
+            | Token::TableRow(_) | Token::TableCell(_) => true,
+            Token::Directive(d) => !d.inline,
+            _ => false
+        }
+    }
+
+    // Blocks that hold other blocks rather than text.
+    pub fn is_container(&self) -> bool {
+        match self {
+            Token::List(_) | Token::OrderedList(_) | Token::DefinitionList(_) | Token::Admonition(_)
+            | Token::Table(_) | Token::TableRow(_) => true,
+            Token::Directive(d) => !d.inline,
+            _ => false
+        }
+    }
+
+    // Bulleted or numbered list.
+    pub fn is_list(&self) -> bool {
+        return matches!(self, Token::List(_) | Token::OrderedList(_));
+    }
+}
+
+impl Alignment {
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Alignment::None => None,
+            Alignment::Left => Some("left"),
+            Alignment::Center => Some("center"),
+            Alignment::Right => Some("right")
+        }
+    }
+}
+
+impl AdmonitionKind {
+    pub fn from_name(name: &str) -> Option<AdmonitionKind> {
+        match name.to_lowercase().as_str() {
+            "note" => Some(AdmonitionKind::Note),
+            "tip" => Some(AdmonitionKind::Tip),
+            "info" => Some(AdmonitionKind::Info),
+            "important" => Some(AdmonitionKind::Important),
+            "warning" => Some(AdmonitionKind::Warning),
+            "caution" => Some(AdmonitionKind::Caution),
+            "danger" => Some(AdmonitionKind::Danger),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AdmonitionKind::Note => "note",
+            AdmonitionKind::Tip => "tip",
+            AdmonitionKind::Info => "info",
+            AdmonitionKind::Important => "important",
+            AdmonitionKind::Warning => "warning",
+            AdmonitionKind::Caution => "caution",
+            AdmonitionKind::Danger => "danger"
+        }
+    }
+}
+
+impl Admonition {
+    // Title to display, defaults to the capitalized kind eg. "Warning".
+    pub fn display_title(&self) -> String {
+        if let Some(title) = &self.title { return title.clone() }
+        let name = self.kind.name();
+        return name[..1].to_uppercase() + &name[1..];
+    }
+}
+
 impl TextComponent for PlainText {
     fn text(&self) -> String {
         self.text.clone()
     }
 }
 
+impl Src for InlineMath {
+    fn src(&self) -> String {
+        self.tex.clone()
+    }
+}
+
+impl Src for DisplayMath {
+    fn src(&self) -> String {
+        self.tex.clone()
+    }
+}
+
 // TextComponents are generally inline and do not have children
 
 // ----------------------------------------------------------------------------
@@ -156,11 +445,23 @@ impl fmt::Debug for Token {
             Token::Italic(t) => return t.fmt(f),
             Token::Link(t) => return t.fmt(f),
             Token::List(t) => return t.fmt(f),
+            Token::OrderedList(t) => return t.fmt(f),
             Token::Bold(t) => return t.fmt(f),
             Token::Paragraph(t) => return t.fmt(f),
             Token::InlineCode(t) => return t.fmt(f),
             Token::ListItem(t) => return t.fmt(f),
-            Token::Code(t) => return t.fmt(f)
+            Token::Code(t) => return t.fmt(f),
+            Token::DefinitionList(t) => return t.fmt(f),
+            Token::DefinitionTerm(t) => return t.fmt(f),
+            Token::Definition(t) => return t.fmt(f),
+            Token::InlineMath(t) => return t.fmt(f),
+            Token::DisplayMath(t) => return t.fmt(f),
+            Token::Admonition(t) => return t.fmt(f),
+            Token::Directive(t) => return t.fmt(f),
+            Token::TableOfContents(t) => return t.fmt(f),
+            Token::Table(t) => return t.fmt(f),
+            Token::TableRow(t) => return t.fmt(f),
+            Token::TableCell(t) => return t.fmt(f),
         }
     }
 }
@@ -1,4 +1,4 @@
-use std::{usize, collections::VecDeque, ops::Index, fmt::Display, fmt::Debug};
+use std::{collections::VecDeque, ops::Index, fmt::Display, fmt::Debug};
 
 #[derive(Debug)]
 pub struct Node<T> {
@@ -8,6 +8,13 @@ pub struct Node<T> {
     idx: usize
 }
 
+impl<T> Node<T> {
+    // Index of the node in the tree's arena, see TreeIndex::Arena.
+    pub fn index(&self) -> usize {
+        self.idx
+    }
+}
+
 #[derive(Debug)]
 pub struct NodePosition {
     pub depth: usize,
@@ -39,7 +46,7 @@ impl<T> Display for Tree<T> where T: PartialEq + Debug {
                         &format!("{}{}{}",
                             " ".repeat(node.level),
                             if node.level > 0 {"└"} else {"─"},
-                            if node.children.len() > 0 {"┬"} else {"─"}
+                            if !node.children.is_empty() {"┬"} else {"─"}
                         ).to_string() +
                         &format!("─ {:?}\n",node.val).to_string();
                     acc
@@ -87,7 +94,7 @@ impl<'a,T> Iterator for TreeNodeIter<'a,T> {
             } else {
                 self.idx_queue.extend(&found.children);
             }
-            return Some(&self.arena[n_i]);
+            return Some(self.arena[n_i]);
         }
         return None;
     }
@@ -104,7 +111,7 @@ impl<T> Index<TreeIndex> for Tree<T>
 where T: PartialEq {
     type Output = T;
     fn index(&self, index: TreeIndex) -> &Self::Output {
-        return &self.get(index).unwrap();
+        return self.get(index).unwrap();
     }
 }
 
@@ -119,25 +126,32 @@ where T: PartialEq {
     }
 
     pub fn iter_bfs(&self) -> TreeIter<'_,T> {
-        let references: Vec<&Node<T>> = self.nodes.iter().map(|owned| owned).collect();
+        let references: Vec<&Node<T>> = self.nodes.iter().collect();
         return  TreeIter{dfs: false, idx_queue : VecDeque::from([0]), arena: references };
     }
 
     pub fn iter_dfs(&self) -> TreeIter<'_,T> {
-        let references: Vec<&Node<T>> = self.nodes.iter().map(|owned| owned).collect();
+        let references: Vec<&Node<T>> = self.nodes.iter().collect();
         return  TreeIter{dfs: true, idx_queue : VecDeque::from([0]), arena: references };
     }
 
     pub fn nodes_bfs(&self) -> TreeNodeIter<'_,T> {
-        let references: Vec<&Node<T>> = self.nodes.iter().map(|owned| owned).collect();
+        let references: Vec<&Node<T>> = self.nodes.iter().collect();
         return  TreeNodeIter{dfs: false, idx_queue : VecDeque::from([0]), arena: references };
     }
 
     pub fn nodes_dfs(&self) -> TreeNodeIter<'_,T> {
-        let references: Vec<&Node<T>> = self.nodes.iter().map(|owned| owned).collect();
+        let references: Vec<&Node<T>> = self.nodes.iter().collect();
         return  TreeNodeIter{dfs: true, idx_queue : VecDeque::from([0]), arena: references };
     }
 
+    // Depth first iteration of the subtree starting from a node, including the node itself.
+    pub fn subtree_dfs(&self, index: TreeIndex) -> TreeIter<'_,T> {
+        let references: Vec<&Node<T>> = self.nodes.iter().collect();
+        let start = self.get_node(index).map(|n| VecDeque::from([n.idx])).unwrap_or_default();
+        return  TreeIter{dfs: true, idx_queue : start, arena: references };
+    }
+
 
     pub fn get_node(&self, index: TreeIndex) -> Option<&Node<T>> {
         match index {
@@ -148,7 +162,7 @@ where T: PartialEq {
                     if i_count == bfs_i { return self.nodes.get(n_i) };
                     let found = &self.nodes[n_i].children;
                     queue.extend(found);
-                    i_count = i_count+1;
+                    i_count += 1;
                 }
                 return None;
             }
@@ -170,7 +184,7 @@ where T: PartialEq {
                     if i_count == bfs_i { return self.nodes.get_mut(n_i)}
                     let found = &self.nodes[n_i].children;
                     queue.extend(found);
-                    i_count = i_count+1;
+                    i_count += 1;
                 }
                 return None;
             }
@@ -186,12 +200,10 @@ where T: PartialEq {
 
     fn find_mut_node(&mut self, comp: &T) -> Option<&mut Node<T>> {
         let mut queue: VecDeque<usize> = VecDeque::from([0]);
-        let mut i_count = 0;
         while let Some(n_i) = queue.pop_front() {
             if &self.nodes[n_i].val == comp { return self.nodes.get_mut(n_i)}
             let found = &self.nodes[n_i].children;
             queue.extend(found);
-            i_count = i_count+1;
         }
         return None;
     }
@@ -238,6 +250,8 @@ where T: PartialEq {
 }
 
 #[cfg(test)]
+// The iteration tests predate clippy and are kept as written.
+#[allow(clippy::map_clone)]
 mod tree_t {
     use super::*;
 
@@ -280,6 +294,14 @@ mod tree_t {
         assert_eq!(collected, vals);
     }
 
+    #[test]
+    fn subtree_iteration() {
+        let tree: Tree<&str> = make_tree();
+        let collected: Vec<&str> = tree.subtree_dfs(TreeIndex::Arena(2)).copied().collect();
+        assert_eq!(collected, ["c","f","g"]);
+        assert_eq!(tree.subtree_dfs(TreeIndex::Arena(10)).count(), 0);
+    }
+
     #[test]
     fn mutation() {
         let mut tree: Tree<&str> = make_tree();